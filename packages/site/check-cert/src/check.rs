@@ -186,16 +186,34 @@ where
     }
 
     pub fn check(&self, value: T, output: OutputType, args: LevelsCheckerArgs) -> CheckResult<T> {
-        let evaluate = |value: &T| -> State {
-            if self.strategy.cmp(value, &self.levels.crit) {
-                State::Crit
-            } else if self.strategy.cmp(value, &self.levels.warn) {
-                State::Warn
-            } else {
-                State::Ok
-            }
+        let SimpleCheckResult {
+            state,
+            summary,
+            details,
+        } = self.check_without_metric(&value, output);
+        CheckResult {
+            state,
+            summary,
+            details,
+            metrics: Some(Metric::<T> {
+                label: args.label,
+                value,
+                uom: args.uom,
+                levels: Some(self.levels.clone()),
+                bounds: None,
+            }),
+        }
+    }
+
+    /// Evaluate the levels for values that don't deserve a metric of their own.
+    pub fn check_without_metric(&self, value: &T, output: OutputType) -> SimpleCheckResult {
+        let state = if self.strategy.cmp(value, &self.levels.crit) {
+            State::Crit
+        } else if self.strategy.cmp(value, &self.levels.warn) {
+            State::Warn
+        } else {
+            State::Ok
         };
-        let state = evaluate(&value);
         // According to documentation the details default to the summary.
         // see: plugin-api/cmk.agent_based/v2.html#cmk.agent_based.v2.Result
         let (summary, details) = match (output, state) {
@@ -210,17 +228,10 @@ where
                 (Some(text), None)
             }
         };
-        CheckResult {
+        SimpleCheckResult {
             state,
             summary,
             details,
-            metrics: Some(Metric::<T> {
-                label: args.label,
-                value,
-                uom: args.uom,
-                levels: Some(self.levels.clone()),
-                bounds: None,
-            }),
         }
     }
 }
@@ -1131,4 +1142,17 @@ mod test_writer_format {
             "notice (warn/crit at 10/20) (!) | label=15ms;10;20;;\nnotice (warn/crit at 10/20) (!)"
         );
     }
+
+    #[test]
+    fn test_collection_levels_checker_without_metric() {
+        let levels =
+            LevelsChecker::try_new(LevelsStrategy::Upper, Levels { warn: 10, crit: 20 }).unwrap();
+        let check = levels.check_without_metric(&25, OutputType::Notice("notice".to_string()));
+        let coll = Collection::from(&mut vec![CheckResult::<Real>::from(check)]);
+        assert_eq!(coll.state, State::Crit);
+        assert_eq!(
+            format!("{}", coll),
+            "notice (warn/crit at 10/20) (!!)\nnotice (warn/crit at 10/20) (!!)"
+        );
+    }
 }
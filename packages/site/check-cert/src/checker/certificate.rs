@@ -12,7 +12,9 @@ use time::Duration;
 use typed_builder::TypedBuilder;
 use x509_parser::certificate::{BasicExtension, Validity, X509Certificate};
 use x509_parser::error::X509Error;
-use x509_parser::extensions::{GeneralName, SubjectAlternativeName};
+use x509_parser::extensions::{
+    BasicConstraints, ExtendedKeyUsage, GeneralName, KeyUsage, SubjectAlternativeName,
+};
 use x509_parser::prelude::FromDer;
 use x509_parser::prelude::{oid2sn, oid_registry};
 use x509_parser::public_key::PublicKey;
@@ -53,8 +55,27 @@ pub struct Config {
     issuer_c: Option<String>,
     not_after: Option<LevelsChecker<Duration>>,
     max_validity: Option<Duration>,
+    /// Expected key usages as named in RFC 5280, e.g. `digitalSignature`
+    key_usage: Option<Vec<String>>,
+    /// Expected extended key usages, e.g. `serverAuth`, or their OIDs
+    extended_key_usage: Option<Vec<String>>,
+    /// Expected CA flag of the basic constraints
+    basic_constraints_ca: Option<bool>,
 }
 
+// Same order as the bits of the key usage (RFC 5280, 4.2.1.3)
+static KEY_USAGE_NAMES: [&str; 9] = [
+    "digitalSignature",
+    "nonRepudiation",
+    "keyEncipherment",
+    "dataEncipherment",
+    "keyAgreement",
+    "keyCertSign",
+    "cRLSign",
+    "encipherOnly",
+    "decipherOnly",
+];
+
 fn handle_empty(s: &str) -> &str {
     if s.trim().is_empty() {
         "empty"
@@ -177,6 +198,9 @@ pub fn check(der: &[u8], config: Config) -> Collection {
         )
         .map(|cr: CheckResult<Duration>| cr.map(|x| Real::from(x.whole_seconds() as isize))),
         check_max_validity(cert.validity(), config.max_validity),
+        check_key_usage(cert.key_usage(), config.key_usage),
+        check_extended_key_usage(cert.extended_key_usage(), config.extended_key_usage),
+        check_basic_constraints(cert.basic_constraints(), config.basic_constraints_ca),
     ))
 }
 
@@ -355,6 +379,124 @@ fn check_max_validity(
     })
 }
 
+// `any` is the usage that permits all others, if there is one. Then a missing extension
+// doesn't restrict the usage either (RFC 5280, 4.2.1.12).
+fn check_usage(
+    name: &str,
+    found: Result<Option<Vec<String>>, X509Error>,
+    expected: Option<Vec<String>>,
+    any: Option<&str>,
+) -> Option<SimpleCheckResult> {
+    expected.map(|expected| match found {
+        Err(err) => SimpleCheckResult::crit(format!("{name}: {err}")),
+        Ok(None) if any.is_some() => {
+            SimpleCheckResult::notice(format!("No {} (unrestricted)", name.to_lowercase()))
+        }
+        Ok(None) => {
+            if expected.is_empty() {
+                SimpleCheckResult::notice(format!("No {}", name.to_lowercase()))
+            } else {
+                SimpleCheckResult::warn(format!("No {}", name.to_lowercase()))
+            }
+        }
+        Ok(Some(found)) => {
+            let unrestricted = any.is_some_and(|any| found.iter().any(|usage| usage == any));
+            let missing = expected
+                .iter()
+                .filter(|usage| !unrestricted && !found.contains(usage))
+                .map(|usage| usage.as_str())
+                .collect::<Vec<_>>();
+            if missing.is_empty() {
+                SimpleCheckResult::notice(format!("{name}: {}", found.join(", ")))
+            } else {
+                SimpleCheckResult::warn_with_details(
+                    format!("{name}: missing {}", missing.join(", ")),
+                    format!(
+                        "{name}: {} but expected {}",
+                        handle_empty(&found.join(", ")),
+                        expected.join(", ")
+                    ),
+                )
+            }
+        }
+    })
+}
+
+fn check_key_usage(
+    key_usage: Result<Option<BasicExtension<&KeyUsage>>, X509Error>,
+    expected: Option<Vec<String>>,
+) -> Option<SimpleCheckResult> {
+    check_usage(
+        "Key usage",
+        key_usage.map(|ext| {
+            ext.map(|ext| {
+                KEY_USAGE_NAMES
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| ext.value.flags >> bit & 1 == 1)
+                    .map(|(_, name)| String::from(*name))
+                    .collect()
+            })
+        }),
+        expected,
+        None,
+    )
+}
+
+fn check_extended_key_usage(
+    extended_key_usage: Result<Option<BasicExtension<&ExtendedKeyUsage>>, X509Error>,
+    expected: Option<Vec<String>>,
+) -> Option<SimpleCheckResult> {
+    check_usage(
+        "Extended key usage",
+        extended_key_usage.map(|ext| {
+            ext.map(|ext| {
+                let eku = ext.value;
+                [
+                    (eku.server_auth, "serverAuth"),
+                    (eku.client_auth, "clientAuth"),
+                    (eku.code_signing, "codeSigning"),
+                    (eku.email_protection, "emailProtection"),
+                    (eku.time_stamping, "timeStamping"),
+                    (eku.ocsp_signing, "OCSPSigning"),
+                    (eku.any, "anyExtendedKeyUsage"),
+                ]
+                .into_iter()
+                .filter(|(present, _)| *present)
+                .map(|(_, name)| String::from(name))
+                .chain(eku.other.iter().map(|oid| oid.to_id_string()))
+                .collect()
+            })
+        }),
+        expected,
+        Some("anyExtendedKeyUsage"),
+    )
+}
+
+fn check_basic_constraints(
+    basic_constraints: Result<Option<BasicExtension<&BasicConstraints>>, X509Error>,
+    expected: Option<bool>,
+) -> Option<SimpleCheckResult> {
+    let name = "Basic constraints";
+    let to_str = |ca: bool| if ca { "CA:TRUE" } else { "CA:FALSE" };
+    expected.map(|expected| match basic_constraints {
+        Err(err) => SimpleCheckResult::crit(format!("{name}: {err}")),
+        Ok(ext) => {
+            // A missing extension means that the certificate is not a CA.
+            let ca = ext.map_or(false, |ext| ext.value.ca);
+            if ca == expected {
+                SimpleCheckResult::notice(format!("{name}: {}", to_str(ca)))
+            } else {
+                SimpleCheckResult::warn(format!(
+                    "{name}: {} but expected {}",
+                    to_str(ca),
+                    to_str(expected)
+                ))
+            }
+        }
+    })
+}
+
 #[cfg(test)]
 mod test_check_serial {
     use super::{check_serial, SimpleCheckResult};
@@ -0,0 +1,179 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::check::{
+    CheckResult, Collection, LevelsChecker, Metric, OutputType, Real, SimpleCheckResult,
+};
+use crate::truststore;
+use openssl::x509::X509;
+use time::Duration;
+use typed_builder::TypedBuilder;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::prelude::FromDer;
+use x509_parser::prelude::{oid2sn, oid_registry};
+use x509_parser::public_key::PublicKey;
+use x509_parser::time::ASN1Time;
+
+// MD2, MD5 and SHA-1 with RSA, DSA and ECDSA
+static WEAK_SIGNATURE_ALGORITHMS: [&str; 5] = [
+    "1.2.840.113549.1.1.2",
+    "1.2.840.113549.1.1.4",
+    "1.2.840.113549.1.1.5",
+    "1.2.840.10040.4.3",
+    "1.2.840.10045.4.1",
+];
+
+#[derive(Debug, TypedBuilder)]
+#[builder(field_defaults(default))]
+pub struct Config<'a> {
+    #[builder(!default)]
+    trust_store: &'a [Vec<u8>],
+    /// Expiration levels for the intermediate and root certificates
+    not_after: Option<LevelsChecker<Duration>>,
+    /// Minimum size of RSA and DSA keys
+    pubkey_min_size: Option<usize>,
+    /// Also evaluate the root certificate, from the chain or the trust store
+    include_root: bool,
+}
+
+/// Evaluate the certificates of the chain beyond the leaf.
+pub fn check(chain: &[Vec<u8>], config: Config) -> Collection {
+    assert!(!chain.is_empty());
+
+    let mut ders = chain.to_vec();
+    if config.include_root {
+        if let Some(root) = find_root(chain, config.trust_store) {
+            ders.push(root);
+        }
+    }
+    let certs = ders
+        .iter()
+        .flat_map(|der| X509Certificate::from_der(der).map(|(_rem, cert)| cert))
+        .collect::<Vec<_>>();
+
+    let mut check_results: Vec<CheckResult<Real>> = vec![];
+    let mut evaluated = vec![];
+    for (index, cert) in certs.iter().enumerate() {
+        let is_root = cert.subject() == cert.issuer();
+        if index != 0 && is_root && !config.include_root {
+            continue;
+        }
+        evaluated.push(cert);
+        if index == 0 {
+            // The leaf is up to `checker::certificate`.
+            continue;
+        }
+        let name = format!(
+            "{} certificate {}",
+            if is_root { "Root" } else { "Intermediate" },
+            common_name(cert)
+        );
+        check_results.extend(
+            [
+                Some(check_not_after(&name, cert, config.not_after.as_ref())),
+                // Nobody relies on the signature of a root.
+                (!is_root).then(|| check_signature_algorithm(&name, cert)),
+                Some(check_pubkey_size(&name, cert, config.pubkey_min_size)),
+            ]
+            .into_iter()
+            .flatten()
+            .map(Into::into),
+        );
+    }
+    check_results.extend(check_earliest_expiry(&evaluated));
+    Collection::from(&mut check_results)
+}
+
+fn find_root(chain: &[Vec<u8>], trust_store: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let last = X509::from_der(chain.last()?).ok()?;
+    if last.subject_name().to_der().ok()? == last.issuer_name().to_der().ok()? {
+        // The server sent the root along.
+        return None;
+    }
+    truststore::find_issuer(&last, &[], trust_store)?
+        .to_der()
+        .ok()
+}
+
+fn common_name<'a>(cert: &'a X509Certificate) -> &'a str {
+    cert.subject()
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .unwrap_or("without CN")
+}
+
+fn check_not_after(
+    name: &str,
+    cert: &X509Certificate,
+    levels: Option<&LevelsChecker<Duration>>,
+) -> SimpleCheckResult {
+    let Some(time_to_expiration) = cert.validity().time_to_expiration() else {
+        return SimpleCheckResult::crit(format!("{name} expired ({})", cert.validity().not_after));
+    };
+    let text = format!(
+        "{name} validity: {} day(s)",
+        time_to_expiration.whole_days()
+    );
+    match levels {
+        // Only the earliest expiry in the chain gets a metric.
+        Some(levels) => levels.check_without_metric(&time_to_expiration, OutputType::Notice(text)),
+        None => SimpleCheckResult::notice(text),
+    }
+}
+
+fn check_signature_algorithm(name: &str, cert: &X509Certificate) -> SimpleCheckResult {
+    let oid = &cert.signature_algorithm.algorithm;
+    let algorithm = match oid2sn(oid, oid_registry()) {
+        Ok(s) => s.to_owned(),
+        _ => format!("{oid}"),
+    };
+    if WEAK_SIGNATURE_ALGORITHMS.contains(&oid.to_id_string().as_str()) {
+        SimpleCheckResult::warn(format!("{name} signature algorithm: {algorithm} is weak"))
+    } else {
+        SimpleCheckResult::notice(format!("{name} signature algorithm: {algorithm}"))
+    }
+}
+
+fn check_pubkey_size(
+    name: &str,
+    cert: &X509Certificate,
+    min_size: Option<usize>,
+) -> SimpleCheckResult {
+    let (algorithm, size) = match cert.public_key().parsed() {
+        Ok(PublicKey::RSA(rsa)) => ("RSA", rsa.key_size()),
+        Ok(PublicKey::DSA(k)) => ("DSA", 8 * k.len()),
+        Ok(PublicKey::EC(ec)) => ("EC", ec.key_size()),
+        Ok(PublicKey::GostR3410(k)) | Ok(PublicKey::GostR3410_2012(k)) => ("GOST", 8 * k.len()),
+        Ok(PublicKey::Unknown(k)) => ("Unknown", 8 * k.len()),
+        Err(_) => return SimpleCheckResult::warn(format!("{name}: Invalid public key")),
+    };
+    match min_size {
+        Some(min_size) if matches!(algorithm, "RSA" | "DSA") && size < min_size => {
+            SimpleCheckResult::warn(format!(
+                "{name} public key: {algorithm} {size} bit(s) but expected at least {min_size}"
+            ))
+        }
+        _ => SimpleCheckResult::notice(format!("{name} public key: {algorithm} {size} bit(s)")),
+    }
+}
+
+fn check_earliest_expiry(certs: &[&X509Certificate]) -> Option<CheckResult<Real>> {
+    let cert = certs
+        .iter()
+        .min_by_key(|cert| cert.validity().not_after.timestamp())?;
+    let remaining = cert.validity().not_after.timestamp() - ASN1Time::now().timestamp();
+    Some(CheckResult::notice(
+        format!(
+            "Earliest expiry in chain: {} on {}",
+            common_name(cert),
+            cert.validity().not_after
+        ),
+        Metric::builder()
+            .label("certificate_chain_remaining_validity")
+            .value(Real::from(remaining as isize))
+            .uom("s".parse().unwrap())
+            .build(),
+    ))
+}
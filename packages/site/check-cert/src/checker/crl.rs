@@ -0,0 +1,160 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::check::{CheckResult, Collection, Real, SimpleCheckResult};
+use crate::crl::{Crl, Crls};
+use crate::truststore;
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::nid::Nid;
+use openssl::x509::{CrlStatus, ReasonCode, X509Crl, X509Ref, X509VerifyResult, X509};
+use std::cmp::Ordering;
+use time::OffsetDateTime;
+use typed_builder::TypedBuilder;
+use x509_parser::time::ASN1Time;
+use x509_parser::x509::ReasonCode as Reason;
+
+#[derive(Debug, TypedBuilder)]
+pub struct Config<'a> {
+    trust_store: &'a [Vec<u8>],
+    /// Whether the CRLs were fetched from the distribution points.
+    /// Then a missing CRL is an error for every certificate, not only for those with a
+    /// distribution point.
+    #[builder(default)]
+    fetched: bool,
+}
+
+pub fn check(chain: &[Vec<u8>], crls: &Crls, config: Config) -> Collection {
+    assert!(!chain.is_empty());
+
+    let now = Asn1Time::days_from_now(0).unwrap();
+    let parsed = crls
+        .crls
+        .iter()
+        .flat_map(|crl| X509Crl::from_der(&crl.der).map(|parsed| (crl, parsed)))
+        .collect::<Vec<_>>();
+
+    let mut check_results: Vec<CheckResult<Real>> = crls
+        .errors
+        .iter()
+        .map(|err| SimpleCheckResult::unknown(format!("CRL: {err}")).into())
+        .collect();
+    for (index, der) in chain.iter().enumerate() {
+        let Ok(cert) = X509::from_der(der) else {
+            continue;
+        };
+        // Nobody revokes a root.
+        if cert.issued(&cert) == X509VerifyResult::OK {
+            continue;
+        }
+        check_results.extend(
+            check_cert(
+                &cert,
+                &chain[index + 1..],
+                config.trust_store,
+                &parsed,
+                &now,
+                config.fetched,
+            )
+            .into_iter()
+            .map(Into::into),
+        );
+    }
+    Collection::from(&mut check_results)
+}
+
+fn check_cert(
+    cert: &X509Ref,
+    intermediates: &[Vec<u8>],
+    trust_store: &[Vec<u8>],
+    crls: &[(&Crl, X509Crl)],
+    now: &Asn1TimeRef,
+    fetched: bool,
+) -> Vec<SimpleCheckResult> {
+    let name = common_name(cert);
+    let Some(issuer) = truststore::find_issuer(cert, intermediates, trust_store) else {
+        return vec![SimpleCheckResult::unknown(format!(
+            "CRL: Cannot find issuer of {name}"
+        ))];
+    };
+    let Ok(key) = issuer.public_key() else {
+        return vec![SimpleCheckResult::unknown(format!(
+            "CRL: Invalid public key in issuer of {name}"
+        ))];
+    };
+    // Only CRLs signed by the issuer count.
+    let crls = crls
+        .iter()
+        .filter(|(_, crl)| {
+            crl.issuer_name().try_cmp(cert.issuer_name()).ok() == Some(Ordering::Equal)
+                && crl.verify(&key).unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    if crls.is_empty() {
+        // Without a distribution point, nobody expects a CRL for the certificate,
+        // e.g. for an intermediate when only the CRL of a private CA is given.
+        let text = format!("CRL: No CRL for {name}");
+        return vec![if fetched || cert.crl_distribution_points().is_some() {
+            SimpleCheckResult::unknown(text)
+        } else {
+            SimpleCheckResult::notice(text)
+        }];
+    }
+
+    let mut revoked_by = vec![];
+    let mut outdated = vec![];
+    for (source, crl) in &crls {
+        if let CrlStatus::Revoked(revoked) = crl.get_by_cert(&cert.to_owned()) {
+            let date = format_time(revoked.revocation_date());
+            let reason = revoked
+                .extension::<ReasonCode>()
+                .ok()
+                .flatten()
+                .and_then(|(_, code)| code.get_i64().ok())
+                .map_or(String::from("unspecified"), |code| {
+                    Reason(code as u8).to_string()
+                });
+            revoked_by.push(SimpleCheckResult::crit_with_details(
+                format!("CRL: {name} revoked on {date}"),
+                format!(
+                    "CRL: {name} revoked on {date}, reason: {reason} ({})",
+                    source.source
+                ),
+            ));
+        }
+        if let Some(next_update) = crl.next_update() {
+            if next_update.compare(now).ok() == Some(Ordering::Less) {
+                outdated.push(SimpleCheckResult::warn(format!(
+                    "CRL: {} outdated (next update was due on {})",
+                    source.source,
+                    format_time(next_update)
+                )));
+            }
+        }
+    }
+    if revoked_by.is_empty() {
+        revoked_by.push(SimpleCheckResult::notice(format!(
+            "CRL: {name} not revoked"
+        )));
+    }
+    revoked_by.extend(outdated);
+    revoked_by
+}
+
+fn common_name(cert: &X509Ref) -> String {
+    cert.subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .next()
+        .and_then(|entry| entry.data().as_utf8().ok())
+        .map_or(String::from("certificate"), |cn| cn.to_string())
+}
+
+fn format_time(time: &Asn1TimeRef) -> String {
+    Asn1Time::from_unix(0)
+        .and_then(|epoch| epoch.diff(time))
+        .ok()
+        .and_then(|diff| {
+            OffsetDateTime::from_unix_timestamp(diff.days as i64 * 86400 + diff.secs as i64).ok()
+        })
+        .map_or(time.to_string(), |time| ASN1Time::from(time).to_string())
+}
@@ -0,0 +1,109 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::check::{
+    CheckResult, Collection, LevelsChecker, LevelsCheckerArgs, OutputType, Real, SimpleCheckResult,
+};
+use crate::ct::{self, Log, Sct};
+use crate::truststore;
+use anyhow::Result;
+use openssl::base64;
+use openssl::x509::X509;
+use time::OffsetDateTime;
+use typed_builder::TypedBuilder;
+use x509_parser::time::ASN1Time;
+
+#[derive(Debug, TypedBuilder)]
+#[builder(field_defaults(default))]
+pub struct Config<'a> {
+    #[builder(!default)]
+    trust_store: &'a [Vec<u8>],
+    /// Known logs from the log list
+    #[builder(!default)]
+    logs: &'a [Log],
+    /// Levels on the number of distinct logs with a valid SCT
+    valid_scts: Option<LevelsChecker<usize>>,
+}
+
+/// Verify the SCTs of the server certificate against the known logs.
+pub fn check(chain: &[Vec<u8>], scts: Result<Vec<Sct>>, config: Config) -> Collection {
+    assert!(!chain.is_empty());
+
+    let scts = match scts {
+        Ok(scts) => scts,
+        Err(err) => {
+            return Collection::from(SimpleCheckResult::unknown(format!("SCTs: {err:#}")));
+        }
+    };
+    // Only embedded SCTs sign the precertificate and require the issuer.
+    let issuer = X509::from_der(&chain[0])
+        .ok()
+        .and_then(|cert| truststore::find_issuer(&cert, &chain[1..], config.trust_store));
+
+    let mut check_results: Vec<CheckResult<Real>> = vec![];
+    // Several SCTs from the same log (e.g. embedded and via TLS) don't add any assurance.
+    let mut valid_logs: Vec<&[u8]> = vec![];
+    for sct in &scts {
+        let Some(log) = config.logs.iter().find(|log| log.id == sct.log_id) else {
+            check_results.push(
+                SimpleCheckResult::notice(format!(
+                    "SCT from unknown log {} ({})",
+                    base64::encode_block(&sct.log_id),
+                    sct.source
+                ))
+                .into(),
+            );
+            continue;
+        };
+        let name = format!("SCT from {} ({})", log.description, sct.source);
+        check_results.push(
+            match ct::verify(sct, log, &chain[0], issuer.as_deref()) {
+                Ok(true) => {
+                    if !valid_logs.contains(&sct.log_id.as_slice()) {
+                        valid_logs.push(&sct.log_id);
+                    }
+                    SimpleCheckResult::notice(format!(
+                        "{name}: valid, issued {}",
+                        format_timestamp(sct.timestamp)
+                    ))
+                }
+                Ok(false) => SimpleCheckResult::warn(format!("{name}: invalid signature")),
+                Err(err) => SimpleCheckResult::warn(format!("{name}: {err:#}")),
+            }
+            .into(),
+        );
+    }
+    if scts.is_empty() {
+        check_results.push(
+            SimpleCheckResult::notice(
+                "No SCTs in the certificate, the TLS extension or the OCSP response",
+            )
+            .into(),
+        );
+    }
+    let valid = valid_logs.len();
+    check_results.insert(
+        0,
+        match config.valid_scts {
+            None => SimpleCheckResult::notice(format!("Logs with valid SCTs: {valid}")).into(),
+            Some(levels) => levels
+                .check(
+                    valid,
+                    OutputType::Summary(format!("Logs with valid SCTs: {valid}")),
+                    LevelsCheckerArgs::builder()
+                        .label("certificate_valid_scts")
+                        .build(),
+                )
+                .map(|x| Real::from(x as isize)),
+        },
+    );
+    Collection::from(&mut check_results)
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    match OffsetDateTime::from_unix_timestamp((timestamp / 1000) as i64) {
+        Ok(time) => ASN1Time::from(time).to_string(),
+        Err(_) => format!("{timestamp}"),
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::check::{self, Collection, SimpleCheckResult};
+use std::net::IpAddr;
+use typed_builder::TypedBuilder;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::FromDer;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnMismatch {
+    Ok,
+    Warn,
+    #[default]
+    Crit,
+}
+
+#[derive(Debug, TypedBuilder)]
+#[builder(field_defaults(default))]
+pub struct Config {
+    #[builder(!default, setter(into))]
+    hostname: String,
+    /// Match the subject CN if the certificate has no DNS names
+    cn_fallback: bool,
+    on_mismatch: OnMismatch,
+}
+
+/// Verify that the certificate is valid for the host according to RFC 6125.
+pub fn check(der: &[u8], config: Config) -> Collection {
+    let cert = match X509Certificate::from_der(der) {
+        Ok((_rem, cert)) => cert,
+        Err(_) => check::abort("Failed to parse certificate"),
+    };
+
+    let hostname = config.hostname.trim_end_matches('.');
+    let mut dns_names = vec![];
+    let mut ip_addresses = vec![];
+    if let Ok(Some(ext)) = cert.subject_alternative_name() {
+        for name in &ext.value.general_names {
+            match name {
+                GeneralName::DNSName(name) => dns_names.push(name.to_string()),
+                GeneralName::IPAddress(bytes) => ip_addresses.extend(to_ip(bytes)),
+                _ => (),
+            }
+        }
+    }
+
+    let matched = match hostname.parse::<IpAddr>() {
+        // IP addresses must be in the SAN, DNS names and the CN do not count.
+        Ok(ip) => ip_addresses.contains(&ip).then(|| format!("IP:{ip}")),
+        Err(_) => {
+            let cn = match config.cn_fallback && dns_names.is_empty() {
+                true => cert
+                    .subject()
+                    .iter_common_name()
+                    .next()
+                    .and_then(|cn| cn.as_str().ok()),
+                false => None,
+            };
+            dns_names
+                .iter()
+                .find(|name| matches_dns_name(name, hostname))
+                .map(|name| format!("DNS:{name}"))
+                .or(cn
+                    .filter(|cn| matches_dns_name(cn, hostname))
+                    .map(|cn| format!("CN:{cn}")))
+        }
+    };
+
+    Collection::from(match matched {
+        Some(name) => {
+            SimpleCheckResult::notice(format!("Hostname {hostname} matches certificate ({name})"))
+        }
+        None => {
+            let summary = format!("Hostname {hostname} does not match certificate");
+            let names = dns_names
+                .iter()
+                .map(|name| format!("DNS:{name}"))
+                .chain(ip_addresses.iter().map(|ip| format!("IP:{ip}")))
+                .collect::<Vec<_>>();
+            let details = match names.is_empty() {
+                true => format!("{summary} (no subject alternative names)"),
+                false => format!("{summary} ({})", names.join(", ")),
+            };
+            match config.on_mismatch {
+                OnMismatch::Ok => SimpleCheckResult::ok_with_details(summary, details),
+                OnMismatch::Warn => SimpleCheckResult::warn_with_details(summary, details),
+                OnMismatch::Crit => SimpleCheckResult::crit_with_details(summary, details),
+            }
+        }
+    })
+}
+
+fn to_ip(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => <[u8; 4]>::try_from(bytes).ok().map(IpAddr::from),
+        16 => <[u8; 16]>::try_from(bytes).ok().map(IpAddr::from),
+        _ => None,
+    }
+}
+
+// Wildcards are only allowed as the complete leftmost label and match exactly one
+// label. They are not allowed directly below a top-level domain.
+fn matches_dns_name(pattern: &str, hostname: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.');
+    match pattern.strip_prefix("*.") {
+        None => pattern.eq_ignore_ascii_case(hostname),
+        Some(suffix) => {
+            if !suffix.contains('.') || suffix.contains('*') {
+                return false;
+            }
+            match hostname.split_once('.') {
+                Some((label, rest)) => !label.is_empty() && rest.eq_ignore_ascii_case(suffix),
+                None => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_matches_dns_name {
+    use super::matches_dns_name;
+
+    #[test]
+    fn test_exact() {
+        assert!(matches_dns_name("www.example.com", "www.example.com"));
+        assert!(matches_dns_name("WWW.Example.com", "www.example.COM"));
+        assert!(matches_dns_name("www.example.com.", "www.example.com"));
+        assert!(!matches_dns_name("www.example.com", "example.com"));
+        assert!(!matches_dns_name("example.com", "www.example.com"));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        assert!(matches_dns_name("*.example.com", "www.example.com"));
+        assert!(matches_dns_name("*.example.com", "WWW.EXAMPLE.COM"));
+        assert!(!matches_dns_name("*.example.com", "example.com"));
+        assert!(!matches_dns_name("*.example.com", ".example.com"));
+        assert!(!matches_dns_name("*.example.com", "a.b.example.com"));
+        assert!(!matches_dns_name("*.example.com", "www.example.org"));
+    }
+
+    #[test]
+    fn test_invalid_wildcard() {
+        assert!(!matches_dns_name("*.com", "example.com"));
+        assert!(!matches_dns_name("w*.example.com", "www.example.com"));
+        assert!(!matches_dns_name("www.*.com", "www.example.com"));
+        assert!(!matches_dns_name("*.*.com", "www.example.com"));
+        assert!(!matches_dns_name("*", "localhost"));
+    }
+}
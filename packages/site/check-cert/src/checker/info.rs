@@ -3,17 +3,37 @@
 // conditions defined in the file COPYING, which is part of this source code package.
 
 use crate::check::{Collection, SimpleCheckResult};
+use std::net::IpAddr;
+use std::path::Path;
 use typed_builder::TypedBuilder;
 
 #[derive(Debug, TypedBuilder)]
 pub struct Config<'a> {
     server: &'a str,
     port: u16,
+    #[builder(default)]
+    address: Option<IpAddr>,
+    #[builder(default)]
+    sni: Option<&'a str>,
 }
 
 pub fn collect(config: Config) -> Collection {
-    Collection::from(&mut vec![
+    let mut check_results = vec![
         SimpleCheckResult::notice(format!("Host to test: {}", config.server)).into(),
         SimpleCheckResult::notice(format!("Host port: {}", config.port)).into(),
-    ])
+    ];
+    if let Some(address) = config.address {
+        check_results.push(SimpleCheckResult::notice(format!("Host address: {address}")).into());
+    }
+    if let Some(sni) = config.sni {
+        check_results.push(SimpleCheckResult::notice(format!("SNI: {sni}")).into());
+    }
+    Collection::from(&mut check_results)
+}
+
+pub fn collect_file(path: &Path) -> Collection {
+    Collection::from(SimpleCheckResult::notice(format!(
+        "File to test: {}",
+        path.display()
+    )))
 }
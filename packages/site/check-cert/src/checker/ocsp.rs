@@ -0,0 +1,389 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::check::{
+    CheckResult, Collection, LevelsChecker, LevelsCheckerArgs, OutputType, Real, SimpleCheckResult,
+};
+use crate::ocsp::{Response, Source};
+use anyhow::{anyhow, Result};
+use openssl::asn1::Asn1Object;
+use openssl::hash::{hash, MessageDigest};
+use openssl::ocsp::{OcspFlag, OcspResponse, OcspResponseStatus};
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::X509;
+use time::{Duration, OffsetDateTime};
+use typed_builder::TypedBuilder;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::der_parser::asn1_rs::{Any, Class, FromDer, Tag};
+use x509_parser::time::ASN1Time;
+use x509_parser::x509::ReasonCode;
+
+#[derive(Debug, TypedBuilder)]
+#[builder(field_defaults(default))]
+pub struct Config<'a> {
+    #[builder(!default)]
+    trust_store: &'a [Vec<u8>],
+    max_age: Option<LevelsChecker<Duration>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum CertStatus {
+    Good,
+    Revoked {
+        time: OffsetDateTime,
+        reason: Option<ReasonCode>,
+    },
+    Unknown,
+}
+
+#[derive(Debug)]
+struct SingleResponse {
+    status: CertStatus,
+    this_update: OffsetDateTime,
+    next_update: Option<OffsetDateTime>,
+}
+
+pub fn check(chain: &[Vec<u8>], response: Result<Response>, config: Config) -> Collection {
+    assert!(!chain.is_empty());
+
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => return Collection::from(SimpleCheckResult::unknown(format!("OCSP: {err:#}"))),
+    };
+    let single = match evaluate(chain, &response.der, config.trust_store) {
+        Ok(single) => single,
+        Err(result) => return Collection::from(result),
+    };
+    let now = OffsetDateTime::now_utc();
+    let mut check_results = vec![check_status(&single.status, &response.source).into()];
+    check_results.push(
+        check_response_age(now - single.this_update, config.max_age)
+            .map(|x| Real::from(x.whole_seconds() as isize)),
+    );
+    check_results.extend(check_next_update(now, single.next_update).map(Into::into));
+    Collection::from(&mut check_results)
+}
+
+fn evaluate(
+    chain: &[Vec<u8>],
+    der: &[u8],
+    trust_store: &[Vec<u8>],
+) -> Result<SingleResponse, SimpleCheckResult> {
+    let Ok(response) = OcspResponse::from_der(der) else {
+        return Err(SimpleCheckResult::crit("OCSP: Invalid response"));
+    };
+    if response.status() != OcspResponseStatus::SUCCESSFUL {
+        return Err(SimpleCheckResult::unknown(format!(
+            "OCSP: Responder returned {}",
+            status_name(response.status())
+        )));
+    }
+    if verify_signature(&response, &chain[1..], trust_store).is_err() {
+        return Err(SimpleCheckResult::crit(
+            "OCSP: Response signature verification failed",
+        ));
+    }
+    let responses = match single_responses(der) {
+        Ok(responses) => responses,
+        Err(err) => return Err(SimpleCheckResult::crit(format!("OCSP: {err}"))),
+    };
+    let Ok((_, cert)) = X509Certificate::from_der(&chain[0]) else {
+        return Err(SimpleCheckResult::crit("OCSP: Failed to parse certificate"));
+    };
+    responses
+        .into_iter()
+        .find_map(|(cert_id, single)| cert_id.matches(&cert).then_some(single))
+        .ok_or(SimpleCheckResult::crit(
+            "OCSP: Response does not cover the certificate",
+        ))
+}
+
+fn status_name(status: OcspResponseStatus) -> &'static str {
+    match status {
+        OcspResponseStatus::MALFORMED_REQUEST => "malformedRequest",
+        OcspResponseStatus::INTERNAL_ERROR => "internalError",
+        OcspResponseStatus::TRY_LATER => "tryLater",
+        OcspResponseStatus::SIG_REQUIRED => "sigRequired",
+        OcspResponseStatus::UNAUTHORIZED => "unauthorized",
+        _ => "unknown status",
+    }
+}
+
+fn verify_signature(
+    response: &OcspResponse,
+    intermediates: &[Vec<u8>],
+    trust_store: &[Vec<u8>],
+) -> Result<()> {
+    let mut store = X509StoreBuilder::new()?;
+    for der in trust_store {
+        let _ = store.add_cert(X509::from_der(der)?);
+    }
+    let mut certs = Stack::<X509>::new()?;
+    for der in intermediates {
+        certs.push(X509::from_der(der)?)?;
+    }
+    response
+        .basic()?
+        .verify(&certs, &store.build(), OcspFlag::empty())?;
+    Ok(())
+}
+
+// The openssl crate cannot cope with a missing nextUpdate, so we read
+// the single responses from the BasicOCSPResponse ourselves (RFC 6960, 4.2.1).
+
+struct CertId {
+    hash_algorithm: String,
+    issuer_name_hash: Vec<u8>,
+    serial: Vec<u8>,
+}
+
+impl CertId {
+    fn matches(&self, cert: &X509Certificate) -> bool {
+        let Some(digest) = Asn1Object::from_str(&self.hash_algorithm)
+            .ok()
+            .and_then(|oid| MessageDigest::from_nid(oid.nid()))
+        else {
+            return false;
+        };
+        self.serial == cert.raw_serial()
+            && hash(digest, cert.issuer().as_raw())
+                .is_ok_and(|digest| *digest == self.issuer_name_hash)
+    }
+}
+
+fn parse(data: &[u8]) -> Result<Any> {
+    Any::from_der(data)
+        .map(|(_, any)| any)
+        .map_err(|_| anyhow!("Invalid response"))
+}
+
+fn children<'a>(any: &Any<'a>) -> Result<Vec<Any<'a>>> {
+    let mut rest = any.data;
+    let mut children = vec![];
+    while !rest.is_empty() {
+        let (next, child) = Any::from_der(rest).map_err(|_| anyhow!("Invalid response"))?;
+        children.push(child);
+        rest = next;
+    }
+    Ok(children)
+}
+
+fn is_context(any: &Any, tag: u32) -> bool {
+    any.class() == Class::ContextSpecific && any.tag() == Tag(tag)
+}
+
+fn to_datetime(any: &Any) -> Result<OffsetDateTime> {
+    Ok(any.clone().generalizedtime()?.utc_datetime()?)
+}
+
+fn single_responses(der: &[u8]) -> Result<Vec<(CertId, SingleResponse)>> {
+    // OCSPResponse: responseStatus, [0] responseBytes: responseType, response
+    let response = children(&parse(der)?)?;
+    let response_bytes = response
+        .iter()
+        .find(|any| is_context(any, 0))
+        .ok_or(anyhow!("Empty response"))?;
+    let response_bytes = children(&parse(response_bytes.data)?)?;
+    let basic = response_bytes
+        .get(1)
+        .ok_or(anyhow!("Invalid response"))?
+        .clone()
+        .octetstring()?;
+    // BasicOCSPResponse: tbsResponseData, signatureAlgorithm, signature, [0] certs
+    let basic = children(&parse(basic.as_ref())?)?;
+    let tbs = children(basic.first().ok_or(anyhow!("Invalid response"))?)?;
+    // ResponseData: [0] version, responderID, producedAt, responses, [1] responseExtensions
+    let responses = tbs
+        .iter()
+        .find(|any| any.class() == Class::Universal && any.tag() == Tag::Sequence)
+        .ok_or(anyhow!("Invalid response"))?;
+    children(responses)?.iter().map(single_response).collect()
+}
+
+fn single_response(any: &Any) -> Result<(CertId, SingleResponse)> {
+    // SingleResponse: certID, certStatus, thisUpdate, [0] nextUpdate, [1] singleExtensions
+    let fields = children(any)?;
+    let [cert_id, status, this_update, ..] = fields.as_slice() else {
+        return Err(anyhow!("Invalid single response"));
+    };
+    // CertID: hashAlgorithm, issuerNameHash, issuerKeyHash, serialNumber
+    let cert_id = children(cert_id)?;
+    let [hash_algorithm, issuer_name_hash, _, serial] = cert_id.as_slice() else {
+        return Err(anyhow!("Invalid certificate ID"));
+    };
+    let hash_algorithm = children(hash_algorithm)?
+        .first()
+        .ok_or(anyhow!("Invalid certificate ID"))?
+        .clone()
+        .oid()?
+        .to_id_string();
+    let status = match status.tag() {
+        Tag(0) => CertStatus::Good,
+        Tag(1) => {
+            // RevokedInfo: revocationTime, [0] revocationReason
+            let info = children(status)?;
+            let time = to_datetime(info.first().ok_or(anyhow!("Invalid revocation info"))?)?;
+            let reason = match info.get(1) {
+                Some(reason) if is_context(reason, 0) => Some(ReasonCode(
+                    parse(reason.data)?.clone().enumerated()?.0 as u8,
+                )),
+                _ => None,
+            };
+            CertStatus::Revoked { time, reason }
+        }
+        _ => CertStatus::Unknown,
+    };
+    let next_update = match fields.get(3) {
+        Some(next_update) if is_context(next_update, 0) => {
+            Some(to_datetime(&parse(next_update.data)?)?)
+        }
+        _ => None,
+    };
+    Ok((
+        CertId {
+            hash_algorithm,
+            issuer_name_hash: issuer_name_hash.data.to_vec(),
+            serial: serial.data.to_vec(),
+        },
+        SingleResponse {
+            status,
+            this_update: to_datetime(this_update)?,
+            next_update,
+        },
+    ))
+}
+
+fn format_time(time: OffsetDateTime) -> String {
+    ASN1Time::from(time).to_string()
+}
+
+fn format_duration(duration: Duration) -> String {
+    match duration {
+        d if d.whole_days() > 0 => format!("{} day(s)", d.whole_days()),
+        d if d.whole_hours() > 0 => format!("{} hour(s)", d.whole_hours()),
+        d => format!("{} minute(s)", d.whole_minutes()),
+    }
+}
+
+fn check_status(status: &CertStatus, source: &Source) -> SimpleCheckResult {
+    match status {
+        CertStatus::Good => SimpleCheckResult::notice(format!("OCSP: good ({source})")),
+        CertStatus::Revoked { time, reason } => SimpleCheckResult::crit_with_details(
+            format!("OCSP: revoked on {}", format_time(*time)),
+            format!(
+                "OCSP: revoked on {}, reason: {} ({source})",
+                format_time(*time),
+                reason.map_or(String::from("unspecified"), |r| r.to_string()),
+            ),
+        ),
+        CertStatus::Unknown => SimpleCheckResult::warn_with_details(
+            "OCSP: unknown",
+            format!("OCSP: unknown ({source})"),
+        ),
+    }
+}
+
+fn check_response_age(
+    age: Duration,
+    levels: Option<LevelsChecker<Duration>>,
+) -> CheckResult<Duration> {
+    let age = std::cmp::max(age, Duration::ZERO);
+    let text = format!("OCSP response age: {}", format_duration(age));
+    match levels {
+        None => SimpleCheckResult::notice(text).into(),
+        Some(levels) => levels.check(
+            age,
+            OutputType::Notice(text),
+            LevelsCheckerArgs::builder()
+                .label("ocsp_response_age")
+                .uom("s".parse().unwrap())
+                .build(),
+        ),
+    }
+}
+
+fn check_next_update(
+    now: OffsetDateTime,
+    next_update: Option<OffsetDateTime>,
+) -> Option<SimpleCheckResult> {
+    next_update
+        .filter(|next_update| *next_update < now)
+        .map(|next_update| {
+            SimpleCheckResult::warn(format!(
+                "OCSP response outdated (next update was due on {})",
+                format_time(next_update)
+            ))
+        })
+}
+
+#[cfg(test)]
+mod test_check_status {
+    use super::{check_status, CertStatus, ReasonCode, SimpleCheckResult, Source};
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_good() {
+        assert_eq!(
+            check_status(&CertStatus::Good, &Source::Stapled),
+            SimpleCheckResult::notice("OCSP: good (stapled response)")
+        );
+    }
+
+    #[test]
+    fn test_revoked() {
+        assert_eq!(
+            check_status(
+                &CertStatus::Revoked {
+                    time: OffsetDateTime::from_unix_timestamp(1704067200).unwrap(),
+                    reason: Some(ReasonCode::KeyCompromise),
+                },
+                &Source::Responder(String::from("http://ocsp.example.com")),
+            ),
+            SimpleCheckResult::crit_with_details(
+                "OCSP: revoked on Jan  1 00:00:00 2024 +00:00",
+                "OCSP: revoked on Jan  1 00:00:00 2024 +00:00, reason: KeyCompromise \
+                 (responder http://ocsp.example.com)"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert_eq!(
+            check_status(&CertStatus::Unknown, &Source::Stapled),
+            SimpleCheckResult::warn_with_details(
+                "OCSP: unknown",
+                "OCSP: unknown (stapled response)"
+            )
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_check_next_update {
+    use super::{check_next_update, SimpleCheckResult};
+    use time::OffsetDateTime;
+
+    fn day(n: i64) -> OffsetDateTime {
+        // 2024-01-01 plus n days
+        OffsetDateTime::from_unix_timestamp(1704067200 + n * 86400).unwrap()
+    }
+
+    #[test]
+    fn test_fresh() {
+        assert_eq!(check_next_update(day(0), Some(day(1))), None);
+        assert_eq!(check_next_update(day(0), None), None);
+    }
+
+    #[test]
+    fn test_outdated() {
+        assert_eq!(
+            check_next_update(day(2), Some(day(1))),
+            Some(SimpleCheckResult::warn(
+                "OCSP response outdated (next update was due on Jan  2 00:00:00 2024 +00:00)"
+            ))
+        );
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::check::{CheckResult, Collection, Real, SimpleCheckResult};
+use crate::scan::Protocol;
+use anyhow::Result;
+use typed_builder::TypedBuilder;
+
+// Substrings of the OpenSSL names of broken or anonymous cipher suites
+static WEAK_CIPHERS: [&str; 9] = [
+    "NULL", "EXP", "RC2", "RC4", "DES", "IDEA", "MD5", "ADH", "AECDH",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ok,
+    Warn,
+    Crit,
+}
+
+#[derive(Debug, TypedBuilder)]
+pub struct Config {
+    /// State if the server accepts TLS 1.0 or 1.1
+    #[builder(default = Severity::Crit)]
+    deprecated_version: Severity,
+    /// State if the server accepts weak cipher suites
+    #[builder(default = Severity::Warn)]
+    weak_cipher: Severity,
+}
+
+pub fn check(scan: Result<Vec<Protocol>>, config: Config) -> Collection {
+    let protocols = match scan {
+        Ok(protocols) => protocols,
+        Err(err) => {
+            return Collection::from(SimpleCheckResult::unknown(format!("TLS scan: {err:#}")))
+        }
+    };
+    let accepted = protocols
+        .iter()
+        .filter(|protocol| protocol.accepted)
+        .map(|protocol| protocol.version.to_string())
+        .collect::<Vec<_>>();
+    let failed = protocols.iter().any(|protocol| protocol.error.is_some());
+    let mut check_results: Vec<CheckResult<Real>> = vec![match (accepted.is_empty(), failed) {
+        (true, false) => SimpleCheckResult::crit("TLS versions: none accepted").into(),
+        (true, true) => SimpleCheckResult::unknown("TLS versions: none confirmed").into(),
+        (false, _) => {
+            SimpleCheckResult::ok(format!("TLS versions: {}", accepted.join(", "))).into()
+        }
+    }];
+
+    for protocol in &protocols {
+        let version = protocol.version;
+        if let Some(err) = &protocol.error {
+            check_results.push(SimpleCheckResult::unknown(format!("{version}: {err}")).into());
+            continue;
+        }
+        if !protocol.accepted {
+            check_results
+                .push(SimpleCheckResult::notice(format!("{version}: not accepted")).into());
+            continue;
+        }
+        let details = format!("{version}: {}", protocol.ciphers.join(", "));
+        check_results.push(
+            match version.is_deprecated() {
+                true => with_severity(
+                    config.deprecated_version,
+                    format!("{version} accepted (deprecated)"),
+                    details,
+                ),
+                false => SimpleCheckResult::notice(details),
+            }
+            .into(),
+        );
+        let weak = protocol
+            .ciphers
+            .iter()
+            .filter(|cipher| WEAK_CIPHERS.iter().any(|weak| cipher.contains(weak)))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !weak.is_empty() {
+            let text = format!("{version} weak cipher suites: {}", weak.join(", "));
+            check_results.push(with_severity(config.weak_cipher, &text, &text).into());
+        }
+    }
+    Collection::from(&mut check_results)
+}
+
+fn with_severity(
+    severity: Severity,
+    summary: impl Into<String>,
+    details: impl Into<String>,
+) -> SimpleCheckResult {
+    match severity {
+        Severity::Ok => SimpleCheckResult::ok_with_details(summary, details),
+        Severity::Warn => SimpleCheckResult::warn_with_details(summary, details),
+        Severity::Crit => SimpleCheckResult::crit_with_details(summary, details),
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::http;
+use anyhow::{anyhow, Context, Result};
+use openssl::asn1::Asn1Time;
+use openssl::hash::{hash, MessageDigest};
+use openssl::x509::{X509Crl, X509};
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use typed_builder::TypedBuilder;
+use x509_parser::pem::Pem;
+
+#[derive(Debug, TypedBuilder)]
+#[builder(field_defaults(default))]
+pub struct Config {
+    /// Local CRL files, PEM or DER
+    files: Vec<PathBuf>,
+    /// Fetch the CRLs from the distribution points of the certificates
+    fetch: bool,
+    /// Keep fetched CRLs in this directory until their next update
+    cache_dir: Option<PathBuf>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct Crl {
+    /// File or URL the CRL was loaded from
+    pub source: String,
+    pub der: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct Crls {
+    pub crls: Vec<Crl>,
+    pub errors: Vec<String>,
+}
+
+pub fn load(chain: &[Vec<u8>], config: Config) -> Crls {
+    let mut crls = Crls::default();
+    for path in &config.files {
+        match read_file(path) {
+            Ok(ders) => crls.crls.extend(ders.into_iter().map(|der| Crl {
+                source: path.display().to_string(),
+                der,
+            })),
+            Err(err) => crls
+                .errors
+                .push(format!("Cannot load {}: {}", path.display(), err)),
+        }
+    }
+    if !config.fetch {
+        return crls;
+    }
+    for url in distribution_points(chain) {
+        let cache_file = config
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(cache_file_name(&url)));
+        if let Some(der) = cache_file.as_deref().and_then(read_cache) {
+            crls.crls.push(Crl { source: url, der });
+            continue;
+        }
+        match fetch(&url, config.timeout) {
+            Ok(der) => {
+                if let Some(cache_file) = cache_file {
+                    if let Err(err) = write_cache(&cache_file, &der) {
+                        crls.errors
+                            .push(format!("Cannot write {}: {}", cache_file.display(), err));
+                    }
+                }
+                crls.crls.push(Crl { source: url, der });
+            }
+            Err(err) => crls.errors.push(format!("{err:#}")),
+        }
+    }
+    crls
+}
+
+fn read_file(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let content = std::fs::read(path)?;
+    if !content.starts_with(b"-----BEGIN") {
+        X509Crl::from_der(&content).context("Invalid CRL")?;
+        return Ok(vec![content]);
+    }
+    // A PEM file may contain several CRLs
+    let mut ders = vec![];
+    for pem in Pem::iter_from_buffer(&content) {
+        let pem = pem?;
+        if pem.label == "X509 CRL" {
+            X509Crl::from_der(&pem.contents).context("Invalid CRL")?;
+            ders.push(pem.contents);
+        }
+    }
+    match ders.is_empty() {
+        true => Err(anyhow!("No CRL found")),
+        false => Ok(ders),
+    }
+}
+
+/// The HTTP URLs of the CRL distribution points of all certificates in the chain
+fn distribution_points(chain: &[Vec<u8>]) -> Vec<String> {
+    let mut urls: Vec<String> = vec![];
+    for cert in chain.iter().flat_map(|der| X509::from_der(der)) {
+        let Some(points) = cert.crl_distribution_points() else {
+            continue;
+        };
+        for point in &points {
+            let url = point
+                .distpoint()
+                .and_then(|name| name.fullname())
+                .and_then(|names| {
+                    names
+                        .iter()
+                        .flat_map(|name| name.uri())
+                        .find(|uri| uri.starts_with("http://"))
+                });
+            if let Some(url) = url {
+                if !urls.iter().any(|known| known == url) {
+                    urls.push(url.to_string());
+                }
+            }
+        }
+    }
+    urls
+}
+
+fn fetch(url: &str, timeout: Option<Duration>) -> Result<Vec<u8>> {
+    let der = http::get(url, timeout).with_context(|| format!("Cannot fetch CRL {url}"))?;
+    X509Crl::from_der(&der).with_context(|| format!("Invalid CRL from {url}"))?;
+    Ok(der)
+}
+
+fn cache_file_name(url: &str) -> String {
+    let digest = hash(MessageDigest::sha256(), url.as_bytes()).expect("valid digest");
+    let name = digest.iter().fold(String::new(), |mut name, b| {
+        let _ = write!(name, "{b:02x}");
+        name
+    });
+    format!("{name}.crl")
+}
+
+// Cached CRLs are valid until their next update, CRLs without one are not cached.
+fn read_cache(path: &Path) -> Option<Vec<u8>> {
+    let der = std::fs::read(path).ok()?;
+    let crl = X509Crl::from_der(&der).ok()?;
+    let now = Asn1Time::days_from_now(0).ok()?;
+    match crl.next_update()?.compare(&now).ok()? {
+        Ordering::Greater => Some(der),
+        _ => None,
+    }
+}
+
+fn write_cache(path: &Path, der: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    std::fs::write(&tmp, der)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_cache {
+    use super::{cache_file_name, read_cache, write_cache};
+    use tempfile::TempDir;
+
+    static CURRENT: &[u8] = include_bytes!("../assets/crl/root.crl");
+    static OUTDATED: &[u8] = include_bytes!("../assets/crl/intermediate-outdated.crl");
+
+    #[test]
+    fn test_cache_file_name() {
+        assert_eq!(
+            cache_file_name("http://crl.example.com/ca.crl"),
+            cache_file_name("http://crl.example.com/ca.crl")
+        );
+        assert_ne!(
+            cache_file_name("http://crl.example.com/ca.crl"),
+            cache_file_name("http://crl.example.com/other.crl")
+        );
+    }
+
+    #[test]
+    fn test_current_crl_is_cached() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sub").join("ca.crl");
+        write_cache(&path, CURRENT).unwrap();
+        assert_eq!(read_cache(&path).unwrap(), CURRENT);
+    }
+
+    #[test]
+    fn test_outdated_crl_is_not_used() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("ca.crl");
+        write_cache(&path, OUTDATED).unwrap();
+        assert_eq!(read_cache(&path), None);
+    }
+
+    #[test]
+    fn test_missing_cache() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(read_cache(&dir.path().join("ca.crl")), None);
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use anyhow::{anyhow, bail, Context, Result};
+use openssl::base64;
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::Verifier;
+use openssl::x509::X509Ref;
+use serde_json::Value;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::path::Path;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::der_parser::asn1_rs::{Any, Class, FromDer, Tag};
+
+const EMBEDDED_SCTS: &str = "1.3.6.1.4.1.11129.2.4.2";
+const OCSP_SCTS: &str = "1.3.6.1.4.1.11129.2.4.5";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Embedded,
+    TlsExtension,
+    Ocsp,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter) -> FormatResult {
+        match self {
+            Self::Embedded => write!(f, "embedded"),
+            Self::TlsExtension => write!(f, "TLS extension"),
+            Self::Ocsp => write!(f, "OCSP response"),
+        }
+    }
+}
+
+/// Signed certificate timestamp (RFC 6962, 3.2)
+#[derive(Debug, Clone)]
+pub struct Sct {
+    pub source: Source,
+    pub version: u8,
+    pub log_id: Vec<u8>,
+    /// Milliseconds since the epoch
+    pub timestamp: u64,
+    pub extensions: Vec<u8>,
+    pub hash_algorithm: u8,
+    pub signature_algorithm: u8,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Log {
+    pub description: String,
+    /// SHA-256 hash of the key
+    pub id: Vec<u8>,
+    /// DER encoded public key
+    pub key: Vec<u8>,
+}
+
+/// Load the logs from a log list in the JSON format published by Google (version 3).
+pub fn load_logs(path: &Path) -> Result<Vec<Log>> {
+    let data = std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let list: Value = serde_json::from_slice(&data)
+        .with_context(|| format!("Invalid CT log list {}", path.display()))?;
+    let operators = list["operators"].as_array().ok_or(anyhow!(
+        "Invalid CT log list {}: no operators",
+        path.display()
+    ))?;
+    let mut logs = vec![];
+    for log in operators.iter().flat_map(|operator| {
+        ["logs", "tiled_logs"]
+            .iter()
+            .flat_map(|kind| operator[kind].as_array().into_iter().flatten())
+    }) {
+        let key = log["key"].as_str().ok_or(anyhow!(
+            "Invalid CT log list {}: log without key",
+            path.display()
+        ))?;
+        let key = base64::decode_block(key)?;
+        logs.push(Log {
+            description: log["description"].as_str().unwrap_or("unknown").to_string(),
+            id: hash(MessageDigest::sha256(), &key)?.to_vec(),
+            key,
+        });
+    }
+    Ok(logs)
+}
+
+/// Collect the SCTs of the certificate from all sources.
+pub fn collect(
+    der: &[u8],
+    sct_list: Option<&[u8]>,
+    ocsp_response: Option<&[u8]>,
+) -> Result<Vec<Sct>> {
+    let (_, cert) = X509Certificate::from_der(der).map_err(|_| anyhow!("Invalid certificate"))?;
+    let mut scts = vec![];
+    if let Some(ext) = cert
+        .extensions()
+        .iter()
+        .find(|ext| ext.oid.to_id_string() == EMBEDDED_SCTS)
+    {
+        scts.extend(parse_list(&octets(ext.value)?, Source::Embedded)?);
+    }
+    if let Some(sct_list) = sct_list {
+        scts.extend(parse_list(sct_list, Source::TlsExtension)?);
+    }
+    if let Some(ocsp_response) = ocsp_response {
+        for value in ocsp_extensions(ocsp_response, OCSP_SCTS)? {
+            scts.extend(parse_list(&octets(&value)?, Source::Ocsp)?);
+        }
+    }
+    Ok(scts)
+}
+
+/// Verify the signature of the log over the certificate.
+///
+/// Embedded SCTs sign the precertificate and require the issuer of the certificate.
+pub fn verify(sct: &Sct, log: &Log, der: &[u8], issuer: Option<&X509Ref>) -> Result<bool> {
+    let mut data = vec![sct.version, 0];
+    data.extend(sct.timestamp.to_be_bytes());
+    match sct.source {
+        Source::Embedded => {
+            let issuer = issuer.ok_or(anyhow!("Cannot find issuer certificate"))?;
+            data.extend([0, 1]);
+            data.extend(
+                hash(
+                    MessageDigest::sha256(),
+                    &issuer.public_key()?.public_key_to_der()?,
+                )?
+                .as_ref(),
+            );
+            let tbs = precert_tbs(der)?;
+            data.extend(&(tbs.len() as u32).to_be_bytes()[1..]);
+            data.extend(tbs);
+        }
+        Source::TlsExtension | Source::Ocsp => {
+            data.extend([0, 0]);
+            data.extend(&(der.len() as u32).to_be_bytes()[1..]);
+            data.extend(der);
+        }
+    }
+    data.extend((sct.extensions.len() as u16).to_be_bytes());
+    data.extend(&sct.extensions);
+
+    let key = PKey::public_key_from_der(&log.key)?;
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+    verifier.update(&data)?;
+    Ok(verifier.verify(&sct.signature).unwrap_or(false))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("Truncated SCT");
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |value, byte| value << 8 | u64::from(*byte)))
+    }
+
+    fn vector(&mut self, len_size: usize) -> Result<&'a [u8]> {
+        let len = self.uint(len_size)? as usize;
+        self.take(len)
+    }
+}
+
+/// Parse a TLS encoded SignedCertificateTimestampList.
+pub fn parse_list(data: &[u8], source: Source) -> Result<Vec<Sct>> {
+    let mut list = Reader(Reader(data).vector(2)?);
+    let mut scts = vec![];
+    while !list.0.is_empty() {
+        let mut sct = Reader(list.vector(2)?);
+        let version = sct.uint(1)? as u8;
+        if version != 0 {
+            // Unknown versions cannot be parsed and are ignored.
+            continue;
+        }
+        scts.push(Sct {
+            source,
+            version,
+            log_id: sct.take(32)?.to_vec(),
+            timestamp: sct.uint(8)?,
+            extensions: sct.vector(2)?.to_vec(),
+            hash_algorithm: sct.uint(1)? as u8,
+            signature_algorithm: sct.uint(1)? as u8,
+            signature: sct.vector(2)?.to_vec(),
+        });
+    }
+    Ok(scts)
+}
+
+fn parse(data: &[u8]) -> Result<Any> {
+    Any::from_der(data)
+        .map(|(_, any)| any)
+        .map_err(|_| anyhow!("Invalid DER"))
+}
+
+/// Return the DER encoded children of a constructed value.
+fn children(data: &[u8]) -> Result<Vec<(Any, &[u8])>> {
+    let mut rest = data;
+    let mut children = vec![];
+    while !rest.is_empty() {
+        let (next, child) = Any::from_der(rest).map_err(|_| anyhow!("Invalid DER"))?;
+        children.push((child, &rest[..rest.len() - next.len()]));
+        rest = next;
+    }
+    Ok(children)
+}
+
+fn octets(data: &[u8]) -> Result<Vec<u8>> {
+    Ok(parse(data)?.octetstring()?.as_ref().to_vec())
+}
+
+fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut data = vec![tag];
+    match content.len() {
+        len @ 0..=0x7f => data.push(len as u8),
+        len => {
+            let bytes = (len as u64).to_be_bytes();
+            let bytes = &bytes[bytes.iter().position(|byte| *byte != 0).unwrap_or(7)..];
+            data.push(0x80 | bytes.len() as u8);
+            data.extend(bytes);
+        }
+    }
+    data.extend(content);
+    data
+}
+
+/// Reconstruct the TBSCertificate of the precertificate by removing the SCT list extension.
+fn precert_tbs(der: &[u8]) -> Result<Vec<u8>> {
+    let cert = parse(der)?;
+    let (tbs, _) = children(cert.data)?
+        .into_iter()
+        .next()
+        .ok_or(anyhow!("Invalid certificate"))?;
+    let mut content = vec![];
+    for (field, raw) in children(tbs.data)? {
+        if field.class() != Class::ContextSpecific || field.tag() != Tag(3) {
+            content.extend(raw);
+            continue;
+        }
+        let extensions = parse(field.data)?;
+        let mut kept = vec![];
+        for (extension, raw) in children(extensions.data)? {
+            let (oid, _) = children(extension.data)?
+                .into_iter()
+                .next()
+                .ok_or(anyhow!("Invalid extension"))?;
+            if oid.oid()?.to_id_string() != EMBEDDED_SCTS {
+                kept.extend(raw);
+            }
+        }
+        content.extend(encode(0xa3, &encode(0x30, &kept)));
+    }
+    Ok(encode(0x30, &content))
+}
+
+fn first(data: &[u8]) -> Result<Any> {
+    Ok(children(data)?
+        .into_iter()
+        .next()
+        .ok_or(anyhow!("Invalid DER"))?
+        .0)
+}
+
+/// Return the values of the single extensions with the OID in the OCSP response.
+fn ocsp_extensions(der: &[u8], wanted: &str) -> Result<Vec<Vec<u8>>> {
+    // OCSPResponse: responseStatus, [0] responseBytes: responseType, response
+    let Some((response_bytes, _)) = children(parse(der)?.data)?
+        .into_iter()
+        .find(|(any, _)| any.class() == Class::ContextSpecific && any.tag() == Tag(0))
+    else {
+        return Ok(vec![]);
+    };
+    let response_bytes = first(response_bytes.data)?;
+    let (basic, _) = children(response_bytes.data)?
+        .into_iter()
+        .nth(1)
+        .ok_or(anyhow!("Invalid OCSP response"))?;
+    // BasicOCSPResponse: tbsResponseData, ...
+    let basic = basic.octetstring()?;
+    let tbs = first(parse(basic.as_ref())?.data)?;
+    // ResponseData: [0] version, responderID, producedAt, responses, [1] responseExtensions
+    let (responses, _) = children(tbs.data)?
+        .into_iter()
+        .find(|(any, _)| any.class() == Class::Universal && any.tag() == Tag::Sequence)
+        .ok_or(anyhow!("Invalid OCSP response"))?;
+    let mut values = vec![];
+    for (single, _) in children(responses.data)? {
+        // SingleResponse: certID, certStatus, thisUpdate, [0] nextUpdate, [1] singleExtensions
+        for (field, _) in children(single.data)? {
+            if field.class() != Class::ContextSpecific || field.tag() != Tag(1) {
+                continue;
+            }
+            for (extension, _) in children(parse(field.data)?.data)? {
+                // Extension: extnID, critical, extnValue
+                let fields = children(extension.data)?;
+                let (Some((oid, _)), Some((value, _))) = (fields.first(), fields.last()) else {
+                    bail!("Invalid OCSP extension");
+                };
+                if oid.clone().oid()?.to_id_string() == wanted {
+                    values.push(value.clone().octetstring()?.as_ref().to_vec());
+                }
+            }
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test_encode {
+    use super::encode;
+
+    #[test]
+    fn test_short_length() {
+        assert_eq!(encode(0x04, &[1, 2]), vec![0x04, 0x02, 1, 2]);
+    }
+
+    #[test]
+    fn test_long_length() {
+        let content = vec![0; 0x80];
+        assert_eq!(encode(0x04, &content)[..3], [0x04, 0x81, 0x80]);
+        let content = vec![0; 0x100];
+        assert_eq!(encode(0x04, &content)[..4], [0x04, 0x82, 0x01, 0x00]);
+    }
+}
@@ -2,36 +2,104 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
+use crate::starttls::{self, Protocol};
 use anyhow::{anyhow, Context, Result};
-use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
-use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use openssl::ssl::{
+    ExtensionContext, SslConnector, SslMethod, SslStream, SslVerifyMode, StatusType,
+};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use typed_builder::TypedBuilder;
 
-#[derive(Debug, TypedBuilder)]
+#[derive(Debug, Clone, TypedBuilder)]
 pub struct Config {
     timeout: Option<Duration>,
+    #[builder(default)]
+    starttls: Option<Protocol>,
+    /// Connect to this address instead of resolving the server name
+    #[builder(default)]
+    address: Option<IpAddr>,
+    /// Request this name with SNI instead of the server name
+    #[builder(default)]
+    sni: Option<String>,
+    #[builder(default)]
+    disable_sni: bool,
 }
 
+#[derive(Debug)]
+pub struct ServerCert {
+    pub chain: Vec<Vec<u8>>,
+    /// OCSP response stapled to the handshake by the server
+    pub ocsp_response: Option<Vec<u8>>,
+    /// Signed certificate timestamps sent in the TLS extension (RFC 6962)
+    pub sct_list: Option<Vec<u8>>,
+}
+
+const SIGNED_CERTIFICATE_TIMESTAMP: u16 = 18;
+
 fn to_addr(server: &str, port: u16) -> Result<SocketAddr> {
     let mut addr_iter = format!("{server}:{port}").to_socket_addrs()?;
     addr_iter.next().ok_or(anyhow!("no address"))
 }
 
-pub fn fetch_server_cert(server: &str, port: u16, config: Config) -> Result<Vec<Vec<u8>>> {
-    let addr = to_addr(server, port)?;
-    let stream = match config.timeout {
+/// Open the connection and upgrade it with STARTTLS if configured.
+pub(crate) fn connect(server: &str, port: u16, config: &Config) -> Result<TcpStream> {
+    let addr = match config.address {
+        Some(ip) => SocketAddr::new(ip, port),
+        None => to_addr(server, port)?,
+    };
+    let mut stream = match config.timeout {
         None => TcpStream::connect(addr)?,
         Some(dur) => TcpStream::connect_timeout(&addr, dur)?,
     };
     stream.set_read_timeout(config.timeout)?;
+    if let Some(protocol) = config.starttls {
+        starttls::negotiate(&mut stream, protocol, server_name(server, config))?;
+    }
+    Ok(stream)
+}
+
+pub(crate) fn handshake(
+    connector: &SslConnector,
+    server: &str,
+    stream: TcpStream,
+    config: &Config,
+) -> Result<SslStream<TcpStream>> {
+    let mut connect_config = connector
+        .configure()
+        .context("Cannot configure connection")?;
+    connect_config.set_status_type(StatusType::OCSP)?;
+    connect_config.set_use_server_name_indication(!config.disable_sni);
+    Ok(connect_config.connect(server_name(server, config), stream)?)
+}
+
+fn server_name<'a>(server: &'a str, config: &'a Config) -> &'a str {
+    config.sni.as_deref().unwrap_or(server)
+}
+
+pub fn fetch_server_cert(server: &str, port: u16, config: Config) -> Result<ServerCert> {
+    let stream = connect(server, port, &config)?;
     let mut connector_builder = SslConnector::builder(SslMethod::tls())?;
     connector_builder.set_verify(SslVerifyMode::NONE);
+    // Request the SCTs with an empty extension and keep those of the server certificate.
+    let sct_list = Arc::new(Mutex::new(None));
+    let received = Arc::clone(&sct_list);
+    connector_builder.add_custom_ext(
+        SIGNED_CERTIFICATE_TIMESTAMP,
+        ExtensionContext::CLIENT_HELLO
+            | ExtensionContext::TLS1_2_SERVER_HELLO
+            | ExtensionContext::TLS1_3_CERTIFICATE,
+        |_, _, _| Ok(Some(vec![])),
+        move |_, _, data, cert| {
+            if matches!(cert, None | Some((0, _))) {
+                *received.lock().unwrap() = Some(data.to_vec());
+            }
+            Ok(())
+        },
+    )?;
     let connector = connector_builder.build();
-    connector
-        .configure()
-        .context("Cannot configure connection")?;
-    let mut stream = connector.connect(server, stream)?;
+    let mut stream = handshake(&connector, server, stream, &config)?;
     let chain = stream
         .ssl()
         .peer_cert_chain()
@@ -39,6 +107,12 @@ pub fn fetch_server_cert(server: &str, port: u16, config: Config) -> Result<Vec<
         .iter()
         .flat_map(|x509| x509.to_der())
         .collect::<Vec<_>>();
+    let ocsp_response = stream.ssl().ocsp_status().map(<[u8]>::to_vec);
     stream.shutdown()?;
-    Ok(chain)
+    let sct_list = sct_list.lock().unwrap().take();
+    Ok(ServerCert {
+        chain,
+        ocsp_response,
+        sct_list,
+    })
 }
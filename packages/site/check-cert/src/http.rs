@@ -0,0 +1,224 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// OCSP responders and CRL distribution points are served over plain HTTP (RFC 5280, RFC 6960),
+// so a minimal HTTP/1.0 client is all we need to fetch them.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+use url::Url;
+
+const MAX_RESPONSE_SIZE: u64 = 16 * 1024 * 1024;
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, PartialEq)]
+enum Response {
+    Ok(Vec<u8>),
+    // 307 and 308 ask to repeat the request as is, the others to follow up with a GET.
+    Redirect { location: String, same_method: bool },
+}
+
+pub fn get(url: &str, timeout: Option<Duration>) -> Result<Vec<u8>> {
+    request("GET", url, None, timeout)
+}
+
+pub fn post(
+    url: &str,
+    content_type: &str,
+    body: &[u8],
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>> {
+    request("POST", url, Some((content_type, body)), timeout)
+}
+
+fn request(
+    mut method: &str,
+    url: &str,
+    mut body: Option<(&str, &[u8])>,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>> {
+    let mut url = Url::parse(url).with_context(|| format!("Invalid URL {url}"))?;
+    for _ in 0..=MAX_REDIRECTS {
+        let (location, same_method) = match request_once(method, &url, body, timeout)? {
+            Response::Ok(content) => return Ok(content),
+            Response::Redirect {
+                location,
+                same_method,
+            } => (location, same_method),
+        };
+        url = url
+            .join(&location)
+            .with_context(|| format!("Invalid redirect from {url} to {location}"))?;
+        if !same_method {
+            (method, body) = ("GET", None);
+        }
+    }
+    bail!("Too many redirects, giving up at {url}")
+}
+
+fn request_once(
+    method: &str,
+    url: &Url,
+    body: Option<(&str, &[u8])>,
+    timeout: Option<Duration>,
+) -> Result<Response> {
+    if url.scheme() != "http" {
+        bail!("Unsupported URL scheme {} ({})", url.scheme(), url);
+    }
+    let host = url.host_str().ok_or(anyhow!("No host in URL {url}"))?;
+    let addrs = url
+        .socket_addrs(|| Some(80))
+        .with_context(|| format!("Cannot resolve {url}"))?;
+    let mut stream =
+        connect(&addrs, timeout).with_context(|| format!("Cannot connect to {url}"))?;
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    // The URL only carries the port if it's not the default one.
+    let host = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    let mut request = format!("{method} {path} HTTP/1.0\r\nHost: {host}\r\nConnection: close\r\n");
+    if let Some((content_type, body)) = body {
+        request.push_str(&format!(
+            "Content-Type: {content_type}\r\nContent-Length: {}\r\n",
+            body.len()
+        ));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+    if let Some((_, body)) = body {
+        stream.write_all(body)?;
+    }
+
+    let mut response = vec![];
+    stream
+        .take(MAX_RESPONSE_SIZE + 1)
+        .read_to_end(&mut response)
+        .with_context(|| format!("Failed reading response from {url}"))?;
+    if response.len() as u64 > MAX_RESPONSE_SIZE {
+        bail!("Response from {url} exceeds {MAX_RESPONSE_SIZE} bytes");
+    }
+    parse_response(&response).with_context(|| format!("Invalid response from {url}"))
+}
+
+// Try all addresses the host resolves to, like a browser would.
+fn connect(addrs: &[SocketAddr], timeout: Option<Duration>) -> Result<TcpStream> {
+    let mut last_err = None;
+    for addr in addrs {
+        let stream = match timeout {
+            None => TcpStream::connect(addr),
+            Some(dur) => TcpStream::connect_timeout(addr, dur),
+        };
+        match stream {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    match last_err {
+        Some(err) => Err(err.into()),
+        None => bail!("no address"),
+    }
+}
+
+fn parse_response(response: &[u8]) -> Result<Response> {
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or(anyhow!("incomplete header"))?;
+    let header = String::from_utf8_lossy(&response[..header_end]);
+    let mut lines = header.lines();
+    let status_line = lines.next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(Response::Ok(response[header_end + 4..].to_vec())),
+        Some(status @ ("301" | "302" | "303" | "307" | "308")) => lines
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("location"))
+            .map(|(_, location)| Response::Redirect {
+                location: location.trim().to_string(),
+                same_method: matches!(status, "307" | "308"),
+            })
+            .ok_or(anyhow!("{} without location", status_line)),
+        Some(_) => Err(anyhow!("{}", status_line)),
+        None => Err(anyhow!("no status")),
+    }
+}
+
+#[cfg(test)]
+mod test_parse_response {
+    use super::{parse_response, Response};
+
+    #[test]
+    fn test_ok() {
+        assert_eq!(
+            parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabc").unwrap(),
+            Response::Ok(b"abc".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_redirect() {
+        assert_eq!(
+            parse_response(b"HTTP/1.1 302 Found\r\nlocation: /other.crl\r\n\r\n").unwrap(),
+            Response::Redirect {
+                location: "/other.crl".to_string(),
+                same_method: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_redirect_without_location() {
+        assert_eq!(
+            parse_response(b"HTTP/1.1 301 Moved Permanently\r\n\r\n")
+                .unwrap_err()
+                .to_string(),
+            "HTTP/1.1 301 Moved Permanently without location"
+        );
+    }
+
+    #[test]
+    fn test_error_status() {
+        assert_eq!(
+            parse_response(b"HTTP/1.0 404 Not Found\r\n\r\n")
+                .unwrap_err()
+                .to_string(),
+            "HTTP/1.0 404 Not Found"
+        );
+    }
+
+    #[test]
+    fn test_incomplete() {
+        assert!(parse_response(b"HTTP/1.0 200 OK\r\n").is_err());
+    }
+}
+
+#[cfg(test)]
+mod test_connect {
+    use super::connect;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_try_all_addresses() {
+        let refused = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = connect(&[refused, listener.local_addr().unwrap()], None).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), listener.local_addr().unwrap());
+    }
+
+    #[test]
+    fn test_no_address() {
+        assert_eq!(connect(&[], None).unwrap_err().to_string(), "no address");
+    }
+}
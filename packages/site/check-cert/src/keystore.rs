@@ -0,0 +1,164 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use anyhow::{anyhow, bail, Context, Result};
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkcs12::Pkcs12;
+use openssl::x509::X509;
+use std::path::Path;
+use typed_builder::TypedBuilder;
+
+const JKS_MAGIC: u32 = 0xfeedfeed;
+const JCEKS_MAGIC: u32 = 0xcececece;
+const JKS_PRIVATE_KEY: u32 = 1;
+const JKS_TRUSTED_CERT: u32 = 2;
+const JKS_DIGEST_LENGTH: usize = 20;
+
+#[derive(Debug, TypedBuilder)]
+#[builder(field_defaults(default))]
+pub struct Config {
+    /// Password of PKCS#12 bundles and Java keystores
+    password: Option<String>,
+    /// Entry of a Java keystore, the first private key entry otherwise
+    alias: Option<String>,
+}
+
+/// Load the certificate chain from a PEM, DER, PKCS#12 or Java keystore file.
+pub fn load(path: &Path, config: Config) -> Result<Vec<Vec<u8>>> {
+    let content = std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let chain = if content.starts_with(&JKS_MAGIC.to_be_bytes())
+        || content.starts_with(&JCEKS_MAGIC.to_be_bytes())
+    {
+        from_jks(&content, config)?
+    } else if content.starts_with(b"-----BEGIN") {
+        X509::stack_from_pem(&content)?
+            .iter()
+            .flat_map(|cert| cert.to_der())
+            .collect()
+    } else if X509::from_der(&content).is_ok() {
+        vec![content]
+    } else {
+        from_pkcs12(&content, config)?
+    };
+    match chain.is_empty() {
+        true => Err(anyhow!("No certificate in {}", path.display())),
+        false => Ok(chain),
+    }
+}
+
+fn from_pkcs12(content: &[u8], config: Config) -> Result<Vec<Vec<u8>>> {
+    let parsed = Pkcs12::from_der(content)
+        .context("Unknown file format")?
+        .parse2(config.password.as_deref().unwrap_or_default())
+        .context("Cannot decrypt PKCS#12 file, wrong password?")?;
+    let mut chain = vec![];
+    if let Some(cert) = parsed.cert {
+        chain.push(cert.to_der()?);
+    }
+    for cert in parsed.ca.iter().flatten() {
+        chain.push(cert.to_der()?);
+    }
+    Ok(chain)
+}
+
+struct Reader<'a> {
+    content: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .content
+            .get(self.pos..self.pos + len)
+            .ok_or(anyhow!("Truncated Java keystore"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn utf(&mut self) -> Result<String> {
+        let len = self.u16()?.into();
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn cert(&mut self, version: u32) -> Result<Vec<u8>> {
+        if version == 2 {
+            let cert_type = self.utf()?;
+            if cert_type != "X.509" {
+                bail!("Unsupported certificate type {cert_type} in Java keystore");
+            }
+        }
+        Ok(self.bytes()?.to_vec())
+    }
+}
+
+// See `sun.security.provider.JavaKeyStore`. The certificates are stored in clear,
+// the password only protects the integrity of the keystore.
+fn from_jks(content: &[u8], config: Config) -> Result<Vec<Vec<u8>>> {
+    let Some(data_len) = content.len().checked_sub(JKS_DIGEST_LENGTH) else {
+        bail!("Truncated Java keystore");
+    };
+    if let Some(password) = &config.password {
+        let mut digest_input = password
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect::<Vec<_>>();
+        digest_input.extend_from_slice(b"Mighty Aphrodite");
+        digest_input.extend_from_slice(&content[..data_len]);
+        if *hash(MessageDigest::sha1(), &digest_input)? != content[data_len..] {
+            bail!("Java keystore was tampered with, or password was incorrect");
+        }
+    }
+
+    let mut reader = Reader {
+        content: &content[..data_len],
+        pos: 4,
+    };
+    let version = reader.u32()?;
+    if !matches!(version, 1 | 2) {
+        bail!("Unsupported Java keystore version {version}");
+    }
+    let mut key_entry = None;
+    let mut first_entry = None;
+    for _ in 0..reader.u32()? {
+        let tag = reader.u32()?;
+        let alias = reader.utf()?;
+        reader.take(8)?; // creation date
+        let chain = match tag {
+            JKS_PRIVATE_KEY => {
+                reader.bytes()?; // encrypted private key
+                (0..reader.u32()?)
+                    .map(|_| reader.cert(version))
+                    .collect::<Result<Vec<_>>>()?
+            }
+            JKS_TRUSTED_CERT => vec![reader.cert(version)?],
+            _ => bail!("Unsupported entry {alias} in Java keystore"),
+        };
+        match &config.alias {
+            // Java treats aliases case insensitive.
+            Some(wanted) if wanted.eq_ignore_ascii_case(&alias) => return Ok(chain),
+            Some(_) => continue,
+            None if tag == JKS_PRIVATE_KEY && key_entry.is_none() => key_entry = Some(chain),
+            None if first_entry.is_none() => first_entry = Some(chain),
+            None => (),
+        }
+    }
+    match &config.alias {
+        Some(alias) => Err(anyhow!("No entry {alias} in Java keystore")),
+        None => Ok(key_entry.or(first_entry).unwrap_or_default()),
+    }
+}
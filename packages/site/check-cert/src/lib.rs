@@ -5,9 +5,22 @@
 pub mod check;
 pub mod checker {
     pub mod certificate;
+    pub mod chain;
+    pub mod crl;
+    pub mod ct;
     pub mod fetcher;
+    pub mod hostname;
     pub mod info;
+    pub mod ocsp;
+    pub mod scan;
     pub mod verification;
 }
+pub mod crl;
+pub mod ct;
 pub mod fetcher;
+pub mod http;
+pub mod keystore;
+pub mod ocsp;
+pub mod scan;
+pub mod starttls;
 pub mod truststore;
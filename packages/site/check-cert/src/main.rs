@@ -5,12 +5,25 @@
 use anyhow::Result;
 use check_cert::check::{self, Levels, LevelsChecker, LevelsStrategy};
 use check_cert::checker::certificate::{self, Config as CertChecks};
+use check_cert::checker::chain::{self as chain_check, Config as ChainChecks};
+use check_cert::checker::crl::{self as crl_check, Config as CrlChecks};
+use check_cert::checker::ct::{self as ct_check, Config as CtChecks};
 use check_cert::checker::fetcher::{self as fetcher_check, Config as FetcherChecks};
+use check_cert::checker::hostname::{self, Config as HostnameChecks, OnMismatch};
 use check_cert::checker::info::{self, Config as InfoConfig};
+use check_cert::checker::ocsp::{self as ocsp_check, Config as OcspChecks};
+use check_cert::checker::scan::{self as scan_check, Config as ScanChecks, Severity};
 use check_cert::checker::verification::{self, Config as VerifChecks};
-use check_cert::fetcher::{self, Config as FetcherConfig};
+use check_cert::crl::{self, Config as CrlConfig};
+use check_cert::ct;
+use check_cert::fetcher::{self, Config as FetcherConfig, ServerCert};
+use check_cert::keystore::{self, Config as KeystoreConfig};
+use check_cert::ocsp::{self, Config as OcspConfig};
+use check_cert::scan::{self, Config as ScanConfig};
+use check_cert::starttls::Protocol;
 use check_cert::truststore;
 use clap::{Parser, ValueEnum};
+use cmk_pwstore::password_from_store;
 use std::time::Duration as StdDuration;
 use std::time::Instant;
 use time::Duration;
@@ -42,6 +55,94 @@ impl ClapPubKeyAlgorithm {
     }
 }
 
+#[derive(Debug, Clone, ValueEnum)]
+enum ClapKeyUsage {
+    DigitalSignature,
+    NonRepudiation,
+    KeyEncipherment,
+    DataEncipherment,
+    KeyAgreement,
+    KeyCertSign,
+    CrlSign,
+    EncipherOnly,
+    DecipherOnly,
+}
+
+impl ClapKeyUsage {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::DigitalSignature => "digitalSignature",
+            Self::NonRepudiation => "nonRepudiation",
+            Self::KeyEncipherment => "keyEncipherment",
+            Self::DataEncipherment => "dataEncipherment",
+            Self::KeyAgreement => "keyAgreement",
+            Self::KeyCertSign => "keyCertSign",
+            Self::CrlSign => "cRLSign",
+            Self::EncipherOnly => "encipherOnly",
+            Self::DecipherOnly => "decipherOnly",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ClapStartTls {
+    Smtp,
+    Imap,
+    Pop3,
+    Ftp,
+    Ldap,
+    Xmpp,
+    Postgres,
+}
+
+impl From<ClapStartTls> for Protocol {
+    fn from(protocol: ClapStartTls) -> Self {
+        match protocol {
+            ClapStartTls::Smtp => Self::Smtp,
+            ClapStartTls::Imap => Self::Imap,
+            ClapStartTls::Pop3 => Self::Pop3,
+            ClapStartTls::Ftp => Self::Ftp,
+            ClapStartTls::Ldap => Self::Ldap,
+            ClapStartTls::Xmpp => Self::Xmpp,
+            ClapStartTls::Postgres => Self::Postgres,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ClapOnMismatch {
+    Ok,
+    Warn,
+    Crit,
+}
+
+impl From<ClapOnMismatch> for OnMismatch {
+    fn from(state: ClapOnMismatch) -> Self {
+        match state {
+            ClapOnMismatch::Ok => Self::Ok,
+            ClapOnMismatch::Warn => Self::Warn,
+            ClapOnMismatch::Crit => Self::Crit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ClapSeverity {
+    Ok,
+    Warn,
+    Crit,
+}
+
+impl From<ClapSeverity> for Severity {
+    fn from(state: ClapSeverity) -> Self {
+        match state {
+            ClapSeverity::Ok => Self::Ok,
+            ClapSeverity::Warn => Self::Warn,
+            ClapSeverity::Crit => Self::Crit,
+        }
+    }
+}
+
 fn parse_levels<F, T1, T2, U>(strat: LevelsStrategy, lvl: Vec<T1>, mut conv: F) -> LevelsChecker<U>
 where
     T1: std::fmt::Debug,
@@ -61,8 +162,25 @@ where
 #[command(about = "check_cert", version = version::VERSION)]
 struct Args {
     /// URL to check
-    #[arg(short, long)]
-    url: String,
+    #[arg(short, long, required_unless_present = "file")]
+    url: Option<String>,
+
+    /// Check the certificate in this file instead of contacting a host
+    /// (PEM, DER, PKCS#12 or Java keystore)
+    #[arg(long, conflicts_with_all = ["url", "address", "sni", "disable_sni", "starttls", "scan"])]
+    file: Option<std::path::PathBuf>,
+
+    /// Password of the PKCS#12 file or Java keystore
+    #[arg(long, requires = "file")]
+    file_password: Option<String>,
+
+    /// Password of the PKCS#12 file or Java keystore from the password store
+    #[arg(long, requires = "file", conflicts_with = "file_password", value_parser = password_from_store)]
+    file_password_pwstore: Option<String>,
+
+    /// Alias of the entry in the Java keystore, defaults to the first private key entry
+    #[arg(long, requires = "file")]
+    file_alias: Option<String>,
 
     /// Port
     #[arg(short, long, default_value_t = 443)]
@@ -76,6 +194,38 @@ struct Args {
     #[arg(long, default_value_t = 10)]
     timeout: u64,
 
+    /// Connect to this IP address instead of resolving the URL
+    #[arg(long)]
+    address: Option<std::net::IpAddr>,
+
+    /// Request this server name with SNI instead of the URL
+    #[arg(long)]
+    sni: Option<String>,
+
+    /// Do not send SNI
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue, conflicts_with = "sni")]
+    disable_sni: bool,
+
+    /// Upgrade the connection to TLS with the given protocol (STARTTLS)
+    #[arg(long)]
+    starttls: Option<ClapStartTls>,
+
+    /// Verify that the certificate is valid for the host name (RFC 6125)
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    verify_hostname: bool,
+
+    /// Host name to verify, defaults to the SNI name or the URL
+    #[arg(long, requires = "verify_hostname")]
+    hostname: Option<String>,
+
+    /// State if the host name does not match the certificate
+    #[arg(long, default_value = "crit", requires = "verify_hostname")]
+    hostname_mismatch: ClapOnMismatch,
+
+    /// Match the subject CN if the certificate has no DNS names in the SAN
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue, requires = "verify_hostname")]
+    hostname_cn_fallback: bool,
+
     /// Expected serial
     #[arg(long)]
     serial: Option<String>,
@@ -136,6 +286,39 @@ struct Args {
     #[arg(long)]
     max_validity: Option<u32>,
 
+    /// Expected key usages
+    #[arg(long, num_args = 1..)]
+    key_usage: Option<Vec<ClapKeyUsage>>,
+
+    /// Expected extended key usages (serverAuth, clientAuth, codeSigning, emailProtection,
+    /// timeStamping, OCSPSigning, anyExtendedKeyUsage or OID)
+    ///
+    /// Certificates without the extension or with anyExtendedKeyUsage meet all expectations.
+    #[arg(long, num_args = 1..)]
+    extended_key_usage: Option<Vec<String>>,
+
+    /// Expected CA flag of the basic constraints, false for server certificates
+    #[arg(long)]
+    basic_constraints_ca: Option<bool>,
+
+    /// Check the intermediate certificates of the chain (expiration, signature algorithm and
+    /// key size)
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    chain: bool,
+
+    /// Expiration levels of the intermediate certificates in seconds [WARN CRIT],
+    /// defaults to the levels of the server certificate
+    #[arg(long, num_args = 2)]
+    chain_not_after: Option<Vec<u32>>,
+
+    /// Minimum size of the RSA and DSA keys of the intermediate certificates
+    #[arg(long)]
+    chain_pubkey_min_size: Option<usize>,
+
+    /// Also check the root certificate, from the chain or the trust store
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    chain_include_root: bool,
+
     /// Overall response time levels in seconds [WARN CRIT]
     #[arg(
         long,
@@ -151,6 +334,57 @@ struct Args {
     /// Allow self-signed certificates
     #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
     allow_self_signed: bool,
+
+    /// Check the revocation status via OCSP (stapled response or responder)
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    ocsp: bool,
+
+    /// Query this OCSP responder instead of the one from the certificate
+    #[arg(long, requires = "ocsp")]
+    ocsp_url: Option<String>,
+
+    /// OCSP response age levels in seconds [WARN CRIT]
+    #[arg(long, num_args = 2, requires = "ocsp")]
+    ocsp_max_age: Option<Vec<u32>>,
+
+    /// Check the revocation status with the CRLs from the distribution points of the certificates
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    crl: bool,
+
+    /// Check the revocation status with the CRLs in this file (PEM or DER, can be repeated)
+    ///
+    /// A missing CRL is only an error for certificates with a CRL distribution point.
+    #[arg(long)]
+    crl_file: Vec<std::path::PathBuf>,
+
+    /// Keep the CRLs fetched from distribution points in this directory until their next update
+    #[arg(long, requires = "crl")]
+    crl_cache_dir: Option<std::path::PathBuf>,
+
+    /// Verify the signed certificate timestamps against the logs in this CT log list (JSON)
+    #[arg(long)]
+    ct_log_list: Option<std::path::PathBuf>,
+
+    /// Levels on the number of distinct CT logs with a valid signed certificate timestamp
+    /// [WARN CRIT]
+    #[arg(long, num_args = 2, default_values_t = [2, 1], requires = "ct_log_list")]
+    ct_valid_scts: Vec<usize>,
+
+    /// Probe the TLS versions and cipher suites accepted by the server
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    scan: bool,
+
+    /// Enumerate all accepted cipher suites instead of the preferred one per TLS version
+    #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue, requires = "scan")]
+    scan_ciphers: bool,
+
+    /// State if the server accepts TLS 1.0 or TLS 1.1
+    #[arg(long, default_value = "crit", requires = "scan")]
+    scan_deprecated: ClapSeverity,
+
+    /// State if the server accepts weak cipher suites (NULL, export, RC4, DES, MD5, anonymous)
+    #[arg(long, default_value = "warn", requires = "scan")]
+    scan_weak_cipher: ClapSeverity,
 }
 
 fn verbose(verbosity: u8, level: u8, header: &str, text: &str) {
@@ -178,6 +412,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     info("start check-cert");
 
+    let chain_not_after = parse_levels(
+        LevelsStrategy::Lower,
+        args.chain_not_after.unwrap_or(args.not_after.clone()),
+        Duration::seconds,
+    );
     let not_after = parse_levels(LevelsStrategy::Lower, args.not_after, Duration::seconds);
     let response_time = parse_levels(
         LevelsStrategy::Upper,
@@ -194,26 +433,68 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
     info(&format!("loaded {} certificates", trust_store.len()));
 
-    info("contact host...");
-    let start = Instant::now();
-    let chain = match fetcher::fetch_server_cert(
-        &args.url,
-        args.port,
-        FetcherConfig::builder()
-            .timeout((args.timeout != 0).then_some(StdDuration::new(args.timeout, 0)))
-            .build(),
-    ) {
-        Ok(chain) => chain,
-        Err(err) => check::abort(format!("{:?}", err)),
+    let connection = FetcherConfig::builder()
+        .timeout((args.timeout != 0).then_some(StdDuration::new(args.timeout, 0)))
+        .starttls(args.starttls.map(Protocol::from))
+        .address(args.address)
+        .sni(args.sni.clone())
+        .disable_sni(args.disable_sni)
+        .build();
+    let (server_cert, mut collection) = match (&args.file, &args.url) {
+        (Some(file), _) => {
+            info("load certificate file...");
+            let chain = match keystore::load(
+                file,
+                KeystoreConfig::builder()
+                    .password(args.file_password.or(args.file_password_pwstore))
+                    .alias(args.file_alias)
+                    .build(),
+            ) {
+                Ok(chain) => chain,
+                Err(err) => check::abort(format!("{err:#}")),
+            };
+            let server_cert = ServerCert {
+                chain,
+                ocsp_response: None,
+                sct_list: None,
+            };
+            (server_cert, info::collect_file(file))
+        }
+        (None, Some(url)) => {
+            info("contact host...");
+            let start = Instant::now();
+            let server_cert = match fetcher::fetch_server_cert(url, args.port, connection.clone()) {
+                Ok(server_cert) => server_cert,
+                Err(err) => check::abort(format!("{:?}", err)),
+            };
+            let elapsed = start.elapsed();
+            info(" 1/8 - check fetching process");
+            let mut collection = info::collect(
+                InfoConfig::builder()
+                    .server(url)
+                    .port(args.port)
+                    .address(args.address)
+                    .sni(match args.disable_sni {
+                        true => Some("disabled"),
+                        false => args.sni.as_deref(),
+                    })
+                    .build(),
+            );
+            collection.join(&mut fetcher_check::check(
+                time::Duration::new(elapsed.as_secs() as i64, elapsed.subsec_nanos() as i32),
+                FetcherChecks::builder()
+                    .response_time(Some(response_time))
+                    .build(),
+            ));
+            (server_cert, collection)
+        }
+        (None, None) => check::bail_out("Either --url or --file is required"),
     };
-    let elapsed = start.elapsed();
-    info(&format!(
-        "received chain of {} certificates from host",
-        chain.len()
-    ));
+    let chain = server_cert.chain;
+    info(&format!("received chain of {} certificates", chain.len()));
 
     if chain.is_empty() {
-        check::abort("Empty or invalid certificate chain on host")
+        check::abort("Empty or invalid certificate chain")
     }
 
     info("check certificate...");
@@ -221,20 +502,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "\n{}",
         std::str::from_utf8(&to_pem(&chain[0])).expect("valid utf8")
     ));
-    info(" 1/3 - check fetching process");
-    let mut collection = info::collect(
-        InfoConfig::builder()
-            .server(&args.url)
-            .port(args.port)
-            .build(),
-    );
-    collection.join(&mut fetcher_check::check(
-        time::Duration::new(elapsed.as_secs() as i64, elapsed.subsec_nanos() as i32),
-        FetcherChecks::builder()
-            .response_time(Some(response_time))
-            .build(),
-    ));
-    info(" 2/3 - verify certificate with trust store");
+    info(" 2/8 - verify certificate with trust store");
     collection.join(&mut verification::check(
         &chain,
         VerifChecks::builder()
@@ -242,7 +510,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .allow_self_signed(args.allow_self_signed)
             .build(),
     ));
-    info(" 3/3 - check certificate");
+    info(" 3/8 - check certificate");
     collection.join(&mut certificate::check(
         &chain[0],
         CertChecks::builder()
@@ -261,8 +529,124 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .pubkey_size(args.pubkey_size)
             .not_after(Some(not_after))
             .max_validity(args.max_validity.map(|x| Duration::days(x.into())))
+            .key_usage(
+                args.key_usage
+                    .map(|usages| usages.iter().map(|u| String::from(u.as_str())).collect()),
+            )
+            .extended_key_usage(args.extended_key_usage)
+            .basic_constraints_ca(args.basic_constraints_ca)
             .build(),
     ));
+    if args.verify_hostname {
+        let Some(name) = args.hostname.or(args.sni).or(args.url.clone()) else {
+            check::bail_out("--hostname is required to verify the host name of a file")
+        };
+        collection.join(&mut hostname::check(
+            &chain[0],
+            HostnameChecks::builder()
+                .hostname(name)
+                .cn_fallback(args.hostname_cn_fallback)
+                .on_mismatch(args.hostname_mismatch.into())
+                .build(),
+        ));
+    }
+    if args.chain {
+        info(" 4/8 - check certificate chain");
+        collection.join(&mut chain_check::check(
+            &chain,
+            ChainChecks::builder()
+                .trust_store(&trust_store)
+                .not_after(Some(chain_not_after))
+                .pubkey_min_size(args.chain_pubkey_min_size)
+                .include_root(args.chain_include_root)
+                .build(),
+        ));
+    }
+    if args.ocsp {
+        info(" 5/8 - check revocation status via OCSP");
+        let response = ocsp::obtain(
+            &chain,
+            &trust_store,
+            server_cert.ocsp_response.clone(),
+            OcspConfig::builder()
+                .responder(args.ocsp_url)
+                .timeout((args.timeout != 0).then_some(StdDuration::new(args.timeout, 0)))
+                .build(),
+        );
+        collection.join(&mut ocsp_check::check(
+            &chain,
+            response,
+            OcspChecks::builder()
+                .trust_store(&trust_store)
+                .max_age(
+                    args.ocsp_max_age.map(|levels| {
+                        parse_levels(LevelsStrategy::Upper, levels, Duration::seconds)
+                    }),
+                )
+                .build(),
+        ));
+    }
+    if args.crl || !args.crl_file.is_empty() {
+        info(" 6/8 - check revocation status via CRL");
+        let crls = crl::load(
+            &chain,
+            CrlConfig::builder()
+                .files(args.crl_file)
+                .fetch(args.crl)
+                .cache_dir(args.crl_cache_dir)
+                .timeout((args.timeout != 0).then_some(StdDuration::new(args.timeout, 0)))
+                .build(),
+        );
+        info(&format!("loaded {} CRLs", crls.crls.len()));
+        collection.join(&mut crl_check::check(
+            &chain,
+            &crls,
+            CrlChecks::builder()
+                .trust_store(&trust_store)
+                .fetched(args.crl)
+                .build(),
+        ));
+    }
+    if let Some(ct_log_list) = args.ct_log_list {
+        info(" 7/8 - check certificate transparency");
+        let logs = match ct::load_logs(&ct_log_list) {
+            Ok(logs) => logs,
+            Err(err) => check::abort(format!("{err:#}")),
+        };
+        info(&format!("loaded {} CT logs", logs.len()));
+        collection.join(&mut ct_check::check(
+            &chain,
+            ct::collect(
+                &chain[0],
+                server_cert.sct_list.as_deref(),
+                server_cert.ocsp_response.as_deref(),
+            ),
+            CtChecks::builder()
+                .trust_store(&trust_store)
+                .logs(&logs)
+                .valid_scts(Some(parse_levels(
+                    LevelsStrategy::Lower,
+                    args.ct_valid_scts,
+                    |x: usize| x,
+                )))
+                .build(),
+        ));
+    }
+    if let (true, Some(url)) = (args.scan, &args.url) {
+        info(" 8/8 - scan TLS versions and cipher suites");
+        collection.join(&mut scan_check::check(
+            scan::scan(
+                url,
+                args.port,
+                &connection,
+                ScanConfig::builder().ciphers(args.scan_ciphers).build(),
+            ),
+            ScanChecks::builder()
+                .deprecated_version(args.scan_deprecated.into())
+                .weak_cipher(args.scan_weak_cipher.into())
+                .build(),
+        ));
+    }
     info("check certificate... done");
 
     println!("{}", collection);
@@ -0,0 +1,88 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::http;
+use crate::truststore;
+use anyhow::{anyhow, Context, Result};
+use openssl::hash::MessageDigest;
+use openssl::ocsp::{OcspCertId, OcspRequest};
+use openssl::x509::X509;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::time::Duration;
+use typed_builder::TypedBuilder;
+
+#[derive(Debug, TypedBuilder)]
+#[builder(field_defaults(default))]
+pub struct Config {
+    /// Query this responder instead of the one from the certificate
+    responder: Option<String>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Stapled,
+    Responder(String),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter) -> FormatResult {
+        match self {
+            Self::Stapled => write!(f, "stapled response"),
+            Self::Responder(url) => write!(f, "responder {url}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub source: Source,
+    pub der: Vec<u8>,
+}
+
+/// Return the stapled OCSP response or query the responder otherwise.
+pub fn obtain(
+    chain: &[Vec<u8>],
+    trust_store: &[Vec<u8>],
+    stapled: Option<Vec<u8>>,
+    config: Config,
+) -> Result<Response> {
+    assert!(!chain.is_empty());
+
+    if let Some(der) = stapled {
+        return Ok(Response {
+            source: Source::Stapled,
+            der,
+        });
+    }
+    let cert = X509::from_der(&chain[0])?;
+    let url = match config.responder {
+        Some(url) => url,
+        // Fails without Authority Information Access extension
+        None => cert
+            .ocsp_responders()
+            .ok()
+            .and_then(|urls| urls.iter().next().map(|url| url.to_string()))
+            .ok_or(anyhow!("No OCSP responder in certificate"))?,
+    };
+    let issuer = truststore::find_issuer(&cert, &chain[1..], trust_store)
+        .ok_or(anyhow!("Cannot find issuer certificate"))?;
+    let mut request = OcspRequest::new()?;
+    request.add_id(OcspCertId::from_cert(
+        MessageDigest::sha1(),
+        &cert,
+        &issuer,
+    )?)?;
+    let der = http::post(
+        &url,
+        "application/ocsp-request",
+        &request.to_der()?,
+        config.timeout,
+    )
+    .with_context(|| format!("Cannot query OCSP responder {url}"))?;
+    Ok(Response {
+        source: Source::Responder(url),
+        der,
+    })
+}
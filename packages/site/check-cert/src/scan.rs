@@ -0,0 +1,231 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use crate::fetcher::{self, Config as ConnectionConfig};
+use anyhow::Result;
+use openssl::ssl::{HandshakeError, SslConnector, SslMethod, SslVerifyMode, SslVersion};
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::net::TcpStream;
+use typed_builder::TypedBuilder;
+
+// Security level 0 is required to offer the deprecated versions and ciphers at all.
+const TLS12_CIPHERS: &str = "ALL:COMPLEMENTOFALL";
+const TLS13_CIPHERSUITES: [&str; 5] = [
+    "TLS_AES_256_GCM_SHA384",
+    "TLS_CHACHA20_POLY1305_SHA256",
+    "TLS_AES_128_GCM_SHA256",
+    "TLS_AES_128_CCM_SHA256",
+    "TLS_AES_128_CCM_8_SHA256",
+];
+// OpenSSL's reasons for the alerts a server sends when it doesn't support the offered version
+// or none of the offered ciphers.
+const REFUSAL_ALERTS: [&str; 3] = [
+    "tlsv1 alert protocol version",
+    "sslv3 alert handshake failure",
+    "tlsv1 alert insufficient security",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    Tls1_0,
+    Tls1_1,
+    Tls1_2,
+    Tls1_3,
+}
+
+impl Version {
+    pub const ALL: [Self; 4] = [Self::Tls1_0, Self::Tls1_1, Self::Tls1_2, Self::Tls1_3];
+
+    pub fn is_deprecated(&self) -> bool {
+        matches!(self, Self::Tls1_0 | Self::Tls1_1)
+    }
+
+    fn as_ssl_version(&self) -> SslVersion {
+        match self {
+            Self::Tls1_0 => SslVersion::TLS1,
+            Self::Tls1_1 => SslVersion::TLS1_1,
+            Self::Tls1_2 => SslVersion::TLS1_2,
+            Self::Tls1_3 => SslVersion::TLS1_3,
+        }
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter) -> FormatResult {
+        match self {
+            Self::Tls1_0 => write!(f, "TLSv1.0"),
+            Self::Tls1_1 => write!(f, "TLSv1.1"),
+            Self::Tls1_2 => write!(f, "TLSv1.2"),
+            Self::Tls1_3 => write!(f, "TLSv1.3"),
+        }
+    }
+}
+
+#[derive(Debug, TypedBuilder)]
+#[builder(field_defaults(default))]
+pub struct Config {
+    /// Enumerate all cipher suites instead of the preferred one per version
+    ciphers: bool,
+}
+
+#[derive(Debug)]
+pub struct Protocol {
+    pub version: Version,
+    pub accepted: bool,
+    /// OpenSSL names of the cipher suites accepted by the server
+    pub ciphers: Vec<String>,
+    /// Why we couldn't tell whether the server accepts the version
+    pub error: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Probe {
+    Accepted(String),
+    Refused,
+    Failed(String),
+}
+
+/// Probe the TLS versions and cipher suites the server accepts.
+pub fn scan(
+    server: &str,
+    port: u16,
+    connection: &ConnectionConfig,
+    config: Config,
+) -> Result<Vec<Protocol>> {
+    let mut protocols = vec![];
+    for version in Version::ALL {
+        let mut ciphers: Vec<String> = vec![];
+        let mut error = None;
+        // The server picks one of the offered ciphers, offer the remaining ones next.
+        loop {
+            match probe(server, port, connection, version, &ciphers)? {
+                Probe::Accepted(cipher) if !ciphers.contains(&cipher) => ciphers.push(cipher),
+                Probe::Accepted(_) | Probe::Refused => break,
+                // Once the server accepted the version, we merely ran out of ciphers to offer.
+                Probe::Failed(_) if !ciphers.is_empty() => break,
+                Probe::Failed(err) => {
+                    error = Some(err);
+                    break;
+                }
+            }
+            if !config.ciphers {
+                break;
+            }
+        }
+        protocols.push(Protocol {
+            version,
+            accepted: !ciphers.is_empty(),
+            ciphers,
+            error,
+        });
+    }
+    Ok(protocols)
+}
+
+/// Offer the version with all ciphers but the excluded ones.
+fn probe(
+    server: &str,
+    port: u16,
+    connection: &ConnectionConfig,
+    version: Version,
+    excluded: &[String],
+) -> Result<Probe> {
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+    builder.set_verify(SslVerifyMode::NONE);
+    builder.set_security_level(0);
+    builder.set_min_proto_version(Some(version.as_ssl_version()))?;
+    builder.set_max_proto_version(Some(version.as_ssl_version()))?;
+    if version == Version::Tls1_3 {
+        let suites = TLS13_CIPHERSUITES
+            .iter()
+            .filter(|suite| !excluded.iter().any(|cipher| cipher == *suite))
+            .copied()
+            .collect::<Vec<_>>();
+        if suites.is_empty() {
+            return Ok(Probe::Refused);
+        }
+        builder.set_ciphersuites(&suites.join(":"))?;
+    } else {
+        let ciphers = excluded
+            .iter()
+            .fold(String::from(TLS12_CIPHERS), |ciphers, cipher| {
+                format!("{ciphers}:!{cipher}")
+            });
+        // Fails if no cipher is left.
+        if builder.set_cipher_list(&ciphers).is_err() {
+            return Ok(Probe::Refused);
+        }
+    }
+    handshake(&builder.build(), server, port, connection)
+}
+
+fn handshake(
+    connector: &SslConnector,
+    server: &str,
+    port: u16,
+    connection: &ConnectionConfig,
+) -> Result<Probe> {
+    let stream = fetcher::connect(server, port, connection)?;
+    let err = match fetcher::handshake(connector, server, stream, connection) {
+        Ok(stream) => {
+            return Ok(match stream.ssl().current_cipher() {
+                Some(cipher) => Probe::Accepted(cipher.name().to_string()),
+                None => Probe::Failed("no cipher negotiated".to_string()),
+            })
+        }
+        Err(err) => err,
+    };
+    // Only an alert from the server tells us it doesn't accept what we offered. We may just as
+    // well have failed to offer the version at all.
+    let refused = match err.downcast_ref::<HandshakeError<TcpStream>>() {
+        Some(HandshakeError::Failure(stream)) => stream
+            .error()
+            .ssl_error()
+            .map(|stack| {
+                stack
+                    .errors()
+                    .iter()
+                    .any(|err| err.reason().is_some_and(|r| REFUSAL_ALERTS.contains(&r)))
+            })
+            .unwrap_or_default(),
+        _ => false,
+    };
+    Ok(match refused {
+        true => Probe::Refused,
+        false => Probe::Failed(err.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod test_handshake {
+    use super::{handshake, Probe};
+    use crate::fetcher::Config as ConnectionConfig;
+    use openssl::ssl::{SslConnector, SslMethod, SslVersion};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_version_not_offered() {
+        // Nobody needs to answer, the client fails before sending anything.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // The cipher suite requires TLS 1.2, so there is nothing to offer with TLS 1.0.
+        let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+        builder.set_security_level(0);
+        builder
+            .set_min_proto_version(Some(SslVersion::TLS1))
+            .unwrap();
+        builder
+            .set_max_proto_version(Some(SslVersion::TLS1))
+            .unwrap();
+        builder.set_cipher_list("AES128-GCM-SHA256").unwrap();
+        let probe = handshake(
+            &builder.build(),
+            "127.0.0.1",
+            port,
+            &ConnectionConfig::builder().timeout(None).build(),
+        )
+        .unwrap();
+        assert!(matches!(probe, Probe::Failed(_)), "{probe:?}");
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// The plaintext preambles of the protocols that upgrade their connection to TLS.
+// We only speak as much of each protocol as needed to get to the TLS handshake.
+
+use anyhow::{anyhow, bail, Result};
+use std::io::{Read, Write};
+
+const LDAP_STARTTLS_OID: &[u8] = b"1.3.6.1.4.1.1466.20037";
+const POSTGRES_SSL_REQUEST_CODE: u32 = 80877103;
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Smtp,
+    Imap,
+    Pop3,
+    Ftp,
+    Ldap,
+    Xmpp,
+    Postgres,
+}
+
+impl Protocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Smtp => "SMTP",
+            Self::Imap => "IMAP",
+            Self::Pop3 => "POP3",
+            Self::Ftp => "FTP",
+            Self::Ldap => "LDAP",
+            Self::Xmpp => "XMPP",
+            Self::Postgres => "PostgreSQL",
+        }
+    }
+}
+
+/// Speak the plaintext part of the protocol until the server is ready for the TLS handshake.
+pub fn negotiate<S: Read + Write>(stream: &mut S, protocol: Protocol, server: &str) -> Result<()> {
+    match protocol {
+        Protocol::Smtp => smtp(stream),
+        Protocol::Imap => imap(stream),
+        Protocol::Pop3 => pop3(stream),
+        Protocol::Ftp => ftp(stream),
+        Protocol::Ldap => ldap(stream),
+        Protocol::Xmpp => xmpp(stream, server),
+        Protocol::Postgres => postgres(stream),
+    }
+    .map_err(|err| anyhow!("{} STARTTLS failed: {}", protocol.as_str(), err))
+}
+
+fn smtp<S: Read + Write>(stream: &mut S) -> Result<()> {
+    expect_reply(stream, "220")?;
+    send(stream, b"EHLO localhost\r\n")?;
+    let capabilities = expect_reply(stream, "250")?;
+    if !capabilities.iter().any(|line| {
+        line.get(4..)
+            .unwrap_or_default()
+            .trim()
+            .eq_ignore_ascii_case("STARTTLS")
+    }) {
+        bail!("not offered by server");
+    }
+    send(stream, b"STARTTLS\r\n")?;
+    expect_reply(stream, "220")?;
+    Ok(())
+}
+
+fn imap<S: Read + Write>(stream: &mut S) -> Result<()> {
+    expect_line(stream, "* OK")?;
+    send(stream, b"a001 STARTTLS\r\n")?;
+    // Untagged responses may precede the tagged one.
+    loop {
+        let line = read_line(stream)?;
+        if line.starts_with("a001 ") {
+            return match line.starts_with("a001 OK") {
+                true => Ok(()),
+                false => Err(anyhow!("unexpected response: {}", line)),
+            };
+        }
+    }
+}
+
+fn pop3<S: Read + Write>(stream: &mut S) -> Result<()> {
+    expect_line(stream, "+OK")?;
+    send(stream, b"STLS\r\n")?;
+    expect_line(stream, "+OK")?;
+    Ok(())
+}
+
+fn ftp<S: Read + Write>(stream: &mut S) -> Result<()> {
+    expect_reply(stream, "220")?;
+    send(stream, b"AUTH TLS\r\n")?;
+    expect_reply(stream, "234")?;
+    Ok(())
+}
+
+// LDAPMessage { messageID 1, ExtendedRequest { requestName StartTLS } } in BER
+fn ldap<S: Read + Write>(stream: &mut S) -> Result<()> {
+    let oid_len = LDAP_STARTTLS_OID.len() as u8;
+    let mut request = vec![
+        0x30,
+        oid_len + 7,
+        0x02,
+        0x01,
+        0x01,
+        0x77,
+        oid_len + 2,
+        0x80,
+        oid_len,
+    ];
+    request.extend_from_slice(LDAP_STARTTLS_OID);
+    send(stream, &request)?;
+
+    let response = read_ber(stream)?;
+    // messageID, then the ExtendedResponse starting with its resultCode
+    let Some(extended_response) = response.strip_prefix(&[0x02, 0x01, 0x01, 0x78]) else {
+        bail!("unexpected response");
+    };
+    let mut bytes = extended_response.iter();
+    // Active Directory encodes the length in long form even when it's short.
+    read_length(|| {
+        bytes
+            .next()
+            .copied()
+            .ok_or_else(|| anyhow!("unexpected response"))
+    })?;
+    match bytes.as_slice() {
+        [0x0a, 0x01, 0x00, ..] => Ok(()),
+        [0x0a, 0x01, code, ..] => Err(anyhow!("server returned result code {}", code)),
+        _ => Err(anyhow!("unexpected response")),
+    }
+}
+
+fn xmpp<S: Read + Write>(stream: &mut S, server: &str) -> Result<()> {
+    send(
+        stream,
+        format!(
+            "<?xml version='1.0'?><stream:stream xmlns='jabber:client' \
+             xmlns:stream='http://etherx.jabber.org/streams' to='{server}' version='1.0'>"
+        )
+        .as_bytes(),
+    )?;
+    let features = read_until(stream, "</stream:features>")?;
+    if !features.contains("<starttls") {
+        bail!("not offered by server");
+    }
+    send(
+        stream,
+        b"<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>",
+    )?;
+    let response = read_until(stream, "/>")?;
+    if !response.contains("<proceed") {
+        bail!("unexpected response: {}", response.trim());
+    }
+    Ok(())
+}
+
+fn postgres<S: Read + Write>(stream: &mut S) -> Result<()> {
+    let mut request = 8u32.to_be_bytes().to_vec();
+    request.extend_from_slice(&POSTGRES_SSL_REQUEST_CODE.to_be_bytes());
+    send(stream, &request)?;
+    match read_byte(stream)? {
+        b'S' => Ok(()),
+        b'N' => Err(anyhow!("not supported by server")),
+        other => Err(anyhow!("unexpected response: {:#04x}", other)),
+    }
+}
+
+fn send<S: Write>(stream: &mut S, data: &[u8]) -> Result<()> {
+    stream.write_all(data)?;
+    stream.flush()?;
+    Ok(())
+}
+
+// We must not read beyond the plaintext part, so we read byte by byte instead of buffering.
+fn read_byte<S: Read>(stream: &mut S) -> Result<u8> {
+    let mut byte = [0u8];
+    stream.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_line<S: Read>(stream: &mut S) -> Result<String> {
+    let mut line = vec![];
+    while !line.ends_with(b"\n") {
+        if line.len() > MAX_RESPONSE_SIZE {
+            bail!("response too long");
+        }
+        line.push(read_byte(stream)?);
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+fn read_until<S: Read>(stream: &mut S, end: &str) -> Result<String> {
+    let mut data = vec![];
+    while !data.ends_with(end.as_bytes()) {
+        if data.len() > MAX_RESPONSE_SIZE {
+            bail!("response too long");
+        }
+        data.push(read_byte(stream)?);
+    }
+    Ok(String::from_utf8_lossy(&data).to_string())
+}
+
+fn expect_line<S: Read>(stream: &mut S, prefix: &str) -> Result<String> {
+    let line = read_line(stream)?;
+    match line.starts_with(prefix) {
+        true => Ok(line),
+        false => Err(anyhow!("unexpected response: {}", line)),
+    }
+}
+
+// SMTP and FTP replies may span several lines ("250-..."), the last one reads "250 ...".
+fn expect_reply<S: Read>(stream: &mut S, code: &str) -> Result<Vec<String>> {
+    let mut lines = vec![];
+    loop {
+        let line = read_line(stream)?;
+        if !line.starts_with(code) {
+            bail!("unexpected response: {}", line);
+        }
+        let last = line.as_bytes().get(3) != Some(&b'-');
+        lines.push(line);
+        if last {
+            return Ok(lines);
+        }
+    }
+}
+
+// Read a BER encoded SEQUENCE and return its content.
+fn read_ber<S: Read>(stream: &mut S) -> Result<Vec<u8>> {
+    if read_byte(stream)? != 0x30 {
+        bail!("unexpected response");
+    }
+    let len = read_length(|| read_byte(stream))?;
+    if len > MAX_RESPONSE_SIZE {
+        bail!("response too long");
+    }
+    let mut content = vec![0u8; len];
+    stream.read_exact(&mut content)?;
+    Ok(content)
+}
+
+// BER lengths come in short form (< 128) or in long form, with the number of length bytes first.
+fn read_length<F: FnMut() -> Result<u8>>(mut next_byte: F) -> Result<usize> {
+    Ok(match next_byte()? {
+        len @ 0..=0x7f => len as usize,
+        long => {
+            let mut len = 0usize;
+            for _ in 0..(long & 0x7f).min(4) {
+                len = len << 8 | next_byte()? as usize;
+            }
+            len
+        }
+    })
+}
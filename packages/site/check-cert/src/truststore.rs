@@ -4,7 +4,7 @@
 
 use anyhow::{anyhow, Result};
 use openssl::x509::store::X509StoreBuilder;
-use openssl::x509::{X509Ref, X509};
+use openssl::x509::{X509Ref, X509VerifyResult, X509};
 use std::path::Path;
 
 pub fn system() -> Result<Vec<Vec<u8>>> {
@@ -51,6 +51,19 @@ pub fn load_store(path: &impl AsRef<Path>) -> Result<Vec<Vec<u8>>> {
         .ok_or(anyhow!("CA store not found"))
 }
 
+/// Find the certificate that issued `cert` among the intermediates or in the trust store.
+pub fn find_issuer(
+    cert: &X509Ref,
+    intermediates: &[Vec<u8>],
+    trust_store: &[Vec<u8>],
+) -> Option<X509> {
+    intermediates
+        .iter()
+        .chain(trust_store)
+        .flat_map(|der| X509::from_der(der))
+        .find(|candidate| candidate.issued(cert) == X509VerifyResult::OK)
+}
+
 #[cfg(test)]
 mod test {
     use super::try_to_parse;
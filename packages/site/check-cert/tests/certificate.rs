@@ -103,3 +103,110 @@ fn test_signature_algorithm_ee_pss_sha1_wrong_alg() {
         )
     );
 }
+
+fn strings(values: &[&str]) -> Option<Vec<String>> {
+    Some(values.iter().map(|value| value.to_string()).collect())
+}
+
+#[test]
+fn test_key_usage() {
+    // Digital Signature, Key Encipherment
+    static DER: &[u8] = include_bytes!("../assets/certificate.der");
+
+    let coll = certificate::check(
+        DER,
+        CertConfig::builder()
+            .key_usage(strings(&["keyEncipherment", "digitalSignature"]))
+            .build(),
+    );
+    assert_that!(coll.to_string()).contains("\nKey usage: digitalSignature, keyEncipherment");
+
+    let coll = certificate::check(
+        DER,
+        CertConfig::builder()
+            .key_usage(strings(&["digitalSignature", "keyAgreement"]))
+            .build(),
+    );
+    assert_that!(coll.to_string()).contains("Key usage: missing keyAgreement (!)");
+    assert_that!(coll.to_string()).contains(
+        "Key usage: digitalSignature, keyEncipherment but expected digitalSignature, keyAgreement (!)",
+    );
+}
+
+#[test]
+fn test_extended_key_usage() {
+    // TLS Web Server Authentication, TLS Web Client Authentication
+    static DER: &[u8] = include_bytes!("../assets/certificate.der");
+
+    let coll = certificate::check(
+        DER,
+        CertConfig::builder()
+            .extended_key_usage(strings(&["serverAuth"]))
+            .build(),
+    );
+    assert_that!(coll.to_string()).contains("\nExtended key usage: serverAuth, clientAuth");
+
+    let coll = certificate::check(
+        DER,
+        CertConfig::builder()
+            .extended_key_usage(strings(&["serverAuth", "codeSigning"]))
+            .build(),
+    );
+    assert_that!(coll.to_string()).contains("Extended key usage: missing codeSigning (!)");
+}
+
+#[test]
+fn test_any_extended_key_usage() {
+    static DER: &[u8] = include_bytes!("../assets/any-eku.der");
+
+    let coll = certificate::check(
+        DER,
+        CertConfig::builder()
+            .extended_key_usage(strings(&["serverAuth"]))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_that!(coll.to_string()).contains("\nExtended key usage: anyExtendedKeyUsage");
+}
+
+#[test]
+fn test_no_usage_extensions() {
+    static DER: &[u8] = include_bytes!("../assets/cert.der");
+
+    let coll = certificate::check(
+        DER,
+        CertConfig::builder()
+            .key_usage(strings(&["digitalSignature"]))
+            .extended_key_usage(strings(&["serverAuth"]))
+            .basic_constraints_ca(Some(false))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).contains("No key usage (!)");
+    // Without the extension, the extended key usage is unrestricted.
+    assert_that!(coll.to_string()).contains("\nNo extended key usage (unrestricted)");
+    assert_that!(coll.to_string()).contains("\nBasic constraints: CA:FALSE");
+}
+
+#[test]
+fn test_basic_constraints() {
+    static LEAF: &[u8] = include_bytes!("../assets/certificate.der");
+    static CA: &[u8] = include_bytes!("../assets/hostname/wildcard.der");
+
+    let coll = certificate::check(
+        LEAF,
+        CertConfig::builder()
+            .basic_constraints_ca(Some(false))
+            .build(),
+    );
+    assert_that!(coll.to_string()).contains("\nBasic constraints: CA:FALSE");
+
+    let coll = certificate::check(
+        CA,
+        CertConfig::builder()
+            .basic_constraints_ca(Some(false))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).contains("Basic constraints: CA:TRUE but expected CA:FALSE (!)");
+}
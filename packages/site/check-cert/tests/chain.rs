@@ -0,0 +1,146 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// The weak intermediate has a 1024 bit key and a SHA-1 signature, the other one
+// expired in 2021. Everything else is valid until 2126.
+
+use assertor::*;
+use check_cert::check::{self, Levels, LevelsChecker, LevelsStrategy};
+use check_cert::checker::chain::{self, Config};
+use time::Duration;
+
+static ROOT: &[u8] = include_bytes!("../assets/chain/root.der");
+static WEAK: &[u8] = include_bytes!("../assets/chain/weak.der");
+static EXPIRED: &[u8] = include_bytes!("../assets/chain/expired.der");
+static LEAF_WEAK: &[u8] = include_bytes!("../assets/chain/leaf-weak.der");
+static LEAF_EXPIRED: &[u8] = include_bytes!("../assets/chain/leaf-expired.der");
+
+fn not_after(warn: i64, crit: i64) -> Option<LevelsChecker<Duration>> {
+    Some(
+        LevelsChecker::try_new(
+            LevelsStrategy::Lower,
+            Levels::from(&mut [Duration::days(warn), Duration::days(crit)]),
+        )
+        .unwrap(),
+    )
+}
+
+#[test]
+fn test_valid_chain() {
+    let coll = chain::check(
+        &[LEAF_WEAK.to_vec(), WEAK.to_vec(), ROOT.to_vec()],
+        Config::builder()
+            .trust_store(&[])
+            .not_after(not_after(30, 0))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string())
+        .contains("\nIntermediate certificate Chain Test Weak CA validity: ");
+    assert_that!(coll.to_string())
+        .contains("\nIntermediate certificate Chain Test Weak CA public key: RSA 1024 bit(s)\n");
+    assert_that!(coll.to_string()).contains("Earliest expiry in chain: ");
+    assert_that!(coll.to_string()).contains("certificate_chain_remaining_validity=");
+    // The root is skipped.
+    assert_that!(coll.to_string()).does_not_contain("Root certificate");
+}
+
+#[test]
+fn test_weak_signature() {
+    let coll = chain::check(
+        &[LEAF_WEAK.to_vec(), WEAK.to_vec()],
+        Config::builder().trust_store(&[]).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).starts_with(
+        "Intermediate certificate Chain Test Weak CA signature algorithm: sha1WithRSAEncryption is weak (!)",
+    );
+}
+
+#[test]
+fn test_pubkey_min_size() {
+    let coll = chain::check(
+        &[LEAF_WEAK.to_vec(), WEAK.to_vec()],
+        Config::builder()
+            .trust_store(&[])
+            .pubkey_min_size(Some(2048))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).contains(
+        "Intermediate certificate Chain Test Weak CA public key: RSA 1024 bit(s) but expected at least 2048 (!)",
+    );
+}
+
+#[test]
+fn test_expired_intermediate() {
+    let coll = chain::check(
+        &[LEAF_EXPIRED.to_vec(), EXPIRED.to_vec()],
+        Config::builder()
+            .trust_store(&[])
+            .not_after(not_after(30, 0))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_that!(coll.to_string()).starts_with(
+        "Intermediate certificate Chain Test Expired CA expired (Jan  1 00:00:00 2021 +00:00) (!!)",
+    );
+    assert_that!(coll.to_string())
+        .contains("Earliest expiry in chain: Chain Test Expired CA on Jan  1 00:00:00 2021 +00:00");
+    assert_that!(coll.to_string()).contains("certificate_chain_remaining_validity=-");
+}
+
+#[test]
+fn test_expiring_intermediate() {
+    let coll = chain::check(
+        &[LEAF_EXPIRED.to_vec(), WEAK.to_vec()],
+        Config::builder()
+            .trust_store(&[])
+            .not_after(not_after(365 * 200, 0))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string())
+        .contains("Intermediate certificate Chain Test Weak CA validity: ");
+    assert_that!(coll.to_string()).contains(" day(s) (warn/crit below 73000d/0s) (!)");
+    // Only the earliest expiry in the chain gets a metric.
+    assert_that!(coll.to_string()).does_not_contain("certificate_remaining_validity");
+}
+
+#[test]
+fn test_root_from_chain() {
+    let coll = chain::check(
+        &[LEAF_EXPIRED.to_vec(), EXPIRED.to_vec(), ROOT.to_vec()],
+        Config::builder()
+            .trust_store(&[])
+            .include_root(true)
+            .build(),
+    );
+    assert_that!(coll.to_string()).contains("\nRoot certificate Chain Test Root CA validity: ");
+    assert_that!(coll.to_string()).contains("\nRoot certificate Chain Test Root CA public key: ");
+    assert_that!(coll.to_string())
+        .does_not_contain("Root certificate Chain Test Root CA signature");
+}
+
+#[test]
+fn test_root_from_trust_store() {
+    let coll = chain::check(
+        &[LEAF_WEAK.to_vec(), WEAK.to_vec()],
+        Config::builder()
+            .trust_store(&[ROOT.to_vec()])
+            .include_root(true)
+            .build(),
+    );
+    assert_that!(coll.to_string()).contains("\nRoot certificate Chain Test Root CA validity: ");
+}
+
+#[test]
+fn test_leaf_only() {
+    let coll = chain::check(
+        &[LEAF_WEAK.to_vec()],
+        Config::builder().trust_store(&[]).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_that!(coll.to_string()).contains("Earliest expiry in chain: localhost on ");
+}
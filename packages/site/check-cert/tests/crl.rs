@@ -0,0 +1,215 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// The chain root -> intermediate -> leaf and the CRLs were created with `openssl ca`.
+// The CRL distribution points of the certificates point to http://127.0.0.1:8889/.
+
+use assertor::*;
+use check_cert::check;
+use check_cert::checker::crl::{self as crl_check, Config};
+use check_cert::crl::{self, Config as CrlConfig, Crl, Crls};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::thread;
+use tempfile::TempDir;
+
+static ROOT: &[u8] = include_bytes!("../assets/crl/root.der");
+static INTERMEDIATE: &[u8] = include_bytes!("../assets/crl/intermediate.der");
+static LEAF: &[u8] = include_bytes!("../assets/crl/leaf.der");
+
+fn chain() -> Vec<Vec<u8>> {
+    vec![LEAF.to_vec(), INTERMEDIATE.to_vec()]
+}
+
+fn asset(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("crl")
+        .join(name)
+}
+
+fn check_with_files(files: &[&str]) -> check::Collection {
+    let crls = crl::load(
+        &chain(),
+        CrlConfig::builder()
+            .files(files.iter().map(|name| asset(name)).collect())
+            .build(),
+    );
+    crl_check::check(
+        &chain(),
+        &crls,
+        Config::builder().trust_store(&[ROOT.to_vec()]).build(),
+    )
+}
+
+#[test]
+fn test_not_revoked() {
+    let coll = check_with_files(&["root.crl", "intermediate.crl.pem"]);
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_that!(coll.to_string()).contains("\nCRL: localhost not revoked");
+    assert_that!(coll.to_string()).contains("\nCRL: CRL Test Intermediate CA not revoked");
+}
+
+#[test]
+fn test_leaf_revoked() {
+    let coll = check_with_files(&["root.crl", "intermediate-revoked.crl"]);
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_that!(coll.to_string()).starts_with("CRL: localhost revoked on ");
+    assert_that!(coll.to_string()).contains("reason: KeyCompromise");
+}
+
+#[test]
+fn test_intermediate_revoked() {
+    let coll = check_with_files(&["root-revoked.crl", "intermediate.crl.pem"]);
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_that!(coll.to_string()).starts_with("CRL: CRL Test Intermediate CA revoked on ");
+    assert_that!(coll.to_string()).contains("reason: CACompromise");
+}
+
+#[test]
+fn test_missing_crl() {
+    let coll = check_with_files(&["intermediate.crl.pem"]);
+    assert_eq!(check::exit_code(&coll), 3);
+    assert_that!(coll.to_string()).starts_with("CRL: No CRL for CRL Test Intermediate CA (?)");
+}
+
+#[test]
+fn test_missing_crl_without_distribution_point() {
+    let chain = [include_bytes!("../assets/ocsp/leaf.der").to_vec()];
+    let trust_store = [include_bytes!("../assets/ocsp/ca.der").to_vec()];
+    let crls = crl::load(
+        &chain,
+        CrlConfig::builder()
+            .files(vec![asset("intermediate.crl.pem")])
+            .build(),
+    );
+
+    let coll = crl_check::check(
+        &chain,
+        &crls,
+        Config::builder().trust_store(&trust_store).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_that!(coll.to_string()).contains("\nCRL: No CRL for localhost");
+
+    // With --crl, every certificate is expected to have a CRL.
+    let coll = crl_check::check(
+        &chain,
+        &crls,
+        Config::builder()
+            .trust_store(&trust_store)
+            .fetched(true)
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 3);
+    assert_that!(coll.to_string()).starts_with("CRL: No CRL for localhost (?)");
+}
+
+#[test]
+fn test_outdated_crl() {
+    let coll = check_with_files(&["root.crl", "intermediate-outdated.crl"]);
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string())
+        .contains("intermediate-outdated.crl outdated (next update was due on ");
+}
+
+#[test]
+fn test_crl_of_other_issuer_is_ignored() {
+    let crls = Crls {
+        crls: vec![Crl {
+            source: String::from("root.crl"),
+            der: include_bytes!("../assets/crl/root.crl").to_vec(),
+        }],
+        errors: vec![],
+    };
+    // The root CRL must not be taken for the CRL of the intermediate.
+    let coll = crl_check::check(
+        &[LEAF.to_vec()],
+        &crls,
+        Config::builder()
+            .trust_store(&[ROOT.to_vec(), INTERMEDIATE.to_vec()])
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 3);
+    assert_that!(coll.to_string()).starts_with("CRL: No CRL for localhost (?)");
+}
+
+#[test]
+fn test_invalid_file() {
+    let crls = crl::load(
+        &chain(),
+        CrlConfig::builder()
+            .files(vec![asset("leaf.der"), asset("missing.crl")])
+            .build(),
+    );
+    assert!(crls.crls.is_empty());
+    assert_eq!(crls.errors.len(), 2);
+    assert_that!(crls.errors[0]).ends_with("leaf.der: Invalid CRL");
+}
+
+#[test]
+fn test_fetch_from_distribution_points() {
+    let listener = TcpListener::bind("127.0.0.1:8889").unwrap();
+    let server = thread::spawn(move || {
+        let mut requested = vec![];
+        for _ in 0..2 {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&mut stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            let body: &[u8] = match request_line.split_whitespace().nth(1) {
+                Some("/root.crl") => include_bytes!("../assets/crl/root.crl"),
+                _ => include_bytes!("../assets/crl/intermediate-revoked.crl"),
+            };
+            stream
+                .write_all(
+                    format!("HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).as_bytes(),
+                )
+                .unwrap();
+            stream.write_all(body).unwrap();
+            requested.push(request_line);
+        }
+        requested
+    });
+    let cache_dir = TempDir::new().unwrap();
+    let config = || {
+        CrlConfig::builder()
+            .fetch(true)
+            .cache_dir(Some(cache_dir.path().to_path_buf()))
+            .build()
+    };
+
+    let crls = crl::load(&chain(), config());
+    assert_eq!(
+        server.join().unwrap(),
+        vec![
+            "GET /intermediate.crl HTTP/1.0\r\n",
+            "GET /root.crl HTTP/1.0\r\n"
+        ]
+    );
+    assert!(crls.errors.is_empty());
+    assert_eq!(
+        crls.crls
+            .iter()
+            .map(|crl| crl.source.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "http://127.0.0.1:8889/intermediate.crl",
+            "http://127.0.0.1:8889/root.crl"
+        ]
+    );
+    let coll = crl_check::check(
+        &chain(),
+        &crls,
+        Config::builder().trust_store(&[ROOT.to_vec()]).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+
+    // The server is gone, the CRLs come from the cache now.
+    let crls = crl::load(&chain(), config());
+    assert!(crls.errors.is_empty());
+    assert_eq!(crls.crls.len(), 2);
+}
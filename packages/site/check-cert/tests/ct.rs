@@ -0,0 +1,224 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// ca.der: CN=CT Test CA
+// leaf.der: CN=localhost with an SCT of "Test Log" embedded in the precertificate
+// sct-list.bin: SCT list of "Test Log" over leaf.der for the TLS extension
+// log-list.json: log list with "Test Log"
+
+use assertor::*;
+use check_cert::check::{self, Levels, LevelsChecker, LevelsStrategy};
+use check_cert::checker::ct::{self as ct_check, Config};
+use check_cert::ct::{self, Source};
+use check_cert::fetcher::{self, Config as FetcherConfig};
+use openssl::pkey::PKey;
+use openssl::ssl::{ExtensionContext, SslAcceptor, SslMethod};
+use openssl::x509::X509;
+use std::net::TcpListener;
+use std::path::Path;
+use std::thread;
+
+static CA: &[u8] = include_bytes!("../assets/ct/ca.der");
+static LEAF: &[u8] = include_bytes!("../assets/ct/leaf.der");
+static CHAIN: &[u8] = include_bytes!("../assets/ct/chain.pem");
+static SCT_LIST: &[u8] = include_bytes!("../assets/ct/sct-list.bin");
+
+fn logs() -> Vec<ct::Log> {
+    ct::load_logs(Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/ct/log-list.json"
+    )))
+    .unwrap()
+}
+
+fn valid_scts(warn: usize, crit: usize) -> Option<LevelsChecker<usize>> {
+    Some(LevelsChecker::try_new(LevelsStrategy::Lower, Levels::from(&mut [warn, crit])).unwrap())
+}
+
+#[test]
+fn test_load_logs() {
+    let logs = logs();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].description, "Test Log");
+    assert_eq!(logs[0].id.len(), 32);
+}
+
+#[test]
+fn test_load_invalid_log_list() {
+    let err = ct::load_logs(Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/ct/sct-list.bin"
+    )))
+    .unwrap_err();
+    assert_that!(format!("{err:#}")).starts_with("Invalid CT log list");
+}
+
+#[test]
+fn test_embedded_sct() {
+    let scts = ct::collect(LEAF, None, None).unwrap();
+    assert_eq!(scts.len(), 1);
+    assert_eq!(scts[0].source, Source::Embedded);
+
+    let logs = logs();
+    let coll = ct_check::check(
+        &[LEAF.to_vec(), CA.to_vec()],
+        Ok(scts),
+        Config::builder()
+            .trust_store(&[])
+            .logs(&logs)
+            .valid_scts(valid_scts(1, 0))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_that!(coll.to_string()).contains(
+        "Logs with valid SCTs: 1\nSCT from Test Log (embedded): valid, issued Jan  1 00:00:00 2026 +00:00",
+    );
+}
+
+#[test]
+fn test_tls_extension_sct() {
+    let scts = ct::collect(LEAF, Some(SCT_LIST), None).unwrap();
+    assert_eq!(scts.len(), 2);
+    assert_eq!(scts[1].source, Source::TlsExtension);
+
+    let logs = logs();
+    // The issuer is found in the trust store.
+    let coll = ct_check::check(
+        &[LEAF.to_vec()],
+        Ok(scts),
+        Config::builder()
+            .trust_store(&[CA.to_vec()])
+            .logs(&logs)
+            .valid_scts(valid_scts(2, 1))
+            .build(),
+    );
+    // Both SCTs come from the same log.
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).starts_with("Logs with valid SCTs: 1 (warn/crit below 2/1) (!)");
+    assert_that!(coll.to_string()).contains("SCT from Test Log (embedded): valid");
+    assert_that!(coll.to_string()).contains("SCT from Test Log (TLS extension): valid");
+}
+
+#[test]
+fn test_invalid_signature() {
+    let mut scts = ct::collect(LEAF, Some(SCT_LIST), None).unwrap();
+    let signature = &mut scts[1].signature;
+    let last = signature.len() - 1;
+    signature[last] ^= 0xff;
+
+    let logs = logs();
+    let coll = ct_check::check(
+        &[LEAF.to_vec(), CA.to_vec()],
+        Ok(scts),
+        Config::builder()
+            .trust_store(&[])
+            .logs(&logs)
+            .valid_scts(valid_scts(1, 0))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string())
+        .contains("SCT from Test Log (TLS extension): invalid signature (!)");
+}
+
+#[test]
+fn test_missing_issuer() {
+    let logs = logs();
+    let coll = ct_check::check(
+        &[LEAF.to_vec()],
+        ct::collect(LEAF, None, None),
+        Config::builder()
+            .trust_store(&[])
+            .logs(&logs)
+            .valid_scts(valid_scts(1, 0))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string())
+        .contains("SCT from Test Log (embedded): Cannot find issuer certificate (!)");
+}
+
+#[test]
+fn test_unknown_log() {
+    let coll = ct_check::check(
+        &[LEAF.to_vec(), CA.to_vec()],
+        ct::collect(LEAF, None, None),
+        Config::builder()
+            .trust_store(&[])
+            .logs(&[])
+            .valid_scts(valid_scts(2, 1))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_that!(coll.to_string())
+        .starts_with("Logs with valid SCTs: 0 (warn/crit below 2/1) (!!)");
+    assert_that!(coll.to_string()).contains("SCT from unknown log ");
+}
+
+#[test]
+fn test_no_scts() {
+    static NO_SCTS: &[u8] = include_bytes!("../assets/ct/ca.der");
+
+    let coll = ct_check::check(
+        &[NO_SCTS.to_vec()],
+        ct::collect(NO_SCTS, None, None),
+        Config::builder().trust_store(&[]).logs(&[]).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_eq!(
+        coll.to_string(),
+        "OK\nLogs with valid SCTs: 0\n\
+         No SCTs in the certificate, the TLS extension or the OCSP response"
+    );
+}
+
+#[test]
+fn test_truncated_sct_list() {
+    let coll = ct_check::check(
+        &[LEAF.to_vec()],
+        ct::collect(LEAF, Some(&SCT_LIST[..20]), None),
+        Config::builder().trust_store(&[]).logs(&[]).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 3);
+    assert_that!(coll.to_string()).starts_with("SCTs: Truncated SCT");
+}
+
+#[test]
+fn test_fetch_sct_list() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor
+            .set_certificate(&X509::stack_from_pem(CHAIN).unwrap()[0])
+            .unwrap();
+        acceptor
+            .set_private_key(&PKey::private_key_from_pem(CHAIN).unwrap())
+            .unwrap();
+        acceptor
+            .add_custom_ext(
+                18,
+                ExtensionContext::CLIENT_HELLO
+                    | ExtensionContext::TLS1_2_SERVER_HELLO
+                    | ExtensionContext::TLS1_3_CERTIFICATE,
+                |_, context, _| match context {
+                    ExtensionContext::CLIENT_HELLO => Ok(None),
+                    _ => Ok(Some(SCT_LIST)),
+                },
+                |_, _, _, _| Ok(()),
+            )
+            .unwrap();
+        let acceptor = acceptor.build();
+        let (stream, _) = listener.accept().unwrap();
+        let _ = acceptor.accept(stream);
+    });
+    let server_cert = fetcher::fetch_server_cert(
+        "127.0.0.1",
+        port,
+        FetcherConfig::builder().timeout(None).build(),
+    )
+    .unwrap();
+    assert_eq!(server_cert.chain[0], LEAF);
+    assert_eq!(server_cert.sct_list.as_deref(), Some(SCT_LIST));
+}
@@ -0,0 +1,100 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use check_cert::fetcher::{self, Config};
+use openssl::pkey::PKey;
+use openssl::ssl::{NameType, SslAcceptor, SslMethod};
+use openssl::x509::X509;
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
+use std::thread::{self, JoinHandle};
+
+static CHAIN: &[u8] = include_bytes!("../assets/keystore/chain.pem");
+static LEAF: &[u8] = include_bytes!("../assets/crl/leaf.der");
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// Serve one TLS connection and return the requested server name.
+fn serve() -> (u16, JoinHandle<Option<String>>) {
+    let listener = TcpListener::bind((LOCALHOST, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        let mut certs = X509::stack_from_pem(CHAIN).unwrap().into_iter();
+        acceptor.set_certificate(&certs.next().unwrap()).unwrap();
+        for cert in certs {
+            acceptor.add_extra_chain_cert(cert).unwrap();
+        }
+        acceptor
+            .set_private_key(&PKey::private_key_from_pem(CHAIN).unwrap())
+            .unwrap();
+        let acceptor = acceptor.build();
+        let (stream, _) = listener.accept().unwrap();
+        let stream = acceptor.accept(stream).unwrap();
+        stream
+            .ssl()
+            .servername(NameType::HOST_NAME)
+            .map(String::from)
+    });
+    (port, handle)
+}
+
+#[test]
+fn test_no_sni_for_ip_address() {
+    let (port, handle) = serve();
+    let server_cert =
+        fetcher::fetch_server_cert("127.0.0.1", port, Config::builder().timeout(None).build())
+            .unwrap();
+    assert_eq!(server_cert.chain[0], LEAF);
+    assert_eq!(handle.join().unwrap(), None);
+}
+
+#[test]
+fn test_address() {
+    let (port, handle) = serve();
+    // The name does not resolve, the address is used for the connection.
+    fetcher::fetch_server_cert(
+        "www.example.invalid",
+        port,
+        Config::builder()
+            .timeout(None)
+            .address(Some(LOCALHOST))
+            .build(),
+    )
+    .unwrap();
+    assert_eq!(
+        handle.join().unwrap().as_deref(),
+        Some("www.example.invalid")
+    );
+}
+
+#[test]
+fn test_sni() {
+    let (port, handle) = serve();
+    fetcher::fetch_server_cert(
+        "127.0.0.1",
+        port,
+        Config::builder()
+            .timeout(None)
+            .sni(Some(String::from("www.example.com")))
+            .build(),
+    )
+    .unwrap();
+    assert_eq!(handle.join().unwrap().as_deref(), Some("www.example.com"));
+}
+
+#[test]
+fn test_disable_sni() {
+    let (port, handle) = serve();
+    fetcher::fetch_server_cert(
+        "localhost",
+        port,
+        Config::builder()
+            .timeout(None)
+            .address(Some(LOCALHOST))
+            .disable_sni(true)
+            .build(),
+    )
+    .unwrap();
+    assert_eq!(handle.join().unwrap(), None);
+}
@@ -0,0 +1,125 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// wildcard.der: CN=www.example.com
+//   SAN DNS:*.example.com, DNS:example.com, IP:192.0.2.1, IP:2001:db8::1
+// cn-only.der: CN=legacy.example.com without SAN
+
+use check_cert::check;
+use check_cert::checker::hostname::{self, Config, OnMismatch};
+
+static WILDCARD: &[u8] = include_bytes!("../assets/hostname/wildcard.der");
+static CN_ONLY: &[u8] = include_bytes!("../assets/hostname/cn-only.der");
+
+#[test]
+fn test_wildcard() {
+    let coll = hostname::check(
+        WILDCARD,
+        Config::builder().hostname("www.example.com").build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_eq!(
+        coll.to_string(),
+        "OK\nHostname www.example.com matches certificate (DNS:*.example.com)"
+    );
+}
+
+#[test]
+fn test_exact_name_with_trailing_dot() {
+    let coll = hostname::check(WILDCARD, Config::builder().hostname("example.com.").build());
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_eq!(
+        coll.to_string(),
+        "OK\nHostname example.com matches certificate (DNS:example.com)"
+    );
+}
+
+#[test]
+fn test_mismatch() {
+    let coll = hostname::check(
+        WILDCARD,
+        Config::builder().hostname("a.b.example.com").build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_eq!(
+        coll.to_string(),
+        "Hostname a.b.example.com does not match certificate (!!)\n\
+         Hostname a.b.example.com does not match certificate \
+         (DNS:*.example.com, DNS:example.com, IP:192.0.2.1, IP:2001:db8::1) (!!)"
+    );
+}
+
+#[test]
+fn test_mismatch_severity() {
+    let coll = hostname::check(
+        WILDCARD,
+        Config::builder()
+            .hostname("www.example.org")
+            .on_mismatch(OnMismatch::Warn)
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+
+    let coll = hostname::check(
+        WILDCARD,
+        Config::builder()
+            .hostname("www.example.org")
+            .on_mismatch(OnMismatch::Ok)
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+}
+
+#[test]
+fn test_ip_addresses() {
+    for ip in ["192.0.2.1", "2001:db8::1", "2001:DB8:0:0:0:0:0:1"] {
+        let coll = hostname::check(WILDCARD, Config::builder().hostname(ip).build());
+        assert_eq!(check::exit_code(&coll), 0, "{ip}");
+    }
+    let coll = hostname::check(WILDCARD, Config::builder().hostname("192.0.2.2").build());
+    assert_eq!(check::exit_code(&coll), 2);
+}
+
+#[test]
+fn test_cn_fallback() {
+    let coll = hostname::check(
+        CN_ONLY,
+        Config::builder().hostname("legacy.example.com").build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_eq!(
+        coll.to_string(),
+        "Hostname legacy.example.com does not match certificate (!!)\n\
+         Hostname legacy.example.com does not match certificate (no subject alternative names) (!!)"
+    );
+
+    let coll = hostname::check(
+        CN_ONLY,
+        Config::builder()
+            .hostname("legacy.example.com")
+            .cn_fallback(true)
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_eq!(
+        coll.to_string(),
+        "OK\nHostname legacy.example.com matches certificate (CN:legacy.example.com)"
+    );
+}
+
+#[test]
+fn test_no_cn_fallback_with_dns_names() {
+    let coll = hostname::check(
+        WILDCARD,
+        Config::builder()
+            .hostname("www.example.com")
+            .cn_fallback(true)
+            .build(),
+    );
+    // The DNS name matches, not the CN.
+    assert_eq!(
+        coll.to_string(),
+        "OK\nHostname www.example.com matches certificate (DNS:*.example.com)"
+    );
+}
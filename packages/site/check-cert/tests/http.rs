@@ -0,0 +1,95 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use assertor::*;
+use check_cert::http;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const TIMEOUT: Option<Duration> = Some(Duration::from_secs(5));
+
+// A stand-in server answering one request per response, returning the request heads.
+fn serve(responses: Vec<Vec<u8>>) -> (u16, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let mut requests = vec![];
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = String::new();
+            let mut reader = BufReader::new(&mut stream);
+            while !head.ends_with("\r\n\r\n") {
+                if reader.read_line(&mut head).unwrap() == 0 {
+                    break;
+                }
+            }
+            // The client hangs up once it has seen enough.
+            let _ = stream.write_all(&response);
+            requests.push(head);
+        }
+        requests
+    });
+    (port, handle)
+}
+
+#[test]
+fn test_host_with_port() {
+    let (port, server) = serve(vec![b"HTTP/1.0 200 OK\r\n\r\ncontent".to_vec()]);
+    let content = http::get(&format!("http://127.0.0.1:{port}/file.crl"), TIMEOUT).unwrap();
+    assert_eq!(content, b"content");
+    assert_that!(server.join().unwrap()[0])
+        .contains(format!("\r\nHost: 127.0.0.1:{port}\r\n").as_str());
+}
+
+#[test]
+fn test_follow_redirects() {
+    let (port, server) = serve(vec![
+        b"HTTP/1.1 302 Found\r\nLocation: /moved.crl\r\n\r\n".to_vec(),
+        b"HTTP/1.1 200 OK\r\n\r\ncontent".to_vec(),
+    ]);
+    let content = http::get(&format!("http://127.0.0.1:{port}/file.crl"), TIMEOUT).unwrap();
+    assert_eq!(content, b"content");
+    let requests = server.join().unwrap();
+    assert_that!(requests[0]).starts_with("GET /file.crl HTTP/1.0\r\n");
+    assert_that!(requests[1]).starts_with("GET /moved.crl HTTP/1.0\r\n");
+}
+
+#[test]
+fn test_redirect_keeps_post() {
+    let (port, server) = serve(vec![
+        b"HTTP/1.1 307 Temporary Redirect\r\nLocation: /moved\r\n\r\n".to_vec(),
+        b"HTTP/1.1 200 OK\r\n\r\ncontent".to_vec(),
+    ]);
+    let url = format!("http://127.0.0.1:{port}/ocsp");
+    let content = http::post(&url, "application/ocsp-request", b"", TIMEOUT).unwrap();
+    assert_eq!(content, b"content");
+    assert_that!(server.join().unwrap()[1]).starts_with("POST /moved HTTP/1.0\r\n");
+}
+
+#[test]
+fn test_too_many_redirects() {
+    let redirect = b"HTTP/1.1 301 Moved Permanently\r\nLocation: /loop\r\n\r\n".to_vec();
+    let (port, server) = serve(vec![redirect; 6]);
+    let err = http::get(&format!("http://127.0.0.1:{port}/loop"), TIMEOUT).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("Too many redirects, giving up at http://127.0.0.1:{port}/loop")
+    );
+    assert_eq!(server.join().unwrap().len(), 6);
+}
+
+#[test]
+fn test_response_too_large() {
+    let mut response = b"HTTP/1.0 200 OK\r\n\r\n".to_vec();
+    response.resize(17 * 1024 * 1024, b'x');
+    let (port, server) = serve(vec![response]);
+    let err = http::get(&format!("http://127.0.0.1:{port}/"), TIMEOUT).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("Response from http://127.0.0.1:{port}/ exceeds 16777216 bytes")
+    );
+    server.join().unwrap();
+}
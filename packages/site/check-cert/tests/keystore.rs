@@ -0,0 +1,117 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// The files hold the chain from `assets/crl`. The password is "secret", the Java
+// keystore has the trusted entry "root" before the private key entry "server".
+
+use check_cert::keystore::{self, Config};
+use std::path::PathBuf;
+
+static ROOT: &[u8] = include_bytes!("../assets/crl/root.der");
+static INTERMEDIATE: &[u8] = include_bytes!("../assets/crl/intermediate.der");
+static LEAF: &[u8] = include_bytes!("../assets/crl/leaf.der");
+
+fn asset(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("keystore")
+        .join(name)
+}
+
+fn password() -> Option<String> {
+    Some(String::from("secret"))
+}
+
+#[test]
+fn test_der() {
+    let chain = keystore::load(&asset("leaf.der"), Config::builder().build()).unwrap();
+    assert_eq!(chain, vec![LEAF.to_vec()]);
+}
+
+#[test]
+fn test_pem_with_key() {
+    let chain = keystore::load(&asset("chain.pem"), Config::builder().build()).unwrap();
+    assert_eq!(chain, vec![LEAF.to_vec(), INTERMEDIATE.to_vec()]);
+}
+
+#[test]
+fn test_pkcs12() {
+    let chain = keystore::load(
+        &asset("bundle.p12"),
+        Config::builder().password(password()).build(),
+    )
+    .unwrap();
+    assert_eq!(chain, vec![LEAF.to_vec(), INTERMEDIATE.to_vec()]);
+}
+
+#[test]
+fn test_pkcs12_wrong_password() {
+    let err = keystore::load(&asset("bundle.p12"), Config::builder().build()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Cannot decrypt PKCS#12 file, wrong password?"
+    );
+}
+
+#[test]
+fn test_jks_first_key_entry() {
+    let chain = keystore::load(
+        &asset("keystore.jks"),
+        Config::builder().password(password()).build(),
+    )
+    .unwrap();
+    assert_eq!(chain, vec![LEAF.to_vec(), INTERMEDIATE.to_vec()]);
+}
+
+#[test]
+fn test_jks_without_password() {
+    let chain = keystore::load(&asset("keystore.jks"), Config::builder().build()).unwrap();
+    assert_eq!(chain, vec![LEAF.to_vec(), INTERMEDIATE.to_vec()]);
+}
+
+#[test]
+fn test_jks_alias() {
+    let chain = keystore::load(
+        &asset("keystore.jks"),
+        Config::builder().alias(Some(String::from("ROOT"))).build(),
+    )
+    .unwrap();
+    assert_eq!(chain, vec![ROOT.to_vec()]);
+}
+
+#[test]
+fn test_jks_unknown_alias() {
+    let err = keystore::load(
+        &asset("keystore.jks"),
+        Config::builder()
+            .alias(Some(String::from("client")))
+            .build(),
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "No entry client in Java keystore");
+}
+
+#[test]
+fn test_jks_wrong_password() {
+    let err = keystore::load(
+        &asset("keystore.jks"),
+        Config::builder()
+            .password(Some(String::from("wrong")))
+            .build(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Java keystore was tampered with, or password was incorrect"
+    );
+}
+
+#[test]
+fn test_unknown_format() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("garbage");
+    std::fs::write(&path, b"garbage").unwrap();
+    let err = keystore::load(&path, Config::builder().build()).unwrap_err();
+    assert_eq!(err.to_string(), "Unknown file format");
+}
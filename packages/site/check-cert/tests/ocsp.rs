@@ -0,0 +1,223 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// The responses were created with `openssl ocsp -index ...` for a test CA valid until 2126.
+
+use assertor::*;
+use check_cert::check::{self, Levels, LevelsChecker, LevelsStrategy};
+use check_cert::checker::ocsp::{self as ocsp_check, Config};
+use check_cert::ocsp::{self, Config as OcspConfig, Response, Source};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use time::Duration;
+
+static CA: &[u8] = include_bytes!("../assets/ocsp/ca.der");
+static LEAF: &[u8] = include_bytes!("../assets/ocsp/leaf.der");
+
+fn check_stapled(der: &[u8], trust_store: &[Vec<u8>]) -> check::Collection {
+    ocsp_check::check(
+        &[LEAF.to_vec()],
+        Ok(Response {
+            source: Source::Stapled,
+            der: der.to_vec(),
+        }),
+        Config::builder().trust_store(trust_store).build(),
+    )
+}
+
+#[test]
+fn test_good() {
+    let coll = check_stapled(include_bytes!("../assets/ocsp/good.der"), &[CA.to_vec()]);
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_that!(coll.to_string()).contains("\nOCSP: good (stapled response)\n");
+}
+
+#[test]
+fn test_revoked() {
+    let coll = check_stapled(include_bytes!("../assets/ocsp/revoked.der"), &[CA.to_vec()]);
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_that!(coll.to_string()).starts_with("OCSP: revoked on Jan  1 00:00:00 2024 +00:00 (!!)");
+    assert_that!(coll.to_string()).contains("reason: KeyCompromise (stapled response)");
+}
+
+#[test]
+fn test_unknown() {
+    let coll = check_stapled(include_bytes!("../assets/ocsp/unknown.der"), &[CA.to_vec()]);
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).starts_with("OCSP: unknown (!)");
+}
+
+#[test]
+fn test_without_next_update() {
+    let coll = check_stapled(
+        include_bytes!("../assets/ocsp/no-next-update.der"),
+        &[CA.to_vec()],
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_that!(coll.to_string()).contains("OCSP: good");
+}
+
+#[test]
+fn test_outdated() {
+    // valid for one minute only
+    let coll = check_stapled(
+        include_bytes!("../assets/ocsp/outdated.der"),
+        &[CA.to_vec()],
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).starts_with("OCSP response outdated (next update was due on ");
+}
+
+#[test]
+fn test_max_age() {
+    let coll = ocsp_check::check(
+        &[LEAF.to_vec()],
+        Ok(Response {
+            source: Source::Stapled,
+            der: include_bytes!("../assets/ocsp/good.der").to_vec(),
+        }),
+        Config::builder()
+            .trust_store(&[CA.to_vec()])
+            .max_age(Some(
+                LevelsChecker::try_new(
+                    LevelsStrategy::Upper,
+                    Levels::from(&mut [Duration::seconds(1), Duration::days(36500)]),
+                )
+                .unwrap(),
+            ))
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).starts_with("OCSP response age: ");
+    assert_that!(coll.to_string()).contains("ocsp_response_age=");
+}
+
+#[test]
+fn test_untrusted_responder() {
+    let coll = check_stapled(include_bytes!("../assets/ocsp/good.der"), &[]);
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_eq!(
+        coll.to_string(),
+        "OCSP: Response signature verification failed (!!)\nOCSP: Response signature verification failed (!!)"
+    );
+}
+
+#[test]
+fn test_response_for_other_certificate() {
+    let coll = ocsp_check::check(
+        &[include_bytes!("../assets/cert.der").to_vec()],
+        Ok(Response {
+            source: Source::Stapled,
+            der: include_bytes!("../assets/ocsp/good.der").to_vec(),
+        }),
+        Config::builder().trust_store(&[CA.to_vec()]).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_eq!(
+        coll.to_string(),
+        "OCSP: Response does not cover the certificate (!!)\nOCSP: Response does not cover the certificate (!!)"
+    );
+}
+
+#[test]
+fn test_unsuccessful_response() {
+    // tryLater
+    let coll = check_stapled(b"\x30\x03\x0a\x01\x03", &[CA.to_vec()]);
+    assert_eq!(check::exit_code(&coll), 3);
+    assert_eq!(
+        coll.to_string(),
+        "OCSP: Responder returned tryLater (?)\nOCSP: Responder returned tryLater (?)"
+    );
+}
+
+#[test]
+fn test_no_response() {
+    let coll = ocsp_check::check(
+        &[LEAF.to_vec()],
+        Err(anyhow::anyhow!("No OCSP responder in certificate")),
+        Config::builder().trust_store(&[CA.to_vec()]).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 3);
+    assert_eq!(
+        coll.to_string(),
+        "OCSP: No OCSP responder in certificate (?)\nOCSP: No OCSP responder in certificate (?)"
+    );
+}
+
+#[test]
+fn test_obtain_stapled() {
+    let response = ocsp::obtain(
+        &[LEAF.to_vec()],
+        &[],
+        Some(b"stapled".to_vec()),
+        OcspConfig::builder().build(),
+    )
+    .unwrap();
+    assert_eq!(response.source, Source::Stapled);
+    assert_eq!(response.der, b"stapled");
+}
+
+#[test]
+fn test_obtain_from_responder() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/ocsp", listener.local_addr().unwrap());
+    let responder = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = vec![];
+        let mut reader = BufReader::new(&mut stream);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(len) = line.strip_prefix("Content-Length: ") {
+                content_length = len.trim().parse().unwrap();
+            }
+            request.push(line.clone());
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut request_body = vec![0u8; content_length];
+        reader.read_exact(&mut request_body).unwrap();
+        let body = include_bytes!("../assets/ocsp/good.der");
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.0 200 OK\r\nContent-Type: application/ocsp-response\r\n\
+                     Content-Length: {}\r\n\r\n",
+                    body.len()
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+        stream.write_all(body).unwrap();
+        request.concat()
+    });
+
+    let response = ocsp::obtain(
+        &[LEAF.to_vec()],
+        &[CA.to_vec()],
+        None,
+        OcspConfig::builder().responder(Some(url.clone())).build(),
+    )
+    .unwrap();
+    let request = responder.join().unwrap();
+    assert_that!(request).starts_with("POST /ocsp HTTP/1.0\r\n");
+    assert_that!(request).contains("Content-Type: application/ocsp-request\r\n");
+    assert_eq!(response.source, Source::Responder(url));
+    assert_eq!(response.der, include_bytes!("../assets/ocsp/good.der"));
+}
+
+#[test]
+fn test_obtain_without_responder() {
+    let err = ocsp::obtain(&[CA.to_vec()], &[], None, OcspConfig::builder().build()).unwrap_err();
+    assert_eq!(err.to_string(), "No OCSP responder in certificate");
+}
+
+#[test]
+fn test_obtain_without_issuer() {
+    let err = ocsp::obtain(&[LEAF.to_vec()], &[], None, OcspConfig::builder().build()).unwrap_err();
+    assert_eq!(err.to_string(), "Cannot find issuer certificate");
+}
@@ -0,0 +1,211 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use assertor::*;
+use check_cert::check;
+use check_cert::checker::scan::{self as scan_check, Config as ScanChecks, Severity};
+use check_cert::fetcher::Config as FetcherConfig;
+use check_cert::scan::{self, Config, Protocol, Version};
+use openssl::pkey::PKey;
+use openssl::ssl::{SslAcceptor, SslMethod, SslOptions, SslVersion};
+use openssl::x509::X509;
+use std::net::TcpListener;
+use std::thread;
+
+static CHAIN: &[u8] = include_bytes!("../assets/keystore/chain.pem");
+
+/// Serve TLS connections with the given versions and ciphers until the test ends.
+fn serve(min: SslVersion, max: SslVersion, ciphers: &'static str) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.clear_options(SslOptions::NO_TLSV1 | SslOptions::NO_TLSV1_1);
+    acceptor.set_security_level(0);
+    acceptor.set_min_proto_version(Some(min)).unwrap();
+    acceptor.set_max_proto_version(Some(max)).unwrap();
+    acceptor.set_cipher_list(ciphers).unwrap();
+    acceptor
+        .set_certificate(&X509::stack_from_pem(CHAIN).unwrap()[0])
+        .unwrap();
+    acceptor
+        .set_private_key(&PKey::private_key_from_pem(CHAIN).unwrap())
+        .unwrap();
+    let acceptor = acceptor.build();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = acceptor.accept(stream);
+        }
+    });
+    port
+}
+
+fn connection() -> FetcherConfig {
+    FetcherConfig::builder().timeout(None).build()
+}
+
+fn protocol(version: Version, ciphers: &[&str]) -> Protocol {
+    Protocol {
+        version,
+        accepted: !ciphers.is_empty(),
+        ciphers: ciphers.iter().map(|cipher| cipher.to_string()).collect(),
+        error: None,
+    }
+}
+
+#[test]
+fn test_scan_tls12_only() {
+    let port = serve(
+        SslVersion::TLS1_2,
+        SslVersion::TLS1_2,
+        "ECDHE-RSA-AES128-GCM-SHA256:AES128-SHA",
+    );
+    let protocols = scan::scan(
+        "127.0.0.1",
+        port,
+        &connection(),
+        Config::builder().ciphers(true).build(),
+    )
+    .unwrap();
+    let accepted = protocols
+        .iter()
+        .filter(|protocol| protocol.accepted)
+        .map(|protocol| protocol.version)
+        .collect::<Vec<_>>();
+    assert_eq!(accepted, vec![Version::Tls1_2]);
+    assert!(protocols.iter().all(|protocol| protocol.error.is_none()));
+    let mut ciphers = protocols[2].ciphers.clone();
+    ciphers.sort();
+    assert_eq!(ciphers, vec!["AES128-SHA", "ECDHE-RSA-AES128-GCM-SHA256"]);
+}
+
+#[test]
+fn test_scan_preferred_cipher_only() {
+    let port = serve(
+        SslVersion::TLS1_2,
+        SslVersion::TLS1_2,
+        "ECDHE-RSA-AES128-GCM-SHA256:AES128-SHA",
+    );
+    let protocols =
+        scan::scan("127.0.0.1", port, &connection(), Config::builder().build()).unwrap();
+    assert_eq!(protocols[2].ciphers.len(), 1);
+}
+
+#[test]
+fn test_scan_deprecated_version() {
+    let port = serve(
+        SslVersion::TLS1,
+        SslVersion::TLS1_2,
+        "ECDHE-RSA-AES128-SHA:@SECLEVEL=0",
+    );
+    let coll = scan_check::check(
+        scan::scan("127.0.0.1", port, &connection(), Config::builder().build()),
+        ScanChecks::builder().build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_that!(coll.to_string())
+        .starts_with("TLS versions: TLSv1.0, TLSv1.1, TLSv1.2, TLSv1.0 accepted (deprecated) (!!)");
+    assert_that!(coll.to_string()).contains("TLSv1.3: not accepted");
+}
+
+#[test]
+fn test_scan_connection_refused() {
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let coll = scan_check::check(
+        scan::scan("127.0.0.1", port, &connection(), Config::builder().build()),
+        ScanChecks::builder().build(),
+    );
+    assert_eq!(check::exit_code(&coll), 3);
+    assert_that!(coll.to_string()).starts_with("TLS scan: ");
+}
+
+#[test]
+fn test_scan_no_tls() {
+    // Without an alert from the server we can't tell whether it refuses a version.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            drop(stream);
+        }
+    });
+    let coll = scan_check::check(
+        scan::scan("127.0.0.1", port, &connection(), Config::builder().build()),
+        ScanChecks::builder().build(),
+    );
+    assert_eq!(check::exit_code(&coll), 3);
+    assert_that!(coll.to_string()).starts_with("TLS versions: none confirmed");
+    assert_that!(coll.to_string()).contains("TLSv1.2: ");
+    assert_that!(coll.to_string()).does_not_contain("not accepted");
+}
+
+#[test]
+fn test_check_modern_versions() {
+    let coll = scan_check::check(
+        Ok(vec![
+            protocol(Version::Tls1_0, &[]),
+            protocol(Version::Tls1_1, &[]),
+            protocol(Version::Tls1_2, &["ECDHE-RSA-AES128-GCM-SHA256"]),
+            protocol(Version::Tls1_3, &["TLS_AES_256_GCM_SHA384"]),
+        ]),
+        ScanChecks::builder().build(),
+    );
+    assert_eq!(check::exit_code(&coll), 0);
+    assert_eq!(
+        coll.to_string(),
+        "TLS versions: TLSv1.2, TLSv1.3\n\
+         TLS versions: TLSv1.2, TLSv1.3\n\
+         TLSv1.0: not accepted\n\
+         TLSv1.1: not accepted\n\
+         TLSv1.2: ECDHE-RSA-AES128-GCM-SHA256\n\
+         TLSv1.3: TLS_AES_256_GCM_SHA384"
+    );
+}
+
+#[test]
+fn test_check_weak_ciphers() {
+    let versions = || {
+        Ok(vec![protocol(
+            Version::Tls1_2,
+            &["AES128-GCM-SHA256", "RC4-SHA", "DES-CBC3-SHA"],
+        )])
+    };
+    let coll = scan_check::check(versions(), ScanChecks::builder().build());
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string())
+        .contains("TLSv1.2 weak cipher suites: RC4-SHA, DES-CBC3-SHA (!)");
+
+    let coll = scan_check::check(
+        versions(),
+        ScanChecks::builder().weak_cipher(Severity::Crit).build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+}
+
+#[test]
+fn test_check_deprecated_severity() {
+    let coll = scan_check::check(
+        Ok(vec![protocol(Version::Tls1_1, &["ECDHE-RSA-AES128-SHA"])]),
+        ScanChecks::builder()
+            .deprecated_version(Severity::Warn)
+            .build(),
+    );
+    assert_eq!(check::exit_code(&coll), 1);
+    assert_that!(coll.to_string()).contains("TLSv1.1: ECDHE-RSA-AES128-SHA (!)");
+}
+
+#[test]
+fn test_check_nothing_accepted() {
+    let coll = scan_check::check(
+        Ok(Version::ALL
+            .into_iter()
+            .map(|version| protocol(version, &[]))
+            .collect()),
+        ScanChecks::builder().build(),
+    );
+    assert_eq!(check::exit_code(&coll), 2);
+    assert_that!(coll.to_string()).starts_with("TLS versions: none accepted (!!)");
+}
@@ -0,0 +1,271 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use check_cert::starttls::{self, Protocol};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+enum Step {
+    Send(&'static [u8]),
+    Expect(&'static [u8]),
+}
+use Step::{Expect, Send};
+
+// A stand-in server playing its part of the dialogue, returning what it received.
+fn serve(script: Vec<Step>) -> (u16, JoinHandle<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut received = vec![];
+        for step in script {
+            match step {
+                Send(data) => stream.write_all(data).unwrap(),
+                Expect(data) => {
+                    let mut buf = vec![0u8; data.len()];
+                    if stream.read_exact(&mut buf).is_err() {
+                        break;
+                    }
+                    received.extend(buf);
+                }
+            }
+        }
+        received
+    });
+    (port, handle)
+}
+
+fn negotiate(protocol: Protocol, script: Vec<Step>) -> (anyhow::Result<()>, Vec<u8>) {
+    let (port, server) = serve(script);
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let result = starttls::negotiate(&mut stream, protocol, "example.com");
+    drop(stream);
+    (result, server.join().unwrap())
+}
+
+#[test]
+fn test_smtp() {
+    let (result, received) = negotiate(
+        Protocol::Smtp,
+        vec![
+            Send(b"220-mail.example.com ESMTP\r\n220 ready\r\n"),
+            Expect(b"EHLO localhost\r\n"),
+            Send(b"250-mail.example.com\r\n250-PIPELINING\r\n250-STARTTLS\r\n250 8BITMIME\r\n"),
+            Expect(b"STARTTLS\r\n"),
+            Send(b"220 2.0.0 Ready to start TLS\r\n"),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(received, b"EHLO localhost\r\nSTARTTLS\r\n");
+}
+
+#[test]
+fn test_smtp_starttls_not_offered() {
+    let (result, _) = negotiate(
+        Protocol::Smtp,
+        vec![
+            Send(b"220 mail.example.com ESMTP\r\n"),
+            Expect(b"EHLO localhost\r\n"),
+            Send(b"250-mail.example.com\r\n250 8BITMIME\r\n"),
+        ],
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "SMTP STARTTLS failed: not offered by server"
+    );
+}
+
+#[test]
+fn test_imap() {
+    let (result, received) = negotiate(
+        Protocol::Imap,
+        vec![
+            Send(b"* OK [CAPABILITY IMAP4rev1 STARTTLS] ready\r\n"),
+            Expect(b"a001 STARTTLS\r\n"),
+            Send(b"* BYE not really\r\na001 OK Begin TLS negotiation now\r\n"),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(received, b"a001 STARTTLS\r\n");
+}
+
+#[test]
+fn test_imap_refused() {
+    let (result, _) = negotiate(
+        Protocol::Imap,
+        vec![
+            Send(b"* OK ready\r\n"),
+            Expect(b"a001 STARTTLS\r\n"),
+            Send(b"a001 BAD unknown command\r\n"),
+        ],
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "IMAP STARTTLS failed: unexpected response: a001 BAD unknown command"
+    );
+}
+
+#[test]
+fn test_pop3() {
+    let (result, received) = negotiate(
+        Protocol::Pop3,
+        vec![
+            Send(b"+OK POP3 ready\r\n"),
+            Expect(b"STLS\r\n"),
+            Send(b"+OK Begin TLS negotiation\r\n"),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(received, b"STLS\r\n");
+}
+
+#[test]
+fn test_ftp() {
+    let (result, received) = negotiate(
+        Protocol::Ftp,
+        vec![
+            Send(b"220 FTP server ready\r\n"),
+            Expect(b"AUTH TLS\r\n"),
+            Send(b"234 AUTH TLS successful\r\n"),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(received, b"AUTH TLS\r\n");
+}
+
+#[test]
+fn test_ftp_refused() {
+    let (result, _) = negotiate(
+        Protocol::Ftp,
+        vec![
+            Send(b"220 FTP server ready\r\n"),
+            Expect(b"AUTH TLS\r\n"),
+            Send(b"500 AUTH not understood\r\n"),
+        ],
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "FTP STARTTLS failed: unexpected response: 500 AUTH not understood"
+    );
+}
+
+const LDAP_REQUEST: &[u8] = b"\x30\x1d\x02\x01\x01\x77\x18\x80\x161.3.6.1.4.1.1466.20037";
+
+#[test]
+fn test_ldap() {
+    let (result, received) = negotiate(
+        Protocol::Ldap,
+        vec![
+            Expect(LDAP_REQUEST),
+            Send(b"\x30\x0c\x02\x01\x01\x78\x07\x0a\x01\x00\x04\x00\x04\x00"),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(received, LDAP_REQUEST);
+}
+
+#[test]
+fn test_ldap_long_form_lengths() {
+    // As sent by Active Directory
+    let (result, _) = negotiate(
+        Protocol::Ldap,
+        vec![
+            Expect(LDAP_REQUEST),
+            Send(b"\x30\x84\x00\x00\x00\x10\x02\x01\x01\x78\x84\x00\x00\x00\x07\x0a\x01\x00\x04\x00\x04\x00"),
+        ],
+    );
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_ldap_refused() {
+    let (result, _) = negotiate(
+        Protocol::Ldap,
+        vec![
+            Expect(LDAP_REQUEST),
+            // protocolError
+            Send(b"\x30\x0c\x02\x01\x01\x78\x07\x0a\x01\x02\x04\x00\x04\x00"),
+        ],
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "LDAP STARTTLS failed: server returned result code 2"
+    );
+}
+
+const XMPP_STREAM: &[u8] = b"<?xml version='1.0'?><stream:stream xmlns='jabber:client' \
+    xmlns:stream='http://etherx.jabber.org/streams' to='example.com' version='1.0'>";
+const XMPP_STARTTLS: &[u8] = b"<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>";
+
+#[test]
+fn test_xmpp() {
+    let (result, received) = negotiate(
+        Protocol::Xmpp,
+        vec![
+            Expect(XMPP_STREAM),
+            Send(
+                b"<?xml version='1.0'?><stream:stream from='example.com' id='1' version='1.0' \
+                  xmlns='jabber:client' xmlns:stream='http://etherx.jabber.org/streams'>\
+                  <stream:features><starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'>\
+                  <required/></starttls></stream:features>",
+            ),
+            Expect(XMPP_STARTTLS),
+            Send(b"<proceed xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"),
+        ],
+    );
+    assert!(result.is_ok());
+    assert_eq!(received, [XMPP_STREAM, XMPP_STARTTLS].concat());
+}
+
+#[test]
+fn test_xmpp_failure() {
+    let (result, _) = negotiate(
+        Protocol::Xmpp,
+        vec![
+            Expect(XMPP_STREAM),
+            Send(
+                b"<stream:stream><stream:features>\
+                  <starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/></stream:features>",
+            ),
+            Expect(XMPP_STARTTLS),
+            Send(b"<failure xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"),
+        ],
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "XMPP STARTTLS failed: unexpected response: <failure xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"
+    );
+}
+
+const POSTGRES_SSL_REQUEST: &[u8] = b"\x00\x00\x00\x08\x04\xd2\x16\x2f";
+
+#[test]
+fn test_postgres() {
+    let (result, received) = negotiate(
+        Protocol::Postgres,
+        vec![Expect(POSTGRES_SSL_REQUEST), Send(b"S")],
+    );
+    assert!(result.is_ok());
+    assert_eq!(received, POSTGRES_SSL_REQUEST);
+}
+
+#[test]
+fn test_postgres_ssl_not_supported() {
+    let (result, _) = negotiate(
+        Protocol::Postgres,
+        vec![Expect(POSTGRES_SSL_REQUEST), Send(b"N")],
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "PostgreSQL STARTTLS failed: not supported by server"
+    );
+}
@@ -0,0 +1,226 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// Batch mode runs many checks from a single process, invoked via
+// "check_httpv2 --batch FILE [--parallelism N]".
+// Every line of the input defines a check by its command line arguments, e.g.
+//   {"id": "web01", "args": ["--url", "https://example.com", "--timeout", "5"]}
+// and results in a line with the state and output of the check, e.g.
+//   {"id": "web01", "state": 0, "output": "Version: HTTP/1.1, Status: 200 OK | ..."}
+// Results are written as soon as the checks finish, so their order differs from the input.
+// Without an id, the line number of the definition is used instead.
+
+use crate::{check, parse_args};
+use anyhow::{anyhow, Result as AnyhowResult};
+use check_http::checking_types::State;
+use check_http::output::Output;
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::io::Read;
+use std::path::PathBuf;
+use tokio::task::JoinSet;
+
+pub const BATCH_FLAG: &str = "--batch";
+
+#[derive(Parser, Debug)]
+#[command(about = "check_httpv2 batch mode: Run the checks defined in FILE concurrently.")]
+pub struct BatchCli {
+    /// File with one check definition (JSON) per line, '-' to read from stdin
+    #[arg(long, value_name = "FILE")]
+    batch: PathBuf,
+
+    /// Maximal number of checks to run concurrently
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u16).range(1..))]
+    parallelism: u16,
+}
+
+pub fn is_requested(args: &[String]) -> bool {
+    args.get(1).map(String::as_str) == Some(BATCH_FLAG)
+}
+
+pub async fn run(args: &[String]) -> Result<(), clap::Error> {
+    let cli = BatchCli::try_parse_from(args)?;
+    let input = read_input(&cli.batch).map_err(|err| {
+        BatchCli::command().error(
+            ErrorKind::Io,
+            format!("Cannot read {}: {}", cli.batch.display(), err),
+        )
+    })?;
+
+    let mut running = JoinSet::new();
+    for (index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if running.len() >= cli.parallelism.into() {
+            print_finished(&mut running).await;
+        }
+        running.spawn(run_definition(index + 1, line.to_string()));
+    }
+    while !running.is_empty() {
+        print_finished(&mut running).await;
+    }
+    Ok(())
+}
+
+fn read_input(path: &PathBuf) -> std::io::Result<String> {
+    if path.as_os_str() == "-" {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        Ok(input)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
+
+async fn print_finished(running: &mut JoinSet<Value>) {
+    // run_definition catches the panics of the checks, so there are no join errors to expect.
+    if let Some(Ok(record)) = running.join_next().await {
+        println!("{}", record);
+    }
+}
+
+async fn run_definition(line_number: usize, line: String) -> Value {
+    let (id, args) = match parse_definition(&line) {
+        Ok((id, args)) => (id.unwrap_or(json!(line_number)), args),
+        Err(err) => {
+            return record(
+                json!(line_number),
+                State::Unknown,
+                &format!("Invalid check definition: {}", err),
+            )
+        }
+    };
+
+    let mut raw_args = vec!["check_httpv2".to_string()];
+    raw_args.extend(args);
+    let (args, unsupported_legacy_options) = match parse_args(&raw_args) {
+        Ok(parsed) => parsed,
+        Err(err) => return record(id, State::Unknown, &clap_error_message(&err)),
+    };
+    run_isolated(id, async move {
+        check(args, &raw_args[1..], unsupported_legacy_options).await
+    })
+    .await
+}
+
+// A panic must only take down its own check, not the whole batch.
+async fn run_isolated(
+    id: Value,
+    check: impl Future<Output = Result<Output, clap::Error>> + Send + 'static,
+) -> Value {
+    match tokio::spawn(check).await {
+        Ok(Ok(output)) => record(id, output.worst_state.clone(), &output.to_string()),
+        Ok(Err(err)) => record(id, State::Unknown, &clap_error_message(&err)),
+        Err(err) => record(id, State::Unknown, &format!("Check failed: {}", err)),
+    }
+}
+
+fn parse_definition(line: &str) -> AnyhowResult<(Option<Value>, Vec<String>)> {
+    let definition: Value = serde_json::from_str(line)?;
+    let id = match definition.get("id") {
+        None => None,
+        Some(id @ (Value::String(_) | Value::Number(_))) => Some(id.clone()),
+        Some(_) => return Err(anyhow!("id must be a string or a number")),
+    };
+    let args = definition
+        .get("args")
+        .and_then(Value::as_array)
+        .and_then(|args| {
+            args.iter()
+                .map(|arg| arg.as_str().map(String::from))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| anyhow!("args must be a list of strings"))?;
+    Ok((id, args))
+}
+
+// clap renders its errors including usage hints, which are of no use here.
+fn clap_error_message(err: &clap::Error) -> String {
+    err.to_string()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("error: ")
+        .to_string()
+}
+
+fn record(id: Value, state: State, output: &str) -> Value {
+    json!({
+        "id": id,
+        "state": i32::from(state),
+        "output": output,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn verify_cli() {
+        BatchCli::command().debug_assert()
+    }
+
+    #[test]
+    fn test_is_requested() {
+        assert!(is_requested(&args("check_httpv2 --batch -")));
+        assert!(!is_requested(&args(
+            "check_httpv2 --url http://foo --batch"
+        )));
+        assert!(!is_requested(&args("check_httpv2")));
+    }
+
+    #[test]
+    fn test_parse_definition() {
+        assert_eq!(
+            parse_definition(r#"{"id": "web", "args": ["-u", "http://foo"]}"#).unwrap(),
+            (Some(json!("web")), args("-u http://foo"))
+        );
+        assert_eq!(parse_definition(r#"{"args": []}"#).unwrap(), (None, vec![]));
+        assert!(parse_definition(r#"{"id": "web"}"#).is_err());
+        assert!(parse_definition(r#"{"id": [], "args": []}"#).is_err());
+        assert!(parse_definition(r#"{"args": ["-u", 1]}"#).is_err());
+        assert!(parse_definition("-u http://foo").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_definitions() {
+        assert_eq!(
+            run_definition(3, "garbage".to_string()).await,
+            json!({
+                "id": 3,
+                "state": 3,
+                "output": "Invalid check definition: expected value at line 1 column 1"
+            })
+        );
+        assert_eq!(
+            run_definition(
+                4,
+                r#"{"id": "web", "args": ["--timeout", "1"]}"#.to_string()
+            )
+            .await,
+            json!({
+                "id": "web",
+                "state": 3,
+                "output": "the following required arguments were not provided:"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_panicking_check() {
+        let record = run_isolated(json!("web"), async { panic!("check exploded") }).await;
+        assert_eq!(record["id"], json!("web"));
+        assert_eq!(record["state"], json!(3));
+        let output = record["output"].as_str().unwrap();
+        assert!(output.starts_with("Check failed:"));
+        assert!(output.contains("panicked"));
+    }
+}
@@ -11,6 +11,7 @@ use std::fmt::{Display, Formatter, Result as FormatResult};
 // So we're modelling exactly this.
 
 #[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(Clone)]
 pub struct UpperLevels<T> {
     pub warn: T,
     pub crit: Option<T>,
@@ -53,6 +54,7 @@ impl From<UpperLevels<u64>> for UpperLevels<f64> {
 }
 
 #[cfg_attr(test, derive(PartialEq))]
+#[derive(Clone)]
 pub struct LowerLevels<T> {
     pub warn: T,
     pub crit: Option<T>,
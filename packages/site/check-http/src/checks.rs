@@ -2,13 +2,17 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
+use anyhow::anyhow;
 use httpdate::parse_http_date;
+use jsonschema::JSONSchema;
 use regex::Regex;
 use reqwest::{
     header::{HeaderMap, HeaderValue},
     tls::TlsInfo,
     Method, StatusCode, Url, Version,
 };
+use std::error::Error;
+use std::str::FromStr;
 use std::time::{Duration, SystemTime};
 use x509_parser::{certificate::X509Certificate, prelude::FromDer};
 
@@ -16,7 +20,14 @@ use crate::checking_types::{
     check_lower_levels, check_upper_levels, notice, Bounds, CheckResult, LowerLevels, State,
     UpperLevels,
 };
-use crate::http::{Body, OnRedirect, ProcessedResponse, Server};
+use crate::har::Har;
+use crate::html::{CssSelector, Document};
+use crate::http::{
+    Body, Handshake, LinkScan, LinkScanResult, OcspStapling, OnRedirect, ProcessedResponse,
+    ProxyConnect, Retry, Server,
+};
+use crate::prometheus::{self, SeriesSelector};
+use crate::trend::{History, Snapshot, Trend};
 
 pub struct RequestInformation {
     pub request_url: Url,
@@ -32,10 +43,24 @@ pub struct CheckParameters {
     pub page_size: Option<Bounds<usize>>,
     pub response_time_levels: Option<UpperLevels<f64>>,
     pub document_age_levels: Option<UpperLevels<u64>>,
+    /// Levels for the difference between the server's Date header and our clock in seconds
+    pub clock_skew_levels: Option<UpperLevels<u64>>,
     pub body_matchers: Vec<TextMatcher>,
     pub header_matchers: Vec<(TextMatcher, TextMatcher)>,
     pub certificate_levels: Option<LowerLevels<u64>>,
     pub disable_certificate_verification: bool,
+    pub html_matchers: Vec<HtmlMatcher>,
+    pub link_scan: Option<LinkScan>,
+    pub retry: Option<Retry>,
+    pub prometheus_series: Vec<SeriesCheck>,
+    pub json_schema: Option<(JSONSchema, State)>,
+    pub graphql: Option<GraphQlCheck>,
+    pub trend: Option<(Trend, TrendLevels)>,
+    pub dual_stack: Option<DualStackLevels>,
+    pub har: Option<Har>,
+    pub tls_weak_cipher_state: State,
+    pub tls_missing_stapling_state: Option<State>,
+    pub state_mapping: StateMapping,
 }
 
 pub enum TextMatcher {
@@ -73,6 +98,149 @@ impl TextMatcher {
     }
 }
 
+pub enum HtmlMatcher {
+    Element(CssSelector),
+    Title(String),
+    ElementText(CssSelector, String),
+}
+
+pub struct SeriesCheck {
+    pub selector: SeriesSelector,
+    pub upper_levels: Option<UpperLevels<f64>>,
+    pub lower_levels: Option<LowerLevels<f64>>,
+}
+
+pub struct TrendLevels {
+    /// Levels for the increase of the response time in percent
+    pub response_time_increase: Option<UpperLevels<f64>>,
+    pub certificate_change_state: Option<State>,
+    pub body_change_state: Option<State>,
+    pub final_url_change_state: Option<State>,
+}
+
+/// Comparison of the responses we get via IPv4 and IPv6
+pub struct DualStackLevels {
+    pub difference_state: State,
+    /// Levels for the difference of the response times in seconds
+    pub response_time_difference: Option<UpperLevels<f64>>,
+}
+
+pub struct GraphQlCheck {
+    pub errors_state: State,
+    pub expectations: Vec<DataExpectation>,
+}
+
+/// Expected value in the data of a GraphQL response, addressed by a JSON pointer.
+/// Without a value, the data just has to be present (and not null).
+#[derive(Clone, Debug)]
+pub struct DataExpectation {
+    pub pointer: String,
+    pub value: Option<serde_json::Value>,
+}
+
+impl FromStr for DataExpectation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pointer, value) = match s.split_once('=') {
+            // Values that aren't valid JSON are taken as strings, so quoting is optional.
+            Some((pointer, value)) => (
+                pointer,
+                Some(
+                    serde_json::from_str(value)
+                        .unwrap_or_else(|_| serde_json::Value::String(value.to_string())),
+                ),
+            ),
+            None => (s, None),
+        };
+        if !pointer.starts_with('/') {
+            return Err(anyhow!(
+                "Invalid JSON pointer: {} (expected /path/to/value)",
+                pointer
+            ));
+        }
+        Ok(Self {
+            pointer: pointer.to_string(),
+            value,
+        })
+    }
+}
+
+/// Outcome categories whose state can be overridden by the user.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Connect,
+    Timeout,
+    Tls,
+    Redirect,
+    /// Inclusive range of status codes
+    Status(u16, u16),
+    BodyMatch,
+    HeaderMatch,
+    HtmlMatch,
+    Series,
+    JsonSchema,
+    GraphQl,
+}
+
+impl FromStr for Outcome {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_status = |code: &str| match code.parse::<u16>() {
+            Ok(code @ 100..=599) => Ok(code),
+            _ => Err(anyhow!("Invalid status code: {}", code)),
+        };
+        Ok(match s {
+            "connect" => Self::Connect,
+            "timeout" => Self::Timeout,
+            "tls" => Self::Tls,
+            "redirect" => Self::Redirect,
+            "body_match" => Self::BodyMatch,
+            "header_match" => Self::HeaderMatch,
+            "html_match" => Self::HtmlMatch,
+            "series" => Self::Series,
+            "json_schema" => Self::JsonSchema,
+            "graphql" => Self::GraphQl,
+            _ => match (s.split_once('-'), s.strip_suffix("xx")) {
+                (Some((from, to)), _) => Self::Status(parse_status(from)?, parse_status(to)?),
+                (None, Some(class @ ("1" | "2" | "3" | "4" | "5"))) => {
+                    let class = parse_status(&format!("{}00", class))?;
+                    Self::Status(class, class + 99)
+                }
+                _ => {
+                    let code = parse_status(s).map_err(|_| anyhow!("Unknown outcome: {}", s))?;
+                    Self::Status(code, code)
+                }
+            },
+        })
+    }
+}
+
+/// User defined states for outcomes, the last matching entry wins.
+#[derive(Default)]
+pub struct StateMapping(pub Vec<(Outcome, State)>);
+
+impl StateMapping {
+    fn state(&self, outcome: Outcome, default: State) -> State {
+        self.find(|o| *o == outcome).unwrap_or(default)
+    }
+
+    fn status_state(&self, status: StatusCode, default: State) -> State {
+        let status = status.as_u16();
+        self.find(|o| matches!(o, Outcome::Status(from, to) if (*from..=*to).contains(&status)))
+            .unwrap_or(default)
+    }
+
+    fn find(&self, predicate: impl Fn(&Outcome) -> bool) -> Option<State> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(outcome, _)| predicate(outcome))
+            .map(|(_, state)| state.clone())
+    }
+}
+
 pub fn collect_response_checks(
     response: Result<ProcessedResponse, reqwest::Error>,
     request_information: RequestInformation,
@@ -80,7 +248,7 @@ pub fn collect_response_checks(
 ) -> Vec<CheckResult> {
     let response = match response {
         Ok(resp) => resp,
-        Err(err) => return check_reqwest_error(err, request_information),
+        Err(err) => return check_reqwest_error(err, request_information, &params.state_mapping),
     };
 
     let (body, body_check_results) = check_body(response.body);
@@ -98,16 +266,26 @@ pub fn collect_response_checks(
     ))
     .chain(check_method(request_information.method))
     .chain(check_version(response.version))
-    .chain(check_status(response.status, params.status_code))
+    .chain(check_status(
+        response.status,
+        params.status_code,
+        &params.state_mapping,
+    ))
     .chain(check_response_time(
         response.time_headers,
         response.time_body,
         params.response_time_levels,
         request_information.timeout,
     ))
+    .chain(check_proxy_connect(response.proxy_connect))
     .chain(body_check_results)
+    .chain(check_clock_skew(
+        response.headers_received,
+        response.headers.get("date"),
+        params.clock_skew_levels,
+    ))
     .chain(check_page_age(
-        SystemTime::now(),
+        response.headers_received,
         response
             .headers
             .get("last-modified")
@@ -120,9 +298,48 @@ pub fn collect_response_checks(
         params.certificate_levels,
         params.disable_certificate_verification,
     ))
+    .chain(check_handshake(
+        response.handshake,
+        params.tls_weak_cipher_state,
+        params.tls_missing_stapling_state,
+    ))
     .chain(check_user_agent(request_information.user_agent))
-    .chain(check_headers(&response.headers, params.header_matchers))
-    .chain(check_body_matching(body.as_ref(), params.body_matchers))
+    .chain(check_headers(
+        &response.headers,
+        params.header_matchers,
+        params
+            .state_mapping
+            .state(Outcome::HeaderMatch, State::Warn),
+    ))
+    .chain(check_body_matching(
+        body.as_ref(),
+        params.body_matchers,
+        params.state_mapping.state(Outcome::BodyMatch, State::Warn),
+    ))
+    .chain(check_html(
+        body.as_ref(),
+        params.html_matchers,
+        params.state_mapping.state(Outcome::HtmlMatch, State::Warn),
+    ))
+    .chain(check_prometheus_series(
+        body.as_ref(),
+        params.prometheus_series,
+        &params.state_mapping,
+    ))
+    .chain(check_json_schema(
+        body.as_ref(),
+        params.json_schema.map(|(schema, state)| {
+            (
+                schema,
+                params.state_mapping.state(Outcome::JsonSchema, state),
+            )
+        }),
+    ))
+    .chain(check_graphql(
+        body.as_ref(),
+        params.graphql,
+        &params.state_mapping,
+    ))
     .flatten()
     .collect()
 }
@@ -152,31 +369,67 @@ fn check_urls(url: Url, final_url: Url, server: Option<Server>) -> Vec<Option<Ch
 fn check_reqwest_error(
     err: reqwest::Error,
     request_information: RequestInformation,
+    state_mapping: &StateMapping,
 ) -> Vec<CheckResult> {
+    let state = error_state(&err, state_mapping);
     if err.is_timeout() {
         notice(
-            State::Crit,
+            state,
             &format!(
                 "Could not connect to {} within specified timeout: {}",
                 request_information.request_url,
                 render_seconds_with_ms(&request_information.timeout.as_secs_f64()),
             ),
         )
-    } else if err.is_connect()
-        // Hit one of max_redirs, sticky, stickyport
-        | err.is_redirect()
-    {
-        notice(State::Crit, &err.to_string().replace('\n', " - "))
     } else {
         // The errors coming from reqwest are usually short and don't contain
         // newlines, but we want to be safe.
-        notice(State::Unknown, &err.to_string().replace('\n', " - "))
+        notice(state, &err.to_string().replace('\n', " - "))
     }
     .into_iter()
     .flatten()
     .collect()
 }
 
+fn error_state(err: &reqwest::Error, state_mapping: &StateMapping) -> State {
+    let outcome = if err.is_timeout() {
+        Outcome::Timeout
+    } else if err.is_redirect() {
+        // Hit one of max_redirs, sticky, stickyport
+        Outcome::Redirect
+    } else if !err.is_connect() {
+        return State::Unknown;
+    } else if is_tls_error(err) {
+        Outcome::Tls
+    } else {
+        Outcome::Connect
+    };
+    state_mapping.state(outcome, State::Crit)
+}
+
+// reqwest doesn't tell us about TLS errors explicitly, so we have to look into the source chain.
+// rustls reports its errors as InvalidData, native-tls (OpenSSL) errors are identified by text.
+fn is_tls_error(err: &reqwest::Error) -> bool {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if err
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|io_err| io_err.kind() == std::io::ErrorKind::InvalidData)
+        {
+            return true;
+        }
+        let text = err.to_string().to_ascii_lowercase();
+        if ["tls", "ssl", "certificate", "handshake"]
+            .iter()
+            .any(|word| text.contains(word))
+        {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
 fn check_method(method: Method) -> Vec<Option<CheckResult>> {
     vec![CheckResult::details(
         State::Ok,
@@ -194,18 +447,31 @@ fn check_version(version: Version) -> Vec<Option<CheckResult>> {
 fn check_status(
     status: StatusCode,
     accepted_statuses: Vec<StatusCode>,
+    state_mapping: &StateMapping,
 ) -> Vec<Option<CheckResult>> {
-    let (state, status_text) = if accepted_statuses.is_empty() {
-        (
-            if status.is_client_error() {
-                State::Warn
-            } else if status.is_server_error() {
-                State::Crit
-            } else {
-                State::Ok
-            },
-            String::new(),
-        )
+    let (state, status_text) = status_state(status, &accepted_statuses, state_mapping);
+    let text = format!("Status: {}{}", status, status_text);
+    vec![
+        CheckResult::summary(state.clone(), &text),
+        CheckResult::details(state, &text),
+    ]
+}
+
+fn status_state(
+    status: StatusCode,
+    accepted_statuses: &[StatusCode],
+    state_mapping: &StateMapping,
+) -> (State, String) {
+    // Explicitly expected status codes take precedence over the state mapping.
+    if accepted_statuses.is_empty() {
+        let state = if status.is_client_error() {
+            State::Warn
+        } else if status.is_server_error() {
+            State::Crit
+        } else {
+            State::Ok
+        };
+        (state_mapping.status_state(status, state), String::new())
     } else if accepted_statuses.contains(&status) {
         (State::Ok, String::new())
     } else {
@@ -224,13 +490,7 @@ fn check_status(
                 )
             },
         )
-    };
-
-    let text = format!("Status: {}{}", status, status_text);
-    vec![
-        CheckResult::summary(state.clone(), &text),
-        CheckResult::details(state, &text),
-    ]
+    }
 }
 
 fn check_redirect(
@@ -261,6 +521,7 @@ fn check_redirect(
 fn check_headers(
     headers: &HeaderMap,
     matchers: Vec<(TextMatcher, TextMatcher)>,
+    mismatch_state: State,
 ) -> Vec<Option<CheckResult>> {
     if matchers.is_empty() {
         return vec![];
@@ -309,7 +570,7 @@ fn check_headers(
                 )]
             } else {
                 notice(
-                    State::Warn,
+                    mismatch_state.clone(),
                     &format!(
                         "{}: {}:{} (not {})",
                         match_text,
@@ -358,7 +619,11 @@ fn check_body<T: std::error::Error>(
     (Some(body), vec![])
 }
 
-fn check_body_matching(body: Option<&Body>, matcher: Vec<TextMatcher>) -> Vec<Option<CheckResult>> {
+fn check_body_matching(
+    body: Option<&Body>,
+    matcher: Vec<TextMatcher>,
+    mismatch_state: State,
+) -> Vec<Option<CheckResult>> {
     let Some(body) = body else {
         return vec![];
     };
@@ -389,7 +654,7 @@ fn check_body_matching(body: Option<&Body>, matcher: Vec<TextMatcher>) -> Vec<Op
                 )]
             } else {
                 notice(
-                    State::Warn,
+                    mismatch_state.clone(),
                     &format!("{}: {} ({})", match_text, m.inner(), not_match_predicate),
                 )
             }
@@ -397,945 +662,2620 @@ fn check_body_matching(body: Option<&Body>, matcher: Vec<TextMatcher>) -> Vec<Op
         .collect::<Vec<_>>()
 }
 
-fn check_page_size(
+fn check_html(
     body: Option<&Body>,
-    page_size_limits: Option<Bounds<usize>>,
+    matchers: Vec<HtmlMatcher>,
+    mismatch_state: State,
 ) -> Vec<Option<CheckResult>> {
     let Some(body) = body else {
         return vec![];
     };
-
-    let state = page_size_limits
-        .as_ref()
-        .and_then(|bounds| bounds.evaluate(&body.length, State::Warn))
-        .unwrap_or(State::Ok);
-
-    let bounds_info = if let State::Warn = state {
-        match page_size_limits {
-            Some(Bounds { lower, upper: None }) => format!(" (warn below {} Bytes)", lower),
-            Some(Bounds {
-                lower,
-                upper: Some(upper),
-            }) => format!(" (warn below/above {} Bytes/{} Bytes)", lower, upper),
-            _ => "".to_string(),
-        }
-    } else {
-        "".to_string()
+    if matchers.is_empty() {
+        return vec![];
     };
 
-    let mut res = notice(
-        state,
-        &format!("Page size: {} Bytes{}", body.length, bounds_info),
-    );
-    res.push(CheckResult::metric(
-        "response_size",
-        body.length as f64,
-        Some('B'),
-        None,
-        Some(0.),
-        None,
-    ));
-    res
+    let document = Document::parse(&body.text);
+    matchers
+        .iter()
+        .flat_map(|matcher| match matcher {
+            HtmlMatcher::Element(selector) => {
+                let count = document.count(selector);
+                if count > 0 {
+                    vec![CheckResult::details(
+                        State::Ok,
+                        &format!("Expected HTML element: {} (found {})", selector, count),
+                    )]
+                } else {
+                    notice(
+                        mismatch_state.clone(),
+                        &format!("Expected HTML element: {} (not found)", selector),
+                    )
+                }
+            }
+            HtmlMatcher::Title(expected) => match document.title() {
+                Some(title) if &title == expected => vec![CheckResult::details(
+                    State::Ok,
+                    &format!("HTML title: {}", title),
+                )],
+                Some(title) => notice(
+                    mismatch_state.clone(),
+                    &format!("HTML title: {} (expected {})", title, expected),
+                ),
+                None => notice(
+                    mismatch_state.clone(),
+                    &format!("HTML title: none (expected {})", expected),
+                ),
+            },
+            HtmlMatcher::ElementText(selector, text) => {
+                let match_text = format!("Expected text in HTML element {}: {}", selector, text);
+                if document
+                    .texts(selector)
+                    .iter()
+                    .any(|element_text| element_text.contains(text.as_str()))
+                {
+                    vec![CheckResult::details(
+                        State::Ok,
+                        &format!("{} (found)", match_text),
+                    )]
+                } else {
+                    notice(
+                        mismatch_state.clone(),
+                        &format!("{} (not found)", match_text),
+                    )
+                }
+            }
+        })
+        .collect()
 }
 
-fn check_response_time(
-    time_header: Duration,
-    time_body: Option<Duration>,
-    response_time_levels: Option<UpperLevels<f64>>,
-    timeout: Duration,
+fn check_prometheus_series(
+    body: Option<&Body>,
+    series_checks: Vec<SeriesCheck>,
+    state_mapping: &StateMapping,
 ) -> Vec<Option<CheckResult>> {
-    let response_time = if let Some(time_body) = time_body {
-        time_header + time_body
-    } else {
-        time_header
+    let Some(body) = body else {
+        return vec![];
+    };
+    if series_checks.is_empty() {
+        return vec![];
     };
 
-    let mut ret = check_upper_levels(
-        "Response time",
-        response_time.as_secs_f64(),
-        render_seconds_with_ms,
-        &response_time_levels,
-    );
-    ret.push(CheckResult::metric(
-        "response_time",
-        response_time.as_secs_f64(),
-        Some('s'),
-        response_time_levels,
-        Some(0.),
-        Some(timeout.as_secs_f64()),
-    ));
-    ret.push(CheckResult::metric(
-        "time_http_headers",
-        time_header.as_secs_f64(),
-        Some('s'),
-        None,
-        None,
-        None,
-    ));
-    if let Some(time_body) = time_body {
-        ret.push(CheckResult::metric(
-            "time_http_body",
-            time_body.as_secs_f64(),
-            Some('s'),
-            None,
-            None,
-            None,
-        ))
+    let samples = match prometheus::parse(&body.text) {
+        Ok(samples) => samples,
+        Err(err) => {
+            return notice(
+                state_mapping.state(Outcome::Series, State::Crit),
+                &format!("Unable to parse metrics exposition: {}", err),
+            )
+        }
     };
-    ret
+
+    series_checks
+        .iter()
+        .flat_map(|series_check| {
+            let matching: Vec<_> = samples
+                .iter()
+                .filter(|sample| series_check.selector.matches(sample))
+                .collect();
+            if matching.is_empty() {
+                return notice(
+                    state_mapping.state(Outcome::Series, State::Warn),
+                    &format!("Series not found: {}", series_check.selector),
+                );
+            }
+            matching
+                .into_iter()
+                .flat_map(|sample| {
+                    let description = format!("Series {}", sample);
+                    // Both levels are evaluated, and the worse of both is reported.
+                    let upper_state = series_check
+                        .upper_levels
+                        .as_ref()
+                        .and_then(|levels| levels.evaluate(&sample.value))
+                        .unwrap_or(State::Ok);
+                    let lower_state = series_check
+                        .lower_levels
+                        .as_ref()
+                        .and_then(|levels| levels.evaluate(&sample.value))
+                        .unwrap_or(State::Ok);
+                    let mut results = if lower_state > upper_state {
+                        check_lower_levels(
+                            &description,
+                            sample.value,
+                            f64::to_string,
+                            &series_check.lower_levels,
+                        )
+                    } else {
+                        check_upper_levels(
+                            &description,
+                            sample.value,
+                            f64::to_string,
+                            &series_check.upper_levels,
+                        )
+                    };
+                    if sample.value.is_finite() {
+                        results.push(CheckResult::metric(
+                            &sample.metric_name(),
+                            sample.value,
+                            None,
+                            series_check.upper_levels.clone(),
+                            None,
+                            None,
+                        ));
+                    }
+                    results
+                })
+                .collect()
+        })
+        .collect()
 }
 
-fn check_page_age(
-    now: SystemTime,
-    age_header: Option<&HeaderValue>,
-    page_age_levels: Option<UpperLevels<u64>>,
+fn check_json_schema(
+    body: Option<&Body>,
+    json_schema: Option<(JSONSchema, State)>,
 ) -> Vec<Option<CheckResult>> {
-    if page_age_levels.is_none() {
+    let (Some(body), Some((schema, state))) = (body, json_schema) else {
         return vec![];
     };
 
-    let Some(age) = age_header else {
-        return notice(State::Crit, "Can't determine page age");
-    };
-    let page_age_error = "Can't decode page age";
-    let Ok(age) = age.to_str() else {
-        return notice(State::Crit, page_age_error);
-    };
-    let Ok(age) = parse_http_date(age) else {
-        return notice(State::Crit, page_age_error);
+    let instance: serde_json::Value = match serde_json::from_str(&body.text) {
+        Ok(instance) => instance,
+        Err(err) => return notice(state, &format!("Response body is not valid JSON: {}", err)),
     };
-    let Ok(age) = now.duration_since(age) else {
-        return notice(State::Crit, page_age_error);
+
+    let violations: Vec<String> = match schema.validate(&instance) {
+        Ok(()) => return vec![CheckResult::details(State::Ok, "JSON schema: valid")],
+        Err(errors) => errors
+            .map(|error| {
+                let path = error.instance_path.to_string();
+                format!(
+                    "JSON schema violation at {}: {}",
+                    if path.is_empty() { "/" } else { &path },
+                    error
+                )
+            })
+            .collect(),
     };
 
-    check_upper_levels(
-        "Page age",
-        age.as_secs(),
-        |secs| format!("{} seconds", secs),
-        &page_age_levels,
-    )
+    let mut results = notice(
+        state.clone(),
+        &format!("JSON schema: {} violation(s)", violations.len()),
+    );
+    results.extend(
+        violations
+            .iter()
+            .map(|violation| CheckResult::details(state.clone(), violation)),
+    );
+    results
 }
 
-// TODO(au): Tests
-fn check_certificate(
-    tls_info: Option<TlsInfo>,
-    certificate_levels: Option<LowerLevels<u64>>,
-    disable_certificate_verification: bool,
+fn check_graphql(
+    body: Option<&Body>,
+    graphql: Option<GraphQlCheck>,
+    state_mapping: &StateMapping,
 ) -> Vec<Option<CheckResult>> {
-    if disable_certificate_verification {
-        return vec![CheckResult::details(
-            State::Ok,
-            "Server certificate validity: ignored",
-        )];
-    }
-
-    let Some(cert) = tls_info.as_ref().and_then(|t| t.peer_certificate()) else {
-        // If the outer tlsinfo is None, we didn't fetch it -> OK
-        // If the inner peer cert is None, there is no certificate and we're talking plain HTTP.
-        // Otherwise the TLS handshake would already have failed.
+    let (Some(body), Some(graphql)) = (body, graphql) else {
         return vec![];
     };
-
-    let Ok((_, cert)) = X509Certificate::from_der(cert) else {
-        return notice(State::Unknown, "Unable to parse server certificate");
-    };
-    let Some(validity) = cert.validity().time_to_expiration() else {
-        return notice(State::Crit, "Invalid server certificate");
+    let errors_state = state_mapping.state(Outcome::GraphQl, graphql.errors_state);
+    let mismatch_state = state_mapping.state(Outcome::GraphQl, State::Warn);
+
+    let response: serde_json::Value = match serde_json::from_str(&body.text) {
+        Ok(response) => response,
+        Err(err) => {
+            return notice(
+                errors_state,
+                &format!("GraphQL response is not valid JSON: {}", err),
+            )
+        }
     };
 
-    check_lower_levels(
-        "Server certificate validity",
-        validity.whole_days().max(0).unsigned_abs(),
-        |days| format!("{} days", days),
-        &certificate_levels,
-    )
-}
+    // Errors may come with or without (partial) data, so we check both.
+    let errors = response
+        .get("errors")
+        .and_then(|errors| errors.as_array())
+        .filter(|errors| !errors.is_empty());
+    let mut results = match errors {
+        None => vec![CheckResult::details(State::Ok, "GraphQL errors: none")],
+        Some(errors) => {
+            let mut results = notice(
+                errors_state.clone(),
+                &format!("GraphQL errors: {}", errors.len()),
+            );
+            results.extend(errors.iter().map(|error| {
+                CheckResult::details(
+                    errors_state.clone(),
+                    &format!("GraphQL error: {}", graphql_error_message(error)),
+                )
+            }));
+            results
+        }
+    };
 
-fn check_user_agent(user_agent: String) -> Vec<Option<CheckResult>> {
-    vec![CheckResult::details(
-        State::Ok,
-        &format!("User agent: {}", user_agent),
-    )]
+    let data = response.get("data").unwrap_or(&serde_json::Value::Null);
+    for expectation in graphql.expectations {
+        let text = format!("GraphQL data at {}", expectation.pointer);
+        results.extend(
+            match (data.pointer(&expectation.pointer), expectation.value) {
+                (None | Some(serde_json::Value::Null), None) => {
+                    notice(mismatch_state.clone(), &format!("{}: missing", text))
+                }
+                (Some(_), None) => vec![CheckResult::details(
+                    State::Ok,
+                    &format!("{}: present", text),
+                )],
+                (Some(actual), Some(expected)) if *actual == expected => {
+                    vec![CheckResult::details(
+                        State::Ok,
+                        &format!("{}: {}", text, actual),
+                    )]
+                }
+                (actual, Some(expected)) => notice(
+                    mismatch_state.clone(),
+                    &format!(
+                        "{}: {} (expected {})",
+                        text,
+                        actual.map_or("missing".to_string(), |actual| actual.to_string()),
+                        expected
+                    ),
+                ),
+            },
+        );
+    }
+    results
 }
 
-fn render_seconds_with_ms(val: &f64) -> String {
-    // Format to three digits to get a sense of milliseconds,
-    // but crop unnecessary trailing zeros/decimal point
-    format!(
-        "{} seconds",
-        format!("{:.3}", val)
-            .trim_end_matches('0')
-            .trim_end_matches('.')
+// See the GraphQL spec, section 7.1.2: Every error must have a message and may have a path.
+fn graphql_error_message(error: &serde_json::Value) -> String {
+    let message = error
+        .get("message")
+        .and_then(|message| message.as_str())
+        .map_or_else(|| error.to_string(), str::to_string);
+    match error.get("path").and_then(|path| path.as_array()) {
+        Some(path) => format!(
+            "{} (at {})",
+            message,
+            path.iter()
+                .map(|segment| match segment {
+                    serde_json::Value::String(field) => field.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(".")
+        ),
+        None => message,
+    }
+}
+
+pub fn collect_link_checks(link_scan: Result<LinkScanResult, reqwest::Error>) -> Vec<CheckResult> {
+    check_links(link_scan).into_iter().flatten().collect()
+}
+
+fn check_links(link_scan: Result<LinkScanResult, reqwest::Error>) -> Vec<Option<CheckResult>> {
+    let (link_statuses, time_limit_reached) = match link_scan {
+        Ok(link_scan) => (link_scan.statuses, link_scan.time_limit_reached),
+        Err(err) => {
+            return notice(
+                State::Unknown,
+                &format!("Link scan failed: {}", err.to_string().replace('\n', " - ")),
+            )
+        }
+    };
+
+    let broken: Vec<(&Url, String)> = link_statuses
+        .iter()
+        .filter_map(|link| match &link.status {
+            Ok(status) if status.is_client_error() || status.is_server_error() => {
+                Some((&link.url, status.to_string()))
+            }
+            Ok(_) => None,
+            Err(err) => Some((&link.url, err.to_string().replace('\n', " - "))),
+        })
+        .collect();
+
+    let mut results = if broken.is_empty() {
+        vec![CheckResult::details(
+            State::Ok,
+            &format!("Links checked: {}, none broken", link_statuses.len()),
+        )]
+    } else {
+        notice(
+            State::Warn,
+            &format!(
+                "Broken links: {} of {} checked",
+                broken.len(),
+                link_statuses.len()
+            ),
+        )
+    };
+    results.extend(broken.iter().map(|(url, reason)| {
+        CheckResult::details(State::Warn, &format!("Broken link: {} ({})", url, reason))
+    }));
+    if time_limit_reached {
+        results.push(CheckResult::details(
+            State::Ok,
+            "Link scan stopped at its time limit, remaining links not checked",
+        ));
+    }
+    results
+}
+
+pub fn collect_trend_checks(
+    history: &History,
+    snapshot: &Snapshot,
+    levels: TrendLevels,
+) -> Vec<CheckResult> {
+    check_trend(history, snapshot, levels)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn check_trend(
+    history: &History,
+    snapshot: &Snapshot,
+    levels: TrendLevels,
+) -> Vec<Option<CheckResult>> {
+    let Some(previous) = &history.previous else {
+        return vec![CheckResult::details(
+            State::Ok,
+            "No previous results to compare with",
+        )];
+    };
+
+    check_response_time_increase(
+        snapshot.response_time,
+        history.response_times.len(),
+        history.average_response_time(),
+        levels.response_time_increase,
+    )
+    .into_iter()
+    .chain(check_change(
+        "Server certificate",
+        previous.certificate_serial.as_deref(),
+        snapshot.certificate_serial.as_deref(),
+        levels.certificate_change_state,
+        true,
+    ))
+    .chain(check_change(
+        "Response body",
+        previous.body_hash.as_deref(),
+        snapshot.body_hash.as_deref(),
+        levels.body_change_state,
+        // Hashes of bodies aren't worth showing
+        false,
+    ))
+    .chain(check_change(
+        "Final URL",
+        Some(&previous.final_url),
+        Some(&snapshot.final_url),
+        levels.final_url_change_state,
+        true,
+    ))
+    .collect()
+}
+
+fn check_response_time_increase(
+    response_time: f64,
+    runs: usize,
+    average: Option<f64>,
+    levels: Option<UpperLevels<f64>>,
+) -> Vec<Option<CheckResult>> {
+    let (Some(levels), Some(average)) = (levels, average.filter(|average| *average > 0.)) else {
+        return vec![];
+    };
+    let increase = (response_time / average - 1.) * 100.;
+    let mut results = check_upper_levels(
+        &format!(
+            "Response time compared to average of last {} run(s) ({})",
+            runs,
+            render_seconds_with_ms(&average)
+        ),
+        increase,
+        |percent| format!("{:+.1}%", percent),
+        &Some(levels),
+    );
+    results.push(CheckResult::metric(
+        "response_time_average",
+        average,
+        Some('s'),
+        None,
+        Some(0.),
+        None,
+    ));
+    results
+}
+
+// Values are only compared if we know both, e.g. a body we didn't fetch doesn't count as change.
+fn check_change(
+    what: &str,
+    previous: Option<&str>,
+    current: Option<&str>,
+    state: Option<State>,
+    show_values: bool,
+) -> Vec<Option<CheckResult>> {
+    let (Some(state), Some(previous), Some(current)) = (state, previous, current) else {
+        return vec![];
+    };
+    if previous == current {
+        return vec![CheckResult::details(
+            State::Ok,
+            &format!("{}: unchanged since previous run", what),
+        )];
+    }
+    let change = if show_values {
+        format!(" ({} -> {})", previous, current)
+    } else {
+        String::new()
+    };
+    notice(
+        state,
+        &format!("{}: changed since previous run{}", what, change),
     )
 }
 
+pub fn collect_dual_stack_checks(
+    ipv4: &Result<ProcessedResponse, reqwest::Error>,
+    ipv6: &Result<ProcessedResponse, reqwest::Error>,
+    levels: DualStackLevels,
+    params: &CheckParameters,
+) -> Vec<CheckResult> {
+    let check_ip_version = |label: &str, response: &Result<ProcessedResponse, reqwest::Error>| {
+        let (state, text) = match response {
+            Ok(resp) => {
+                let (state, status_text) =
+                    status_state(resp.status, &params.status_code, &params.state_mapping);
+                (
+                    state,
+                    format!(
+                        "Status: {}{}, Response time: {}",
+                        resp.status,
+                        status_text,
+                        render_seconds_with_ms(&response_time(resp).as_secs_f64())
+                    ),
+                )
+            }
+            Err(err) => (
+                error_state(err, &params.state_mapping),
+                err.to_string().replace('\n', " - "),
+            ),
+        };
+        (state, format!("{}: {}", label, text))
+    };
+    // The IPv4 response also went through all the regular checks, so we only add the details.
+    let (ipv4_state, ipv4_text) = check_ip_version("IPv4", ipv4);
+    let (ipv6_state, ipv6_text) = check_ip_version("IPv6", ipv6);
+    let mut results = vec![CheckResult::details(ipv4_state, &ipv4_text)];
+    results.extend(notice(ipv6_state, &ipv6_text));
+    if let (Ok(ipv4), Ok(ipv6)) = (ipv4, ipv6) {
+        results.extend(check_dual_stack_differences(ipv4, ipv6, levels));
+    }
+    results.into_iter().flatten().collect()
+}
+
+fn check_dual_stack_differences(
+    ipv4: &ProcessedResponse,
+    ipv6: &ProcessedResponse,
+    levels: DualStackLevels,
+) -> Vec<Option<CheckResult>> {
+    let mut results = vec![];
+    if ipv4.status != ipv6.status {
+        results.extend(notice(
+            levels.difference_state.clone(),
+            &format!(
+                "Status differs between IPv4 and IPv6: {} vs. {}",
+                ipv4.status, ipv6.status
+            ),
+        ));
+    }
+    // Bodies we didn't fetch or couldn't read don't count as difference.
+    if let (Some(Ok(ipv4_body)), Some(Ok(ipv6_body))) = (&ipv4.body, &ipv6.body) {
+        if ipv4_body.text != ipv6_body.text {
+            results.extend(notice(
+                levels.difference_state,
+                "Body differs between IPv4 and IPv6",
+            ));
+        }
+    }
+
+    let ipv4_time = response_time(ipv4).as_secs_f64();
+    let ipv6_time = response_time(ipv6).as_secs_f64();
+    results.extend(check_upper_levels(
+        &format!(
+            "Response time difference between IPv4 and IPv6 (IPv6 {})",
+            if ipv6_time > ipv4_time {
+                "slower"
+            } else {
+                "faster"
+            }
+        ),
+        (ipv6_time - ipv4_time).abs(),
+        render_seconds_with_ms,
+        &levels.response_time_difference,
+    ));
+    results.push(CheckResult::metric(
+        "response_time_ipv6",
+        ipv6_time,
+        Some('s'),
+        None,
+        Some(0.),
+        None,
+    ));
+    results
+}
+
+fn response_time(response: &ProcessedResponse) -> Duration {
+    response.time_headers + response.time_body.unwrap_or_default()
+}
+
+pub fn collect_attempt_checks(attempts: usize, max_attempts: usize) -> Vec<CheckResult> {
+    check_attempts(attempts, max_attempts)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn check_attempts(attempts: usize, max_attempts: usize) -> Vec<Option<CheckResult>> {
+    vec![
+        CheckResult::details(
+            State::Ok,
+            &format!("Attempts: {} of {}", attempts, max_attempts),
+        ),
+        CheckResult::metric(
+            "attempts",
+            attempts as f64,
+            None,
+            None,
+            Some(1.),
+            Some(max_attempts as f64),
+        ),
+    ]
+}
+
+fn check_page_size(
+    body: Option<&Body>,
+    page_size_limits: Option<Bounds<usize>>,
+) -> Vec<Option<CheckResult>> {
+    let Some(body) = body else {
+        return vec![];
+    };
+
+    let state = page_size_limits
+        .as_ref()
+        .and_then(|bounds| bounds.evaluate(&body.length, State::Warn))
+        .unwrap_or(State::Ok);
+
+    let bounds_info = if let State::Warn = state {
+        match page_size_limits {
+            Some(Bounds { lower, upper: None }) => format!(" (warn below {} Bytes)", lower),
+            Some(Bounds {
+                lower,
+                upper: Some(upper),
+            }) => format!(" (warn below/above {} Bytes/{} Bytes)", lower, upper),
+            _ => "".to_string(),
+        }
+    } else {
+        "".to_string()
+    };
+
+    let mut res = notice(
+        state,
+        &format!("Page size: {} Bytes{}", body.length, bounds_info),
+    );
+    res.push(CheckResult::metric(
+        "response_size",
+        body.length as f64,
+        Some('B'),
+        None,
+        Some(0.),
+        None,
+    ));
+    res
+}
+
+fn check_response_time(
+    time_header: Duration,
+    time_body: Option<Duration>,
+    response_time_levels: Option<UpperLevels<f64>>,
+    timeout: Duration,
+) -> Vec<Option<CheckResult>> {
+    let response_time = if let Some(time_body) = time_body {
+        time_header + time_body
+    } else {
+        time_header
+    };
+
+    let mut ret = check_upper_levels(
+        "Response time",
+        response_time.as_secs_f64(),
+        render_seconds_with_ms,
+        &response_time_levels,
+    );
+    ret.push(CheckResult::metric(
+        "response_time",
+        response_time.as_secs_f64(),
+        Some('s'),
+        response_time_levels,
+        Some(0.),
+        Some(timeout.as_secs_f64()),
+    ));
+    ret.push(CheckResult::metric(
+        "time_http_headers",
+        time_header.as_secs_f64(),
+        Some('s'),
+        None,
+        None,
+        None,
+    ));
+    if let Some(time_body) = time_body {
+        ret.push(CheckResult::metric(
+            "time_http_body",
+            time_body.as_secs_f64(),
+            Some('s'),
+            None,
+            None,
+            None,
+        ))
+    };
+    ret
+}
+
+fn check_proxy_connect(
+    proxy_connect: Option<anyhow::Result<ProxyConnect>>,
+) -> Vec<Option<CheckResult>> {
+    let connect = match proxy_connect {
+        None => return vec![],
+        Some(Err(err)) => return notice(State::Warn, &format!("Proxy CONNECT failed: {}", err)),
+        Some(Ok(connect)) => connect,
+    };
+    let state = if (200..300).contains(&connect.status) {
+        State::Ok
+    } else {
+        State::Warn
+    };
+    let mut ret = notice(
+        state,
+        &format!(
+            "Proxy CONNECT via {}: {} ({} {})",
+            connect.proxy,
+            render_seconds_with_ms(&connect.time.as_secs_f64()),
+            connect.status,
+            connect.reason
+        ),
+    );
+    ret.push(CheckResult::metric(
+        "time_proxy_connect",
+        connect.time.as_secs_f64(),
+        Some('s'),
+        None,
+        None,
+        None,
+    ));
+    ret
+}
+
+fn check_page_age(
+    now: SystemTime,
+    age_header: Option<&HeaderValue>,
+    page_age_levels: Option<UpperLevels<u64>>,
+) -> Vec<Option<CheckResult>> {
+    if page_age_levels.is_none() {
+        return vec![];
+    };
+
+    let Some(age) = age_header else {
+        return notice(State::Crit, "Can't determine page age");
+    };
+    let page_age_error = "Can't decode page age";
+    let Ok(age) = age.to_str() else {
+        return notice(State::Crit, page_age_error);
+    };
+    let Ok(age) = parse_http_date(age) else {
+        return notice(State::Crit, page_age_error);
+    };
+    let Ok(age) = now.duration_since(age) else {
+        return notice(State::Crit, page_age_error);
+    };
+
+    check_upper_levels(
+        "Page age",
+        age.as_secs(),
+        |secs| format!("{} seconds", secs),
+        &page_age_levels,
+    )
+}
+
+// The Date header only has a resolution of seconds, so we compare in seconds.
+fn check_clock_skew(
+    now: SystemTime,
+    date_header: Option<&HeaderValue>,
+    clock_skew_levels: Option<UpperLevels<u64>>,
+) -> Vec<Option<CheckResult>> {
+    if clock_skew_levels.is_none() {
+        return vec![];
+    };
+
+    let Some(date) = date_header else {
+        return notice(State::Crit, "Can't determine clock skew (no Date header)");
+    };
+    let Some(date) = date
+        .to_str()
+        .ok()
+        .and_then(|date| parse_http_date(date).ok())
+    else {
+        return notice(State::Crit, "Can't decode Date header");
+    };
+    let (skew, direction) = match date.duration_since(now) {
+        Ok(ahead) => (ahead.as_secs(), "ahead"),
+        Err(err) => (err.duration().as_secs(), "behind"),
+    };
+
+    let description = match skew {
+        0 => "Clock skew".to_string(),
+        _ => format!("Clock skew (server {})", direction),
+    };
+    let mut results = check_upper_levels(
+        &description,
+        skew,
+        |secs| format!("{} seconds", secs),
+        &clock_skew_levels,
+    );
+    results.push(CheckResult::metric(
+        "clock_skew",
+        skew as f64,
+        Some('s'),
+        clock_skew_levels.map(UpperLevels::into),
+        Some(0.),
+        None,
+    ));
+    results
+}
+
+// TODO(au): Tests
+fn check_certificate(
+    tls_info: Option<TlsInfo>,
+    certificate_levels: Option<LowerLevels<u64>>,
+    disable_certificate_verification: bool,
+) -> Vec<Option<CheckResult>> {
+    if disable_certificate_verification {
+        return vec![CheckResult::details(
+            State::Ok,
+            "Server certificate validity: ignored",
+        )];
+    }
+
+    let Some(cert) = tls_info.as_ref().and_then(|t| t.peer_certificate()) else {
+        // If the outer tlsinfo is None, we didn't fetch it -> OK
+        // If the inner peer cert is None, there is no certificate and we're talking plain HTTP.
+        // Otherwise the TLS handshake would already have failed.
+        return vec![];
+    };
+
+    let Ok((_, cert)) = X509Certificate::from_der(cert) else {
+        return notice(State::Unknown, "Unable to parse server certificate");
+    };
+    let Some(validity) = cert.validity().time_to_expiration() else {
+        return notice(State::Crit, "Invalid server certificate");
+    };
+
+    check_lower_levels(
+        "Server certificate validity",
+        validity.whole_days().max(0).unsigned_abs(),
+        |days| format!("{} days", days),
+        &certificate_levels,
+    )
+}
+
+fn check_handshake(
+    handshake: Option<anyhow::Result<Handshake>>,
+    weak_cipher_state: State,
+    missing_stapling_state: Option<State>,
+) -> Vec<Option<CheckResult>> {
+    let handshake = match handshake {
+        None => return vec![],
+        Some(Err(err)) => return notice(State::Warn, &format!("TLS probe unavailable: {}", err)),
+        Some(Ok(handshake)) => handshake,
+    };
+
+    let mut ret = vec![CheckResult::details(
+        State::Ok,
+        &format!("TLS probe version: {}", handshake.version),
+    )];
+    let cipher = format!(
+        "TLS probe cipher suite: {} ({} bits)",
+        handshake.cipher, handshake.cipher_bits
+    );
+    ret.extend(if handshake.weak_cipher() {
+        notice(weak_cipher_state, &format!("{}, considered weak", cipher))
+    } else {
+        notice(State::Ok, &cipher)
+    });
+    ret.push(CheckResult::details(
+        State::Ok,
+        &format!(
+            "TLS probe ALPN protocol: {}",
+            handshake.alpn.as_deref().unwrap_or("none")
+        ),
+    ));
+    ret.extend(match handshake.ocsp_stapling {
+        OcspStapling::Good => notice(State::Ok, "TLS probe OCSP stapling: certificate good"),
+        OcspStapling::Revoked => {
+            notice(State::Crit, "TLS probe OCSP stapling: certificate revoked")
+        }
+        OcspStapling::Unknown => notice(
+            State::Warn,
+            "TLS probe OCSP stapling: certificate status unknown",
+        ),
+        OcspStapling::Missing => notice(
+            missing_stapling_state.unwrap_or(State::Ok),
+            "TLS probe OCSP stapling: no response stapled",
+        ),
+        OcspStapling::Invalid(reason) => notice(
+            State::Crit,
+            &format!("TLS probe OCSP stapling: invalid response ({})", reason),
+        ),
+    });
+    ret
+}
+
+fn check_user_agent(user_agent: String) -> Vec<Option<CheckResult>> {
+    vec![CheckResult::details(
+        State::Ok,
+        &format!("User agent: {}", user_agent),
+    )]
+}
+
+fn render_seconds_with_ms(val: &f64) -> String {
+    // Format to three digits to get a sense of milliseconds,
+    // but crop unnecessary trailing zeros/decimal point
+    format!(
+        "{} seconds",
+        format!("{:.3}", val)
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+    )
+}
+
+#[cfg(test)]
+mod test_check_urls {
+
+    use super::*;
+    use reqwest::Url;
+
+    #[test]
+    fn test_no_redirect() {
+        assert_eq!(
+            check_urls(
+                Url::parse("https://foo.bar").unwrap(),
+                Url::parse("https://foo.bar/").unwrap(),
+                None,
+            ),
+            vec![CheckResult::details(
+                State::Ok,
+                "URL to test: https://foo.bar/"
+            ),]
+        )
+    }
+
+    #[test]
+    fn test_redirect() {
+        assert_eq!(
+            check_urls(
+                Url::parse("https://foo.bar").unwrap(),
+                Url::parse("https://foo.bar/baz").unwrap(),
+                None,
+            ),
+            vec![
+                CheckResult::details(State::Ok, "URL to test: https://foo.bar/"),
+                CheckResult::details(State::Ok, "Followed redirect to: https://foo.bar/baz"),
+            ]
+        )
+    }
+}
+
+#[cfg(test)]
+mod test_check_method {
+    use std::vec;
+
+    use super::*;
+    use reqwest::Method;
+
+    #[test]
+    fn test_ok() {
+        assert_eq!(
+            check_method(Method::POST),
+            vec![CheckResult::details(State::Ok, "Method: POST"),]
+        )
+    }
+}
+
+#[cfg(test)]
+mod test_check_version {
+    use std::vec;
+
+    use super::*;
+    use reqwest::Version;
+
+    #[test]
+    fn test_ok() {
+        assert_eq!(
+            check_version(Version::HTTP_11),
+            vec![
+                CheckResult::summary(State::Ok, "Version: HTTP/1.1"),
+                CheckResult::details(State::Ok, "Version: HTTP/1.1"),
+            ]
+        )
+    }
+}
+
+#[cfg(test)]
+mod test_check_status {
+    use std::vec;
+
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_success_unchecked() {
+        assert_eq!(
+            check_status(StatusCode::OK, vec![], &StateMapping::default()),
+            vec![
+                CheckResult::summary(State::Ok, "Status: 200 OK"),
+                CheckResult::details(State::Ok, "Status: 200 OK"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_client_error_unchecked() {
+        assert_eq!(
+            check_status(
+                StatusCode::EXPECTATION_FAILED,
+                vec![],
+                &StateMapping::default()
+            ),
+            vec![
+                CheckResult::summary(State::Warn, "Status: 417 Expectation Failed"),
+                CheckResult::details(State::Warn, "Status: 417 Expectation Failed"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_server_error_unchecked() {
+        assert_eq!(
+            check_status(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                vec![],
+                &StateMapping::default()
+            ),
+            vec![
+                CheckResult::summary(State::Crit, "Status: 500 Internal Server Error"),
+                CheckResult::details(State::Crit, "Status: 500 Internal Server Error"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_success_checked_ok() {
+        assert_eq!(
+            check_status(
+                StatusCode::OK,
+                vec![StatusCode::OK],
+                &StateMapping::default()
+            ),
+            vec![
+                CheckResult::summary(State::Ok, "Status: 200 OK"),
+                CheckResult::details(State::Ok, "Status: 200 OK"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_success_checked_not_ok() {
+        assert_eq!(
+            check_status(
+                StatusCode::OK,
+                vec![StatusCode::IM_USED],
+                &StateMapping::default()
+            ),
+            vec![
+                CheckResult::summary(State::Crit, "Status: 200 OK (expected 226 IM Used)"),
+                CheckResult::details(State::Crit, "Status: 200 OK (expected 226 IM Used)"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_success_checked_multiple_not_ok() {
+        assert_eq!(
+            check_status(
+                StatusCode::OK,
+                vec![StatusCode::ACCEPTED, StatusCode::IM_USED],
+                &StateMapping::default()
+            ),
+            vec![
+                CheckResult::summary(State::Crit, "Status: 200 OK (expected one of [202 226])"),
+                CheckResult::details(State::Crit, "Status: 200 OK (expected one of [202 226])"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_client_error_checked_ok() {
+        assert_eq!(
+            check_status(
+                StatusCode::IM_A_TEAPOT,
+                vec![StatusCode::IM_A_TEAPOT],
+                &StateMapping::default()
+            ),
+            vec![
+                CheckResult::summary(State::Ok, "Status: 418 I'm a teapot"),
+                CheckResult::details(State::Ok, "Status: 418 I'm a teapot"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_state_mapping() {
+        let mapping = StateMapping(vec![
+            (Outcome::Status(400, 499), State::Crit),
+            (Outcome::Status(404, 404), State::Ok),
+        ]);
+        assert_eq!(
+            check_status(StatusCode::NOT_FOUND, vec![], &mapping),
+            vec![
+                CheckResult::summary(State::Ok, "Status: 404 Not Found"),
+                CheckResult::details(State::Ok, "Status: 404 Not Found"),
+            ]
+        );
+        assert_eq!(
+            check_status(StatusCode::GONE, vec![], &mapping),
+            vec![
+                CheckResult::summary(State::Crit, "Status: 410 Gone"),
+                CheckResult::details(State::Crit, "Status: 410 Gone"),
+            ]
+        );
+        assert_eq!(
+            check_status(StatusCode::OK, vec![StatusCode::OK], &mapping),
+            vec![
+                CheckResult::summary(State::Ok, "Status: 200 OK"),
+                CheckResult::details(State::Ok, "Status: 200 OK"),
+            ]
+        );
+    }
+    #[test]
+    fn test_status_code_precedence() {
+        let mapping = StateMapping(vec![
+            (Outcome::Status(200, 299), State::Warn),
+            (Outcome::Status(404, 404), State::Ok),
+        ]);
+        assert_eq!(
+            check_status(StatusCode::OK, vec![StatusCode::OK], &mapping),
+            vec![
+                CheckResult::summary(State::Ok, "Status: 200 OK"),
+                CheckResult::details(State::Ok, "Status: 200 OK"),
+            ]
+        );
+        assert_eq!(
+            check_status(StatusCode::NOT_FOUND, vec![StatusCode::OK], &mapping),
+            vec![
+                CheckResult::summary(State::Crit, "Status: 404 Not Found (expected 200 OK)"),
+                CheckResult::details(State::Crit, "Status: 404 Not Found (expected 200 OK)"),
+            ]
+        );
+        assert_eq!(
+            check_status(StatusCode::OK, vec![], &mapping),
+            vec![
+                CheckResult::summary(State::Warn, "Status: 200 OK"),
+                CheckResult::details(State::Warn, "Status: 200 OK"),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_outcome {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Outcome::from_str("timeout").unwrap(), Outcome::Timeout);
+        assert_eq!(Outcome::from_str("body_match").unwrap(), Outcome::BodyMatch);
+        assert_eq!(Outcome::from_str("graphql").unwrap(), Outcome::GraphQl);
+        assert_eq!(Outcome::from_str("404").unwrap(), Outcome::Status(404, 404));
+        assert_eq!(Outcome::from_str("5xx").unwrap(), Outcome::Status(500, 599));
+        assert_eq!(
+            Outcome::from_str("500-504").unwrap(),
+            Outcome::Status(500, 504)
+        );
+        assert!(Outcome::from_str("6xx").is_err());
+        assert!(Outcome::from_str("99").is_err());
+        assert!(Outcome::from_str("500-600").is_err());
+        assert!(Outcome::from_str("refused").is_err());
+    }
+
+    #[test]
+    fn test_last_match_wins() {
+        let mapping = StateMapping(vec![
+            (Outcome::Timeout, State::Warn),
+            (Outcome::Connect, State::Unknown),
+            (Outcome::Timeout, State::Ok),
+        ]);
+        assert_eq!(mapping.state(Outcome::Timeout, State::Crit), State::Ok);
+        assert_eq!(mapping.state(Outcome::Tls, State::Crit), State::Crit);
+    }
+}
+
+#[cfg(test)]
+mod test_check_redirect {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_no_redirect() {
+        assert!(check_redirect(
+            StatusCode::OK,
+            OnRedirect::Critical,
+            Some(Url::parse("https://foo.bar").unwrap())
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_redirect_ok() {
+        assert_eq!(
+            check_redirect(
+                StatusCode::MOVED_PERMANENTLY,
+                OnRedirect::Ok,
+                Some(Url::parse("https://foo.bar").unwrap())
+            ),
+            vec![CheckResult::details(
+                State::Ok,
+                "Stopped on redirect to: https://foo.bar/"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_redirect_warn() {
+        assert_eq!(
+            check_redirect(
+                StatusCode::MOVED_PERMANENTLY,
+                OnRedirect::Warning,
+                Some(Url::parse("https://foo.bar").unwrap())
+            ),
+            vec![
+                CheckResult::summary(State::Warn, "Stopped on redirect to: https://foo.bar/"),
+                CheckResult::details(State::Warn, "Stopped on redirect to: https://foo.bar/"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redirect_crit() {
+        assert_eq!(
+            check_redirect(
+                StatusCode::MOVED_PERMANENTLY,
+                OnRedirect::Critical,
+                Some(Url::parse("https://foo.bar").unwrap())
+            ),
+            vec![
+                CheckResult::summary(State::Crit, "Stopped on redirect to: https://foo.bar/"),
+                CheckResult::details(State::Crit, "Stopped on redirect to: https://foo.bar/"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redirect_sticky() {
+        assert_eq!(
+            check_redirect(
+                StatusCode::MOVED_PERMANENTLY,
+                OnRedirect::Sticky,
+                Some(Url::parse("https://foo.bar").unwrap())
+            ),
+            vec![
+                CheckResult::summary(
+                    State::Warn,
+                    "Stopped on redirect to: https://foo.bar/ (changed IP)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Stopped on redirect to: https://foo.bar/ (changed IP)"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redirect_stickyport() {
+        assert_eq!(
+            check_redirect(
+                StatusCode::MOVED_PERMANENTLY,
+                OnRedirect::Stickyport,
+                Some(Url::parse("https://foo.bar").unwrap())
+            ),
+            vec![
+                CheckResult::summary(
+                    State::Warn,
+                    "Stopped on redirect to: https://foo.bar/ (changed IP/port)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Stopped on redirect to: https://foo.bar/ (changed IP/port)"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redirect_not_handled() {
+        // This can happen when hitting "max_redirs", but is handled by check_reqwest_error
+        assert!(check_redirect(
+            StatusCode::MOVED_PERMANENTLY,
+            OnRedirect::Follow,
+            Some(Url::parse("https://foo.bar").unwrap())
+        )
+        .is_empty());
+    }
+}
+
+#[cfg(test)]
+mod test_check_headers {
+    use reqwest::header::HeaderName;
+
+    use super::*;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_strings_not_found() {
+        assert_eq!(
+            check_headers(
+                &(&HashMap::from([
+                    ("some_key1".to_string(), "some_value1".to_string()),
+                    ("some_key2".to_string(), "some_value2".to_string()),
+                    ("some_key3".to_string(), "some_value3".to_string()),
+                ]))
+                    .try_into()
+                    .unwrap(),
+                vec![
+                    (
+                        TextMatcher::Exact("some_key1".to_string()),
+                        TextMatcher::Exact("value1".to_string())
+                    ),
+                    (
+                        TextMatcher::Exact(String::new()),
+                        TextMatcher::Exact("value".to_string())
+                    ),
+                    (
+                        TextMatcher::Exact("some_key3".to_string()),
+                        TextMatcher::Exact(String::new())
+                    ),
+                ],
+                State::Warn
+            ),
+            vec![
+                CheckResult::summary(
+                    State::Warn,
+                    "Expected HTTP header: some_key1:value1 (not found)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Expected HTTP header: some_key1:value1 (not found)"
+                ),
+                CheckResult::summary(State::Warn, "Expected HTTP header: :value (not found)"),
+                CheckResult::details(State::Warn, "Expected HTTP header: :value (not found)"),
+                CheckResult::summary(State::Warn, "Expected HTTP header: some_key3: (not found)"),
+                CheckResult::details(State::Warn, "Expected HTTP header: some_key3: (not found)"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_strings_not_all_found() {
+        assert_eq!(
+            check_headers(
+                &(&HashMap::from([
+                    ("some_key1".to_string(), "some_value1".to_string()),
+                    ("some_key2".to_string(), "some_value2".to_string()),
+                    ("some_key3".to_string(), "some_value3".to_string()),
+                ]))
+                    .try_into()
+                    .unwrap(),
+                vec![
+                    (
+                        TextMatcher::Exact("some_key1".to_string()),
+                        TextMatcher::Exact("some_value1".to_string())
+                    ),
+                    (
+                        TextMatcher::Exact(String::new()),
+                        TextMatcher::Exact("value".to_string())
+                    ),
+                ],
+                State::Warn
+            ),
+            vec![
+                CheckResult::details(
+                    State::Ok,
+                    "Expected HTTP header: some_key1:some_value1 (found)"
+                ),
+                CheckResult::summary(State::Warn, "Expected HTTP header: :value (not found)"),
+                CheckResult::details(State::Warn, "Expected HTTP header: :value (not found)"),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_strings_all_found() {
+        assert_eq!(
+            check_headers(
+                &(&HashMap::from([
+                    ("some_key1".to_string(), "some_value1".to_string()),
+                    ("some_key2".to_string(), "some_value2".to_string()),
+                    ("some_key3".to_string(), "some_value3".to_string()),
+                ]))
+                    .try_into()
+                    .unwrap(),
+                vec![
+                    (
+                        TextMatcher::Exact("some_key1".to_string()),
+                        TextMatcher::Exact("some_value1".to_string())
+                    ),
+                    (
+                        TextMatcher::Exact("some_key3".to_string()),
+                        TextMatcher::Exact("some_value3".to_string())
+                    ),
+                ],
+                State::Warn
+            ),
+            vec![
+                CheckResult::details(
+                    State::Ok,
+                    "Expected HTTP header: some_key1:some_value1 (found)"
+                ),
+                CheckResult::details(
+                    State::Ok,
+                    "Expected HTTP header: some_key3:some_value3 (found)"
+                ),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_strings_mixed_not_found() {
+        assert_eq!(
+            check_headers(
+                &(&HashMap::from([
+                    ("some_key1".to_string(), "some_value1".to_string()),
+                    ("some_key2".to_string(), "some_value2".to_string()),
+                    ("some_key3".to_string(), "some_value3".to_string()),
+                ]))
+                    .try_into()
+                    .unwrap(),
+                vec![(
+                    TextMatcher::Exact("some_key1".to_string()),
+                    TextMatcher::Exact("some_value2".to_string())
+                ),],
+                State::Warn
+            ),
+            vec![
+                CheckResult::summary(
+                    State::Warn,
+                    "Expected HTTP header: some_key1:some_value2 (not found)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Expected HTTP header: some_key1:some_value2 (not found)"
+                ),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_impossible_non_latin1() {
+        assert_eq!(
+            check_headers(
+                &(&HashMap::from([("some_key1".to_string(), "ßome_value1".to_string()),]))
+                    .try_into()
+                    .unwrap(),
+                vec![(
+                    TextMatcher::Exact("some_key1".to_string()),
+                    TextMatcher::Exact("ßome_value1".to_string())
+                ),],
+                State::Warn
+            ),
+            vec![
+                CheckResult::summary(
+                    State::Warn,
+                    "Expected HTTP header: some_key1:ßome_value1 (not found)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Expected HTTP header: some_key1:ßome_value1 (not found)"
+                ),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_decode_latin1() {
+        let mut header_map = HeaderMap::new();
+        header_map.append(
+            HeaderName::from_str("some_key").unwrap(),
+            HeaderValue::from_bytes(b"\xF6\xE4\xFC").unwrap(),
+        );
+        assert_eq!(
+            check_headers(
+                &header_map,
+                vec![(
+                    TextMatcher::Exact("some_key".to_string()),
+                    TextMatcher::Exact("öäü".to_string())
+                ),],
+                State::Warn
+            ),
+            vec![CheckResult::details(
+                State::Ok,
+                "Expected HTTP header: some_key:öäü (found)"
+            )]
+        )
+    }
+
+    #[test]
+    fn test_regex() {
+        assert_eq!(
+            check_headers(
+                &(&HashMap::from([
+                    ("some_key1".to_string(), "some_value1".to_string()),
+                    ("some_key2".to_string(), "some_value2".to_string()),
+                    ("some_key3".to_string(), "some_value3".to_string()),
+                ]))
+                    .try_into()
+                    .unwrap(),
+                vec![
+                    (
+                        TextMatcher::from_regex(Regex::new("s.*y[0-9]").unwrap(), true),
+                        TextMatcher::from_regex(Regex::new("s[a-z]+_*value1").unwrap(), true)
+                    ),
+                    (
+                        TextMatcher::from_regex(Regex::new("foobar").unwrap(), true),
+                        TextMatcher::from_regex(Regex::new("baz").unwrap(), true)
+                    ),
+                ],
+                State::Warn
+            ),
+            vec![
+                CheckResult::details(
+                    State::Ok,
+                    "Expected regex in HTTP headers: s.*y[0-9]:s[a-z]+_*value1 (matched)"
+                ),
+                CheckResult::summary(
+                    State::Warn,
+                    "Expected regex in HTTP headers: foobar:baz (not matched)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Expected regex in HTTP headers: foobar:baz (not matched)"
+                ),
+            ]
+        )
+    }
+}
+
+#[cfg(test)]
+mod test_check_body {
+    use super::*;
+    use std::error::Error;
+    use std::fmt::{Display, Formatter, Result};
+
+    #[derive(Debug)]
+    struct DummyError;
+
+    impl Error for DummyError {}
+
+    impl Display for DummyError {
+        fn fmt(&self, f: &mut Formatter) -> Result {
+            write!(f, "Error")
+        }
+    }
+
+    #[test]
+    fn test_no_body() {
+        assert_eq!(check_body::<DummyError>(None), (None, vec![]));
+    }
+
+    #[test]
+    fn test_error_body() {
+        assert_eq!(
+            check_body(Some(Err(DummyError {}))),
+            (
+                None,
+                vec![
+                    CheckResult::summary(State::Crit, "Error fetching the response body"),
+                    CheckResult::details(State::Crit, "Error fetching the response body"),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_all_ok() {
+        assert_eq!(
+            check_body::<DummyError>(Some(Ok(Body {
+                text: "foobär".to_string(),
+                length: 7
+            }))),
+            (
+                Some(Body {
+                    text: "foobär".to_string(),
+                    length: 7
+                }),
+                vec![]
+            )
+        );
+    }
+}
+
 #[cfg(test)]
-mod test_check_urls {
-
+mod test_check_page_size {
     use super::*;
-    use reqwest::Url;
+    use crate::http::Body;
 
     #[test]
-    fn test_no_redirect() {
+    fn test_size_lower_out_of_bounds() {
         assert_eq!(
-            check_urls(
-                Url::parse("https://foo.bar").unwrap(),
-                Url::parse("https://foo.bar/").unwrap(),
-                None,
+            check_page_size(
+                Some(&Body {
+                    text: String::new(),
+                    length: 42,
+                }),
+                Some(Bounds::lower(56)),
             ),
-            vec![CheckResult::details(
-                State::Ok,
-                "URL to test: https://foo.bar/"
-            ),]
-        )
+            vec![
+                CheckResult::summary(State::Warn, "Page size: 42 Bytes (warn below 56 Bytes)"),
+                CheckResult::details(State::Warn, "Page size: 42 Bytes (warn below 56 Bytes)"),
+                CheckResult::metric("response_size", 42., Some('B'), None, Some(0.), None)
+            ]
+        );
     }
 
     #[test]
-    fn test_redirect() {
+    fn test_size_lower_and_higher_too_low() {
         assert_eq!(
-            check_urls(
-                Url::parse("https://foo.bar").unwrap(),
-                Url::parse("https://foo.bar/baz").unwrap(),
-                None,
+            check_page_size(
+                Some(&Body {
+                    text: String::new(),
+                    length: 42,
+                }),
+                Some(Bounds::lower_upper(56, 100)),
             ),
             vec![
-                CheckResult::details(State::Ok, "URL to test: https://foo.bar/"),
-                CheckResult::details(State::Ok, "Followed redirect to: https://foo.bar/baz"),
+                CheckResult::summary(
+                    State::Warn,
+                    "Page size: 42 Bytes (warn below/above 56 Bytes/100 Bytes)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Page size: 42 Bytes (warn below/above 56 Bytes/100 Bytes)"
+                ),
+                CheckResult::metric("response_size", 42., Some('B'), None, Some(0.), None)
             ]
-        )
+        );
     }
-}
-
-#[cfg(test)]
-mod test_check_method {
-    use std::vec;
-
-    use super::*;
-    use reqwest::Method;
 
     #[test]
-    fn test_ok() {
+    fn test_size_lower_and_higher_too_high() {
         assert_eq!(
-            check_method(Method::POST),
-            vec![CheckResult::details(State::Ok, "Method: POST"),]
-        )
+            check_page_size(
+                Some(&Body {
+                    text: String::new(),
+                    length: 142,
+                }),
+                Some(Bounds::lower_upper(56, 100)),
+            ),
+            vec![
+                CheckResult::summary(
+                    State::Warn,
+                    "Page size: 142 Bytes (warn below/above 56 Bytes/100 Bytes)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Page size: 142 Bytes (warn below/above 56 Bytes/100 Bytes)"
+                ),
+                CheckResult::metric("response_size", 142., Some('B'), None, Some(0.), None)
+            ]
+        );
     }
 }
 
 #[cfg(test)]
-mod test_check_version {
-    use std::vec;
-
+mod test_check_body_matching {
     use super::*;
-    use reqwest::Version;
 
-    #[test]
-    fn test_ok() {
-        assert_eq!(
-            check_version(Version::HTTP_11),
-            vec![
-                CheckResult::summary(State::Ok, "Version: HTTP/1.1"),
-                CheckResult::details(State::Ok, "Version: HTTP/1.1"),
-            ]
-        )
+    fn test_body(test_string: &str) -> Option<Body> {
+        Some(Body {
+            text: test_string.to_owned(),
+            length: 0,
+        })
     }
-}
 
-#[cfg(test)]
-mod test_check_status {
-    use std::vec;
+    #[test]
+    fn test_no_matcher() {
+        assert!(check_body_matching(test_body("foobar").as_ref(), vec![], State::Warn).is_empty());
+    }
 
-    use super::*;
-    use reqwest::StatusCode;
+    #[test]
+    fn test_string_ok() {
+        assert_eq!(
+            check_body_matching(
+                test_body("foobar").as_ref(),
+                vec![TextMatcher::Contains("bar".to_string())],
+                State::Warn
+            ),
+            vec![CheckResult::details(
+                State::Ok,
+                "Expected string in body: bar (found)"
+            ),]
+        );
+    }
 
     #[test]
-    fn test_success_unchecked() {
+    fn test_string_not_found() {
         assert_eq!(
-            check_status(StatusCode::OK, vec![]),
+            check_body_matching(
+                test_body("foobär").as_ref(),
+                vec![TextMatcher::Contains("bar".to_string())],
+                State::Warn
+            ),
             vec![
-                CheckResult::summary(State::Ok, "Status: 200 OK"),
-                CheckResult::details(State::Ok, "Status: 200 OK"),
+                CheckResult::summary(State::Warn, "Expected string in body: bar (not found)"),
+                CheckResult::details(State::Warn, "Expected string in body: bar (not found)"),
             ]
-        )
+        );
     }
 
     #[test]
-    fn test_client_error_unchecked() {
+    fn test_multiple_strings_not_found() {
         assert_eq!(
-            check_status(StatusCode::EXPECTATION_FAILED, vec![]),
+            check_body_matching(
+                test_body("foobär").as_ref(),
+                vec![
+                    TextMatcher::Contains("bar".to_string()),
+                    TextMatcher::Contains("baz".to_string())
+                ],
+                State::Warn
+            ),
             vec![
-                CheckResult::summary(State::Warn, "Status: 417 Expectation Failed"),
-                CheckResult::details(State::Warn, "Status: 417 Expectation Failed"),
+                CheckResult::summary(State::Warn, "Expected string in body: bar (not found)"),
+                CheckResult::details(State::Warn, "Expected string in body: bar (not found)"),
+                CheckResult::summary(State::Warn, "Expected string in body: baz (not found)"),
+                CheckResult::details(State::Warn, "Expected string in body: baz (not found)"),
             ]
-        )
+        );
     }
 
     #[test]
-    fn test_server_error_unchecked() {
+    fn test_regex_ok() {
         assert_eq!(
-            check_status(StatusCode::INTERNAL_SERVER_ERROR, vec![]),
-            vec![
-                CheckResult::summary(State::Crit, "Status: 500 Internal Server Error"),
-                CheckResult::details(State::Crit, "Status: 500 Internal Server Error"),
-            ]
-        )
+            check_body_matching(
+                test_body("foobar").as_ref(),
+                vec![TextMatcher::from_regex(Regex::new("f.*r").unwrap(), true)],
+                State::Warn
+            ),
+            vec![CheckResult::details(
+                State::Ok,
+                "Expected regex in body: f.*r (matched)"
+            ),]
+        );
     }
 
     #[test]
-    fn test_success_checked_ok() {
+    fn test_regex_not_ok() {
         assert_eq!(
-            check_status(StatusCode::OK, vec![StatusCode::OK]),
+            check_body_matching(
+                test_body("foobar").as_ref(),
+                vec![TextMatcher::from_regex(Regex::new("f.*z").unwrap(), true)],
+                State::Warn
+            ),
             vec![
-                CheckResult::summary(State::Ok, "Status: 200 OK"),
-                CheckResult::details(State::Ok, "Status: 200 OK"),
+                CheckResult::summary(State::Warn, "Expected regex in body: f.*z (not matched)"),
+                CheckResult::details(State::Warn, "Expected regex in body: f.*z (not matched)"),
             ]
-        )
+        );
     }
 
     #[test]
-    fn test_success_checked_not_ok() {
+    fn test_regex_inverse_ok() {
+        assert_eq!(
+            check_body_matching(
+                test_body("foobar").as_ref(),
+                vec![TextMatcher::from_regex(Regex::new("f.*z").unwrap(), false)],
+                State::Warn
+            ),
+            vec![CheckResult::details(
+                State::Ok,
+                "Not expected regex in body: f.*z (not matched)"
+            ),]
+        );
+    }
+
+    #[test]
+    fn test_regex_inverse_not_ok() {
         assert_eq!(
-            check_status(StatusCode::OK, vec![StatusCode::IM_USED]),
+            check_body_matching(
+                test_body("argl").as_ref(),
+                vec![TextMatcher::from_regex(Regex::new("argl").unwrap(), false)],
+                State::Warn
+            ),
             vec![
-                CheckResult::summary(State::Crit, "Status: 200 OK (expected 226 IM Used)"),
-                CheckResult::details(State::Crit, "Status: 200 OK (expected 226 IM Used)"),
+                CheckResult::summary(State::Warn, "Not expected regex in body: argl (matched)"),
+                CheckResult::details(State::Warn, "Not expected regex in body: argl (matched)")
             ]
-        )
+        );
     }
 
     #[test]
-    fn test_success_checked_multiple_not_ok() {
+    fn test_multiple_matchers_ok() {
         assert_eq!(
-            check_status(
-                StatusCode::OK,
-                vec![StatusCode::ACCEPTED, StatusCode::IM_USED]
+            check_body_matching(
+                test_body("foobar").as_ref(),
+                vec![
+                    TextMatcher::Contains("bar".to_string()),
+                    TextMatcher::Contains("foo".to_string())
+                ],
+                State::Warn
             ),
             vec![
-                CheckResult::summary(State::Crit, "Status: 200 OK (expected one of [202 226])"),
-                CheckResult::details(State::Crit, "Status: 200 OK (expected one of [202 226])"),
+                CheckResult::details(State::Ok, "Expected string in body: bar (found)"),
+                CheckResult::details(State::Ok, "Expected string in body: foo (found)"),
             ]
-        )
+        );
     }
 
     #[test]
-    fn test_client_error_checked_ok() {
+    fn test_mismatch_state() {
         assert_eq!(
-            check_status(StatusCode::IM_A_TEAPOT, vec![StatusCode::IM_A_TEAPOT]),
+            check_body_matching(
+                test_body("foobar").as_ref(),
+                vec![TextMatcher::Contains("baz".to_string())],
+                State::Crit
+            ),
             vec![
-                CheckResult::summary(State::Ok, "Status: 418 I'm a teapot"),
-                CheckResult::details(State::Ok, "Status: 418 I'm a teapot"),
+                CheckResult::summary(State::Crit, "Expected string in body: baz (not found)"),
+                CheckResult::details(State::Crit, "Expected string in body: baz (not found)"),
             ]
-        )
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_check_html {
+    use super::*;
+    use std::str::FromStr;
+
+    const PAGE: &str = "<html><head><title>Shop</title></head>\
+        <body><div id=\"cart\"><span class=\"count\">3 items</span></div></body></html>";
+
+    fn test_body(test_string: &str) -> Option<Body> {
+        Some(Body {
+            text: test_string.to_owned(),
+            length: 0,
+        })
     }
-}
 
-#[cfg(test)]
-mod test_check_redirect {
-    use super::*;
-    use reqwest::StatusCode;
+    fn selector(pattern: &str) -> CssSelector {
+        CssSelector::from_str(pattern).unwrap()
+    }
 
     #[test]
-    fn test_no_redirect() {
-        assert!(check_redirect(
-            StatusCode::OK,
-            OnRedirect::Critical,
-            Some(Url::parse("https://foo.bar").unwrap())
-        )
-        .is_empty());
+    fn test_no_matcher() {
+        assert!(check_html(test_body(PAGE).as_ref(), vec![], State::Warn).is_empty());
     }
 
     #[test]
-    fn test_redirect_ok() {
+    fn test_element_found() {
         assert_eq!(
-            check_redirect(
-                StatusCode::MOVED_PERMANENTLY,
-                OnRedirect::Ok,
-                Some(Url::parse("https://foo.bar").unwrap())
+            check_html(
+                test_body(PAGE).as_ref(),
+                vec![HtmlMatcher::Element(selector("#cart .count"))],
+                State::Warn
             ),
             vec![CheckResult::details(
                 State::Ok,
-                "Stopped on redirect to: https://foo.bar/"
+                "Expected HTML element: #cart .count (found 1)"
             )]
         );
     }
 
     #[test]
-    fn test_redirect_warn() {
+    fn test_element_not_found() {
         assert_eq!(
-            check_redirect(
-                StatusCode::MOVED_PERMANENTLY,
-                OnRedirect::Warning,
-                Some(Url::parse("https://foo.bar").unwrap())
+            check_html(
+                test_body(PAGE).as_ref(),
+                vec![HtmlMatcher::Element(selector("form.login"))],
+                State::Warn
             ),
             vec![
-                CheckResult::summary(State::Warn, "Stopped on redirect to: https://foo.bar/"),
-                CheckResult::details(State::Warn, "Stopped on redirect to: https://foo.bar/"),
+                CheckResult::summary(State::Warn, "Expected HTML element: form.login (not found)"),
+                CheckResult::details(State::Warn, "Expected HTML element: form.login (not found)"),
             ]
         );
     }
 
     #[test]
-    fn test_redirect_crit() {
+    fn test_title() {
         assert_eq!(
-            check_redirect(
-                StatusCode::MOVED_PERMANENTLY,
-                OnRedirect::Critical,
-                Some(Url::parse("https://foo.bar").unwrap())
+            check_html(
+                test_body(PAGE).as_ref(),
+                vec![
+                    HtmlMatcher::Title("Shop".to_string()),
+                    HtmlMatcher::Title("Login".to_string())
+                ],
+                State::Warn
             ),
             vec![
-                CheckResult::summary(State::Crit, "Stopped on redirect to: https://foo.bar/"),
-                CheckResult::details(State::Crit, "Stopped on redirect to: https://foo.bar/"),
+                CheckResult::details(State::Ok, "HTML title: Shop"),
+                CheckResult::summary(State::Warn, "HTML title: Shop (expected Login)"),
+                CheckResult::details(State::Warn, "HTML title: Shop (expected Login)"),
             ]
         );
     }
 
     #[test]
-    fn test_redirect_sticky() {
+    fn test_missing_title() {
         assert_eq!(
-            check_redirect(
-                StatusCode::MOVED_PERMANENTLY,
-                OnRedirect::Sticky,
-                Some(Url::parse("https://foo.bar").unwrap())
+            check_html(
+                test_body("<p>foo</p>").as_ref(),
+                vec![HtmlMatcher::Title("Shop".to_string())],
+                State::Warn
             ),
             vec![
-                CheckResult::summary(
-                    State::Warn,
-                    "Stopped on redirect to: https://foo.bar/ (changed IP)"
-                ),
-                CheckResult::details(
-                    State::Warn,
-                    "Stopped on redirect to: https://foo.bar/ (changed IP)"
-                ),
+                CheckResult::summary(State::Warn, "HTML title: none (expected Shop)"),
+                CheckResult::details(State::Warn, "HTML title: none (expected Shop)"),
             ]
         );
     }
 
     #[test]
-    fn test_redirect_stickyport() {
+    fn test_element_text() {
         assert_eq!(
-            check_redirect(
-                StatusCode::MOVED_PERMANENTLY,
-                OnRedirect::Stickyport,
-                Some(Url::parse("https://foo.bar").unwrap())
+            check_html(
+                test_body(PAGE).as_ref(),
+                vec![
+                    HtmlMatcher::ElementText(selector(".count"), "3 items".to_string()),
+                    HtmlMatcher::ElementText(selector("title"), "items".to_string()),
+                ],
+                State::Warn
             ),
             vec![
+                CheckResult::details(
+                    State::Ok,
+                    "Expected text in HTML element .count: 3 items (found)"
+                ),
                 CheckResult::summary(
                     State::Warn,
-                    "Stopped on redirect to: https://foo.bar/ (changed IP/port)"
+                    "Expected text in HTML element title: items (not found)"
                 ),
                 CheckResult::details(
                     State::Warn,
-                    "Stopped on redirect to: https://foo.bar/ (changed IP/port)"
+                    "Expected text in HTML element title: items (not found)"
                 ),
             ]
         );
     }
+}
+
+#[cfg(test)]
+mod test_check_prometheus_series {
+    use super::*;
+    use std::str::FromStr;
+
+    const EXPOSITION: &str = "# TYPE queue_length gauge
+queue_length{queue=\"mail\"} 12
+queue_length{queue=\"jobs\"} 250
+up 1
+";
+
+    fn test_body(test_string: &str) -> Option<Body> {
+        Some(Body {
+            text: test_string.to_owned(),
+            length: 0,
+        })
+    }
+
+    fn series_check(
+        selector: &str,
+        upper_levels: Option<UpperLevels<f64>>,
+        lower_levels: Option<LowerLevels<f64>>,
+    ) -> SeriesCheck {
+        SeriesCheck {
+            selector: SeriesSelector::from_str(selector).unwrap(),
+            upper_levels,
+            lower_levels,
+        }
+    }
 
     #[test]
-    fn test_redirect_not_handled() {
-        // This can happen when hitting "max_redirs", but is handled by check_reqwest_error
-        assert!(check_redirect(
-            StatusCode::MOVED_PERMANENTLY,
-            OnRedirect::Follow,
-            Some(Url::parse("https://foo.bar").unwrap())
+    fn test_no_series() {
+        assert!(check_prometheus_series(
+            test_body("no metrics").as_ref(),
+            vec![],
+            &StateMapping::default()
         )
         .is_empty());
     }
-}
 
-#[cfg(test)]
-mod test_check_headers {
-    use reqwest::header::HeaderName;
+    #[test]
+    fn test_unparsable() {
+        assert_eq!(
+            check_prometheus_series(
+                test_body("<html>").as_ref(),
+                vec![series_check("up", None, None)],
+                &StateMapping::default()
+            ),
+            vec![
+                CheckResult::summary(
+                    State::Crit,
+                    "Unable to parse metrics exposition: line 1: invalid metric name"
+                ),
+                CheckResult::details(
+                    State::Crit,
+                    "Unable to parse metrics exposition: line 1: invalid metric name"
+                ),
+            ]
+        );
+    }
 
-    use super::*;
-    use std::collections::HashMap;
-    use std::str::FromStr;
+    #[test]
+    fn test_state_mapping() {
+        assert_eq!(
+            check_prometheus_series(
+                test_body(EXPOSITION).as_ref(),
+                vec![series_check("missing", None, None)],
+                &StateMapping(vec![(Outcome::Series, State::Crit)])
+            ),
+            vec![
+                CheckResult::summary(State::Crit, "Series not found: missing"),
+                CheckResult::details(State::Crit, "Series not found: missing"),
+            ]
+        );
+    }
 
     #[test]
-    fn test_strings_not_found() {
+    fn test_not_found() {
         assert_eq!(
-            check_headers(
-                &(&HashMap::from([
-                    ("some_key1".to_string(), "some_value1".to_string()),
-                    ("some_key2".to_string(), "some_value2".to_string()),
-                    ("some_key3".to_string(), "some_value3".to_string()),
-                ]))
-                    .try_into()
-                    .unwrap(),
-                vec![
-                    (
-                        TextMatcher::Exact("some_key1".to_string()),
-                        TextMatcher::Exact("value1".to_string())
-                    ),
-                    (
-                        TextMatcher::Exact(String::new()),
-                        TextMatcher::Exact("value".to_string())
-                    ),
-                    (
-                        TextMatcher::Exact("some_key3".to_string()),
-                        TextMatcher::Exact(String::new())
-                    ),
-                ]
+            check_prometheus_series(
+                test_body(EXPOSITION).as_ref(),
+                vec![series_check(r#"queue_length{queue="print"}"#, None, None)],
+                &StateMapping::default()
             ),
             vec![
                 CheckResult::summary(
                     State::Warn,
-                    "Expected HTTP header: some_key1:value1 (not found)"
+                    r#"Series not found: queue_length{queue="print"}"#
                 ),
                 CheckResult::details(
                     State::Warn,
-                    "Expected HTTP header: some_key1:value1 (not found)"
+                    r#"Series not found: queue_length{queue="print"}"#
                 ),
-                CheckResult::summary(State::Warn, "Expected HTTP header: :value (not found)"),
-                CheckResult::details(State::Warn, "Expected HTTP header: :value (not found)"),
-                CheckResult::summary(State::Warn, "Expected HTTP header: some_key3: (not found)"),
-                CheckResult::details(State::Warn, "Expected HTTP header: some_key3: (not found)"),
             ]
-        )
+        );
     }
 
     #[test]
-    fn test_strings_not_all_found() {
+    fn test_upper_levels() {
         assert_eq!(
-            check_headers(
-                &(&HashMap::from([
-                    ("some_key1".to_string(), "some_value1".to_string()),
-                    ("some_key2".to_string(), "some_value2".to_string()),
-                    ("some_key3".to_string(), "some_value3".to_string()),
-                ]))
-                    .try_into()
-                    .unwrap(),
-                vec![
-                    (
-                        TextMatcher::Exact("some_key1".to_string()),
-                        TextMatcher::Exact("some_value1".to_string())
-                    ),
-                    (
-                        TextMatcher::Exact(String::new()),
-                        TextMatcher::Exact("value".to_string())
-                    ),
-                ]
+            check_prometheus_series(
+                test_body(EXPOSITION).as_ref(),
+                vec![series_check(
+                    "queue_length",
+                    Some(UpperLevels::warn_crit(100., 200.)),
+                    None
+                )],
+                &StateMapping::default()
             ),
             vec![
+                CheckResult::details(State::Ok, r#"Series queue_length{queue="mail"}: 12"#),
+                CheckResult::metric(
+                    "queue_length_queue_mail",
+                    12.,
+                    None,
+                    Some(UpperLevels::warn_crit(100., 200.)),
+                    None,
+                    None
+                ),
+                CheckResult::summary(
+                    State::Crit,
+                    r#"Series queue_length{queue="jobs"}: 250 (warn/crit at 100/200)"#
+                ),
                 CheckResult::details(
-                    State::Ok,
-                    "Expected HTTP header: some_key1:some_value1 (found)"
+                    State::Crit,
+                    r#"Series queue_length{queue="jobs"}: 250 (warn/crit at 100/200)"#
+                ),
+                CheckResult::metric(
+                    "queue_length_queue_jobs",
+                    250.,
+                    None,
+                    Some(UpperLevels::warn_crit(100., 200.)),
+                    None,
+                    None
                 ),
-                CheckResult::summary(State::Warn, "Expected HTTP header: :value (not found)"),
-                CheckResult::details(State::Warn, "Expected HTTP header: :value (not found)"),
             ]
-        )
+        );
     }
 
     #[test]
-    fn test_strings_all_found() {
+    fn test_lower_levels() {
         assert_eq!(
-            check_headers(
-                &(&HashMap::from([
-                    ("some_key1".to_string(), "some_value1".to_string()),
-                    ("some_key2".to_string(), "some_value2".to_string()),
-                    ("some_key3".to_string(), "some_value3".to_string()),
-                ]))
-                    .try_into()
-                    .unwrap(),
-                vec![
-                    (
-                        TextMatcher::Exact("some_key1".to_string()),
-                        TextMatcher::Exact("some_value1".to_string())
-                    ),
-                    (
-                        TextMatcher::Exact("some_key3".to_string()),
-                        TextMatcher::Exact("some_value3".to_string())
-                    ),
-                ]
+            check_prometheus_series(
+                test_body(EXPOSITION).as_ref(),
+                vec![series_check(
+                    "up",
+                    None,
+                    Some(LowerLevels::warn_crit(2., 1.))
+                )],
+                &StateMapping::default()
+            ),
+            vec![
+                CheckResult::summary(State::Warn, "Series up: 1 (warn/crit below 2/1)"),
+                CheckResult::details(State::Warn, "Series up: 1 (warn/crit below 2/1)"),
+                CheckResult::metric("up", 1., None, None, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_both_levels_report_the_worse() {
+        let upper_levels = Some(UpperLevels::warn_crit(100., 200.));
+        assert_eq!(
+            check_prometheus_series(
+                test_body(EXPOSITION).as_ref(),
+                vec![series_check(
+                    r#"queue_length{queue="jobs"}"#,
+                    upper_levels.clone(),
+                    Some(LowerLevels::warn(300.))
+                )],
+                &StateMapping::default()
             ),
             vec![
+                CheckResult::summary(
+                    State::Crit,
+                    r#"Series queue_length{queue="jobs"}: 250 (warn/crit at 100/200)"#
+                ),
                 CheckResult::details(
-                    State::Ok,
-                    "Expected HTTP header: some_key1:some_value1 (found)"
+                    State::Crit,
+                    r#"Series queue_length{queue="jobs"}: 250 (warn/crit at 100/200)"#
+                ),
+                CheckResult::metric(
+                    "queue_length_queue_jobs",
+                    250.,
+                    None,
+                    upper_levels,
+                    None,
+                    None
+                ),
+            ]
+        );
+        assert_eq!(
+            check_prometheus_series(
+                test_body(EXPOSITION).as_ref(),
+                vec![series_check(
+                    r#"queue_length{queue="mail"}"#,
+                    Some(UpperLevels::warn(100.)),
+                    Some(LowerLevels::warn_crit(20., 15.))
+                )],
+                &StateMapping::default()
+            )[..2],
+            [
+                CheckResult::summary(
+                    State::Crit,
+                    r#"Series queue_length{queue="mail"}: 12 (warn/crit below 20/15)"#
                 ),
                 CheckResult::details(
-                    State::Ok,
-                    "Expected HTTP header: some_key3:some_value3 (found)"
+                    State::Crit,
+                    r#"Series queue_length{queue="mail"}: 12 (warn/crit below 20/15)"#
                 ),
             ]
-        )
+        );
     }
+}
 
-    #[test]
-    fn test_strings_mixed_not_found() {
-        assert_eq!(
-            check_headers(
-                &(&HashMap::from([
-                    ("some_key1".to_string(), "some_value1".to_string()),
-                    ("some_key2".to_string(), "some_value2".to_string()),
-                    ("some_key3".to_string(), "some_value3".to_string()),
-                ]))
-                    .try_into()
-                    .unwrap(),
-                vec![(
-                    TextMatcher::Exact("some_key1".to_string()),
-                    TextMatcher::Exact("some_value2".to_string())
-                ),]
-            ),
+#[cfg(test)]
+mod test_check_json_schema {
+    use super::*;
+    use serde_json::json;
+
+    fn test_body(test_string: &str) -> Option<Body> {
+        Some(Body {
+            text: test_string.to_owned(),
+            length: 0,
+        })
+    }
+
+    fn schema() -> Option<(JSONSchema, State)> {
+        Some((
+            JSONSchema::compile(&json!({
+                "type": "object",
+                "properties": {
+                    "id": {"type": "integer"},
+                    "tags": {"type": "array", "items": {"type": "string"}}
+                },
+                "required": ["id"]
+            }))
+            .unwrap(),
+            State::Crit,
+        ))
+    }
+
+    #[test]
+    fn test_no_schema() {
+        assert!(check_json_schema(test_body("{}").as_ref(), None).is_empty());
+    }
+
+    #[test]
+    fn test_valid() {
+        assert_eq!(
+            check_json_schema(test_body(r#"{"id": 1, "tags": ["a"]}"#).as_ref(), schema()),
+            vec![CheckResult::details(State::Ok, "JSON schema: valid")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        assert_eq!(
+            check_json_schema(test_body("<html>").as_ref(), schema()),
             vec![
                 CheckResult::summary(
-                    State::Warn,
-                    "Expected HTTP header: some_key1:some_value2 (not found)"
+                    State::Crit,
+                    "Response body is not valid JSON: expected value at line 1 column 1"
                 ),
                 CheckResult::details(
-                    State::Warn,
-                    "Expected HTTP header: some_key1:some_value2 (not found)"
+                    State::Crit,
+                    "Response body is not valid JSON: expected value at line 1 column 1"
                 ),
             ]
-        )
+        );
     }
 
     #[test]
-    fn test_impossible_non_latin1() {
+    fn test_violations() {
         assert_eq!(
-            check_headers(
-                &(&HashMap::from([("some_key1".to_string(), "ßome_value1".to_string()),]))
-                    .try_into()
-                    .unwrap(),
-                vec![(
-                    TextMatcher::Exact("some_key1".to_string()),
-                    TextMatcher::Exact("ßome_value1".to_string())
-                ),]
-            ),
+            check_json_schema(test_body(r#"{"tags": ["a", 2]}"#).as_ref(), schema()),
             vec![
-                CheckResult::summary(
-                    State::Warn,
-                    "Expected HTTP header: some_key1:ßome_value1 (not found)"
+                CheckResult::summary(State::Crit, "JSON schema: 2 violation(s)"),
+                CheckResult::details(State::Crit, "JSON schema: 2 violation(s)"),
+                CheckResult::details(
+                    State::Crit,
+                    "JSON schema violation at /tags/1: 2 is not of type \"string\""
                 ),
                 CheckResult::details(
-                    State::Warn,
-                    "Expected HTTP header: some_key1:ßome_value1 (not found)"
+                    State::Crit,
+                    "JSON schema violation at /: \"id\" is a required property"
                 ),
             ]
-        )
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_check_graphql {
+    use super::*;
+
+    const RESPONSE: &str = r#"{
+        "errors": [
+            {"message": "Not allowed", "path": ["user", "email"]},
+            {"message": "Rate limited"}
+        ],
+        "data": {"user": {"name": "alice", "age": 42, "email": null}}
+    }"#;
+
+    fn test_body(test_string: &str) -> Option<Body> {
+        Some(Body {
+            text: test_string.to_owned(),
+            length: 0,
+        })
+    }
+
+    fn graphql(expectations: &[&str]) -> Option<GraphQlCheck> {
+        Some(GraphQlCheck {
+            errors_state: State::Crit,
+            expectations: expectations
+                .iter()
+                .map(|e| DataExpectation::from_str(e).unwrap())
+                .collect(),
+        })
     }
 
     #[test]
-    fn test_decode_latin1() {
-        let mut header_map = HeaderMap::new();
-        header_map.append(
-            HeaderName::from_str("some_key").unwrap(),
-            HeaderValue::from_bytes(b"\xF6\xE4\xFC").unwrap(),
+    fn test_no_graphql() {
+        assert!(
+            check_graphql(test_body(RESPONSE).as_ref(), None, &StateMapping::default()).is_empty()
         );
+    }
+
+    #[test]
+    fn test_no_errors() {
         assert_eq!(
-            check_headers(
-                &header_map,
-                vec![(
-                    TextMatcher::Exact("some_key".to_string()),
-                    TextMatcher::Exact("öäü".to_string())
-                ),]
+            check_graphql(
+                test_body(r#"{"data": {}}"#).as_ref(),
+                graphql(&[]),
+                &StateMapping::default()
             ),
-            vec![CheckResult::details(
-                State::Ok,
-                "Expected HTTP header: some_key:öäü (found)"
-            )]
-        )
+            vec![CheckResult::details(State::Ok, "GraphQL errors: none")]
+        );
+        assert_eq!(
+            check_graphql(
+                test_body(r#"{"errors": [], "data": {}}"#).as_ref(),
+                graphql(&[]),
+                &StateMapping::default()
+            ),
+            vec![CheckResult::details(State::Ok, "GraphQL errors: none")]
+        );
     }
 
     #[test]
-    fn test_regex() {
+    fn test_errors() {
         assert_eq!(
-            check_headers(
-                &(&HashMap::from([
-                    ("some_key1".to_string(), "some_value1".to_string()),
-                    ("some_key2".to_string(), "some_value2".to_string()),
-                    ("some_key3".to_string(), "some_value3".to_string()),
-                ]))
-                    .try_into()
-                    .unwrap(),
-                vec![
-                    (
-                        TextMatcher::from_regex(Regex::new("s.*y[0-9]").unwrap(), true),
-                        TextMatcher::from_regex(Regex::new("s[a-z]+_*value1").unwrap(), true)
-                    ),
-                    (
-                        TextMatcher::from_regex(Regex::new("foobar").unwrap(), true),
-                        TextMatcher::from_regex(Regex::new("baz").unwrap(), true)
-                    ),
-                ]
+            check_graphql(
+                test_body(RESPONSE).as_ref(),
+                graphql(&[]),
+                &StateMapping::default()
             ),
             vec![
+                CheckResult::summary(State::Crit, "GraphQL errors: 2"),
+                CheckResult::details(State::Crit, "GraphQL errors: 2"),
+                CheckResult::details(State::Crit, "GraphQL error: Not allowed (at user.email)"),
+                CheckResult::details(State::Crit, "GraphQL error: Rate limited"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        assert_eq!(
+            check_graphql(
+                test_body("<html>").as_ref(),
+                graphql(&[]),
+                &StateMapping::default()
+            )[0],
+            CheckResult::summary(
+                State::Crit,
+                "GraphQL response is not valid JSON: expected value at line 1 column 1"
+            )
+        );
+    }
+
+    #[test]
+    fn test_data_expectations() {
+        let results = check_graphql(
+            test_body(RESPONSE).as_ref(),
+            graphql(&[
+                "/user/name=alice",
+                "/user/age=42",
+                "/user/age=\"42\"",
+                "/user/email",
+                "/user/name",
+                "/user/id=1",
+            ]),
+            &StateMapping::default(),
+        );
+        assert_eq!(
+            results.into_iter().skip(4).collect::<Vec<_>>(),
+            vec![
+                CheckResult::details(State::Ok, "GraphQL data at /user/name: \"alice\""),
+                CheckResult::details(State::Ok, "GraphQL data at /user/age: 42"),
+                CheckResult::summary(
+                    State::Warn,
+                    "GraphQL data at /user/age: 42 (expected \"42\")"
+                ),
                 CheckResult::details(
-                    State::Ok,
-                    "Expected regex in HTTP headers: s.*y[0-9]:s[a-z]+_*value1 (matched)"
+                    State::Warn,
+                    "GraphQL data at /user/age: 42 (expected \"42\")"
                 ),
+                CheckResult::summary(State::Warn, "GraphQL data at /user/email: missing"),
+                CheckResult::details(State::Warn, "GraphQL data at /user/email: missing"),
+                CheckResult::details(State::Ok, "GraphQL data at /user/name: present"),
                 CheckResult::summary(
                     State::Warn,
-                    "Expected regex in HTTP headers: foobar:baz (not matched)"
+                    "GraphQL data at /user/id: missing (expected 1)"
                 ),
                 CheckResult::details(
                     State::Warn,
-                    "Expected regex in HTTP headers: foobar:baz (not matched)"
+                    "GraphQL data at /user/id: missing (expected 1)"
                 ),
             ]
-        )
-    }
-}
-
-#[cfg(test)]
-mod test_check_body {
-    use super::*;
-    use std::error::Error;
-    use std::fmt::{Display, Formatter, Result};
-
-    #[derive(Debug)]
-    struct DummyError;
-
-    impl Error for DummyError {}
-
-    impl Display for DummyError {
-        fn fmt(&self, f: &mut Formatter) -> Result {
-            write!(f, "Error")
-        }
-    }
-
-    #[test]
-    fn test_no_body() {
-        assert_eq!(check_body::<DummyError>(None), (None, vec![]));
+        );
     }
 
     #[test]
-    fn test_error_body() {
+    fn test_state_mapping() {
+        let mapping = StateMapping(vec![(Outcome::GraphQl, State::Ok)]);
         assert_eq!(
-            check_body(Some(Err(DummyError {}))),
-            (
-                None,
-                vec![
-                    CheckResult::summary(State::Crit, "Error fetching the response body"),
-                    CheckResult::details(State::Crit, "Error fetching the response body"),
-                ]
-            )
+            check_graphql(
+                test_body(RESPONSE).as_ref(),
+                graphql(&["/user/email"]),
+                &mapping
+            ),
+            vec![
+                CheckResult::details(State::Ok, "GraphQL errors: 2"),
+                CheckResult::details(State::Ok, "GraphQL error: Not allowed (at user.email)"),
+                CheckResult::details(State::Ok, "GraphQL error: Rate limited"),
+                CheckResult::details(State::Ok, "GraphQL data at /user/email: missing"),
+            ]
         );
     }
 
     #[test]
-    fn test_all_ok() {
+    fn test_data_expectation_from_str() {
+        assert!(DataExpectation::from_str("user/name").is_err());
+        let expectation = DataExpectation::from_str("/user/name=a=b").unwrap();
+        assert_eq!(expectation.pointer, "/user/name");
         assert_eq!(
-            check_body::<DummyError>(Some(Ok(Body {
-                text: "foobär".to_string(),
-                length: 7
-            }))),
-            (
-                Some(Body {
-                    text: "foobär".to_string(),
-                    length: 7
-                }),
-                vec![]
-            )
+            expectation.value,
+            Some(serde_json::Value::String("a=b".to_string()))
         );
+        assert_eq!(DataExpectation::from_str("/id").unwrap().value, None);
     }
 }
 
 #[cfg(test)]
-mod test_check_page_size {
+mod test_check_links {
     use super::*;
-    use crate::http::Body;
+    use crate::http::LinkStatus;
+
+    fn link(url: &str, status: StatusCode) -> LinkStatus {
+        LinkStatus {
+            url: Url::parse(url).unwrap(),
+            status: Ok(status),
+        }
+    }
+
+    fn scan(statuses: Vec<LinkStatus>) -> Result<LinkScanResult, reqwest::Error> {
+        Ok(LinkScanResult {
+            statuses,
+            time_limit_reached: false,
+        })
+    }
 
     #[test]
-    fn test_size_lower_out_of_bounds() {
+    fn test_none_broken() {
         assert_eq!(
-            check_page_size(
-                Some(&Body {
-                    text: String::new(),
-                    length: 42,
-                }),
-                Some(Bounds::lower(56)),
-            ),
-            vec![
-                CheckResult::summary(State::Warn, "Page size: 42 Bytes (warn below 56 Bytes)"),
-                CheckResult::details(State::Warn, "Page size: 42 Bytes (warn below 56 Bytes)"),
-                CheckResult::metric("response_size", 42., Some('B'), None, Some(0.), None)
-            ]
+            check_links(scan(vec![
+                link("http://foo.bar/a", StatusCode::OK),
+                link("http://foo.bar/b", StatusCode::MOVED_PERMANENTLY),
+            ])),
+            vec![CheckResult::details(
+                State::Ok,
+                "Links checked: 2, none broken"
+            )]
         );
     }
 
     #[test]
-    fn test_size_lower_and_higher_too_low() {
+    fn test_broken() {
         assert_eq!(
-            check_page_size(
-                Some(&Body {
-                    text: String::new(),
-                    length: 42,
-                }),
-                Some(Bounds::lower_upper(56, 100)),
-            ),
+            check_links(scan(vec![
+                link("http://foo.bar/a", StatusCode::OK),
+                link("http://foo.bar/b", StatusCode::NOT_FOUND),
+            ])),
             vec![
-                CheckResult::summary(
-                    State::Warn,
-                    "Page size: 42 Bytes (warn below/above 56 Bytes/100 Bytes)"
-                ),
-                CheckResult::details(
-                    State::Warn,
-                    "Page size: 42 Bytes (warn below/above 56 Bytes/100 Bytes)"
-                ),
-                CheckResult::metric("response_size", 42., Some('B'), None, Some(0.), None)
+                CheckResult::summary(State::Warn, "Broken links: 1 of 2 checked"),
+                CheckResult::details(State::Warn, "Broken links: 1 of 2 checked"),
+                CheckResult::details(State::Warn, "Broken link: http://foo.bar/b (404 Not Found)"),
             ]
         );
     }
 
     #[test]
-    fn test_size_lower_and_higher_too_high() {
+    fn test_time_limit_reached() {
         assert_eq!(
-            check_page_size(
-                Some(&Body {
-                    text: String::new(),
-                    length: 142,
-                }),
-                Some(Bounds::lower_upper(56, 100)),
-            ),
+            check_links(Ok(LinkScanResult {
+                statuses: vec![link("http://foo.bar/a", StatusCode::OK)],
+                time_limit_reached: true,
+            })),
             vec![
-                CheckResult::summary(
-                    State::Warn,
-                    "Page size: 142 Bytes (warn below/above 56 Bytes/100 Bytes)"
-                ),
+                CheckResult::details(State::Ok, "Links checked: 1, none broken"),
                 CheckResult::details(
-                    State::Warn,
-                    "Page size: 142 Bytes (warn below/above 56 Bytes/100 Bytes)"
+                    State::Ok,
+                    "Link scan stopped at its time limit, remaining links not checked"
                 ),
-                CheckResult::metric("response_size", 142., Some('B'), None, Some(0.), None)
             ]
         );
     }
 }
 
 #[cfg(test)]
-mod test_check_body_matching {
+mod test_check_proxy_connect {
     use super::*;
+    use anyhow::anyhow;
 
-    fn test_body(test_string: &str) -> Option<Body> {
-        Some(Body {
-            text: test_string.to_owned(),
-            length: 0,
-        })
+    fn connect(status: u16, reason: &str) -> Option<anyhow::Result<ProxyConnect>> {
+        Some(Ok(ProxyConnect {
+            proxy: Url::parse("http://proxy:3128").unwrap(),
+            status,
+            reason: reason.to_string(),
+            time: Duration::from_millis(25),
+        }))
     }
 
     #[test]
-    fn test_no_matcher() {
-        assert!(check_body_matching(test_body("foobar").as_ref(), vec![]).is_empty());
+    fn test_no_proxy() {
+        assert!(check_proxy_connect(None).is_empty());
     }
 
     #[test]
-    fn test_string_ok() {
+    fn test_established() {
         assert_eq!(
-            check_body_matching(
-                test_body("foobar").as_ref(),
-                vec![TextMatcher::Contains("bar".to_string())]
-            ),
-            vec![CheckResult::details(
-                State::Ok,
-                "Expected string in body: bar (found)"
-            ),]
+            check_proxy_connect(connect(200, "Connection established")),
+            vec![
+                CheckResult::details(
+                    State::Ok,
+                    "Proxy CONNECT via http://proxy:3128/: 0.025 seconds (200 Connection established)"
+                ),
+                CheckResult::metric("time_proxy_connect", 0.025, Some('s'), None, None, None),
+            ]
         );
     }
 
     #[test]
-    fn test_string_not_found() {
+    fn test_refused() {
+        let text = "Proxy CONNECT via http://proxy:3128/: 0.025 seconds (403 Forbidden)";
         assert_eq!(
-            check_body_matching(
-                test_body("foobär").as_ref(),
-                vec![TextMatcher::Contains("bar".to_string())]
-            ),
+            check_proxy_connect(connect(403, "Forbidden")),
             vec![
-                CheckResult::summary(State::Warn, "Expected string in body: bar (not found)"),
-                CheckResult::details(State::Warn, "Expected string in body: bar (not found)"),
+                CheckResult::summary(State::Warn, text),
+                CheckResult::details(State::Warn, text),
+                CheckResult::metric("time_proxy_connect", 0.025, Some('s'), None, None, None),
             ]
         );
     }
 
     #[test]
-    fn test_multiple_strings_not_found() {
+    fn test_failed() {
         assert_eq!(
-            check_body_matching(
-                test_body("foobär").as_ref(),
-                vec![
-                    TextMatcher::Contains("bar".to_string()),
-                    TextMatcher::Contains("baz".to_string())
-                ]
-            ),
+            check_proxy_connect(Some(Err(anyhow!("http://proxy:3128/: timeout")))),
             vec![
-                CheckResult::summary(State::Warn, "Expected string in body: bar (not found)"),
-                CheckResult::details(State::Warn, "Expected string in body: bar (not found)"),
-                CheckResult::summary(State::Warn, "Expected string in body: baz (not found)"),
-                CheckResult::details(State::Warn, "Expected string in body: baz (not found)"),
+                CheckResult::summary(
+                    State::Warn,
+                    "Proxy CONNECT failed: http://proxy:3128/: timeout"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Proxy CONNECT failed: http://proxy:3128/: timeout"
+                ),
             ]
         );
     }
+}
+
+#[cfg(test)]
+mod test_check_trend {
+    use super::*;
+
+    fn snapshot(response_time: f64, serial: &str, final_url: &str) -> Snapshot {
+        Snapshot {
+            response_time,
+            body_hash: Some("abc".to_string()),
+            certificate_serial: Some(serial.to_string()),
+            final_url: final_url.to_string(),
+        }
+    }
+
+    fn levels() -> TrendLevels {
+        TrendLevels {
+            response_time_increase: Some(UpperLevels::warn_crit(50., 100.)),
+            certificate_change_state: Some(State::Warn),
+            body_change_state: None,
+            final_url_change_state: Some(State::Crit),
+        }
+    }
 
     #[test]
-    fn test_regex_ok() {
+    fn test_no_history() {
         assert_eq!(
-            check_body_matching(
-                test_body("foobar").as_ref(),
-                vec![TextMatcher::from_regex(Regex::new("f.*r").unwrap(), true)]
+            check_trend(
+                &History::default(),
+                &snapshot(1., "01", "https://a/"),
+                levels()
             ),
             vec![CheckResult::details(
                 State::Ok,
-                "Expected regex in body: f.*r (matched)"
-            ),]
+                "No previous results to compare with"
+            )]
         );
     }
 
     #[test]
-    fn test_regex_not_ok() {
+    fn test_unchanged() {
+        let mut history = History::default();
+        history.record(snapshot(1., "01", "https://a/"), 10);
+        history.record(snapshot(3., "01", "https://a/"), 10);
         assert_eq!(
-            check_body_matching(
-                test_body("foobar").as_ref(),
-                vec![TextMatcher::from_regex(Regex::new("f.*z").unwrap(), true)]
-            ),
+            check_trend(&history, &snapshot(2.5, "01", "https://a/"), levels()),
             vec![
-                CheckResult::summary(State::Warn, "Expected regex in body: f.*z (not matched)"),
-                CheckResult::details(State::Warn, "Expected regex in body: f.*z (not matched)"),
+                CheckResult::details(
+                    State::Ok,
+                    "Response time compared to average of last 2 run(s) (2 seconds): +25.0%"
+                ),
+                CheckResult::metric("response_time_average", 2., Some('s'), None, Some(0.), None),
+                CheckResult::details(
+                    State::Ok,
+                    "Server certificate: unchanged since previous run"
+                ),
+                CheckResult::details(State::Ok, "Final URL: unchanged since previous run"),
             ]
         );
     }
 
     #[test]
-    fn test_regex_inverse_ok() {
+    fn test_changed() {
+        let mut history = History::default();
+        history.record(snapshot(1., "01", "https://a/"), 10);
         assert_eq!(
-            check_body_matching(
-                test_body("foobar").as_ref(),
-                vec![TextMatcher::from_regex(Regex::new("f.*z").unwrap(), false)]
-            ),
-            vec![CheckResult::details(
-                State::Ok,
-                "Not expected regex in body: f.*z (not matched)"
-            ),]
+            check_trend(&history, &snapshot(2.5, "02", "https://b/"), levels()),
+            vec![
+                CheckResult::summary(
+                    State::Crit,
+                    "Response time compared to average of last 1 run(s) (1 seconds): +150.0% \
+                    (warn/crit at +50.0%/+100.0%)"
+                ),
+                CheckResult::details(
+                    State::Crit,
+                    "Response time compared to average of last 1 run(s) (1 seconds): +150.0% \
+                    (warn/crit at +50.0%/+100.0%)"
+                ),
+                CheckResult::metric("response_time_average", 1., Some('s'), None, Some(0.), None),
+                CheckResult::summary(
+                    State::Warn,
+                    "Server certificate: changed since previous run (01 -> 02)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Server certificate: changed since previous run (01 -> 02)"
+                ),
+                CheckResult::summary(
+                    State::Crit,
+                    "Final URL: changed since previous run (https://a/ -> https://b/)"
+                ),
+                CheckResult::details(
+                    State::Crit,
+                    "Final URL: changed since previous run (https://a/ -> https://b/)"
+                ),
+            ]
         );
     }
 
     #[test]
-    fn test_regex_inverse_not_ok() {
+    fn test_change_of_unknown_value() {
+        assert!(
+            check_change("Response body", None, Some("abc"), Some(State::Warn), false).is_empty()
+        );
+        assert!(check_change("Response body", Some("abc"), Some("def"), None, false).is_empty());
         assert_eq!(
-            check_body_matching(
-                test_body("argl").as_ref(),
-                vec![TextMatcher::from_regex(Regex::new("argl").unwrap(), false)]
+            check_change(
+                "Response body",
+                Some("abc"),
+                Some("def"),
+                Some(State::Warn),
+                false
             ),
-            vec![
-                CheckResult::summary(State::Warn, "Not expected regex in body: argl (matched)"),
-                CheckResult::details(State::Warn, "Not expected regex in body: argl (matched)")
-            ]
+            notice(State::Warn, "Response body: changed since previous run")
         );
     }
+}
+
+#[cfg(test)]
+mod test_check_attempts {
+    use super::*;
 
     #[test]
-    fn test_multiple_matchers_ok() {
+    fn test_attempts() {
         assert_eq!(
-            check_body_matching(
-                test_body("foobar").as_ref(),
-                vec![
-                    TextMatcher::Contains("bar".to_string()),
-                    TextMatcher::Contains("foo".to_string())
-                ]
-            ),
+            check_attempts(2, 4),
             vec![
-                CheckResult::details(State::Ok, "Expected string in body: bar (found)"),
-                CheckResult::details(State::Ok, "Expected string in body: foo (found)"),
+                CheckResult::details(State::Ok, "Attempts: 2 of 4"),
+                CheckResult::metric("attempts", 2., None, None, Some(1.), Some(4.)),
             ]
         );
     }
@@ -1676,6 +3616,221 @@ mod test_check_document_age {
     }
 }
 
+#[cfg(test)]
+mod test_check_clock_skew {
+    use super::*;
+
+    const UNIX_TIME_2023_11_16: u64 = 1700092800;
+
+    fn system_time(unix_timestamp: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(unix_timestamp)
+    }
+
+    fn header_date(date: &str) -> HeaderValue {
+        HeaderValue::from_str(date).unwrap()
+    }
+
+    #[test]
+    fn test_no_levels() {
+        assert!(check_clock_skew(
+            system_time(UNIX_TIME_2023_11_16),
+            Some(&header_date("We don't care")),
+            None,
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_missing_header() {
+        assert_eq!(
+            check_clock_skew(
+                system_time(UNIX_TIME_2023_11_16),
+                None,
+                Some(UpperLevels::warn(30)),
+            ),
+            vec![
+                CheckResult::summary(State::Crit, "Can't determine clock skew (no Date header)"),
+                CheckResult::details(State::Crit, "Can't determine clock skew (no Date header)")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_erroneous_date() {
+        assert_eq!(
+            check_clock_skew(
+                system_time(UNIX_TIME_2023_11_16),
+                Some(&header_date("Something wrong")),
+                Some(UpperLevels::warn(30)),
+            ),
+            vec![
+                CheckResult::summary(State::Crit, "Can't decode Date header"),
+                CheckResult::details(State::Crit, "Can't decode Date header")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_server_behind() {
+        assert_eq!(
+            check_clock_skew(
+                system_time(UNIX_TIME_2023_11_16 + 10),
+                Some(&header_date("Thu, 16 Nov 2023 00:00:00 GMT")),
+                Some(UpperLevels::warn_crit(30, 60)),
+            ),
+            vec![
+                CheckResult::details(State::Ok, "Clock skew (server behind): 10 seconds"),
+                CheckResult::metric(
+                    "clock_skew",
+                    10.,
+                    Some('s'),
+                    Some(UpperLevels::warn_crit(30., 60.)),
+                    Some(0.),
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_server_ahead() {
+        assert_eq!(
+            check_clock_skew(
+                system_time(UNIX_TIME_2023_11_16),
+                Some(&header_date("Thu, 16 Nov 2023 00:01:30 GMT")),
+                Some(UpperLevels::warn_crit(30, 60)),
+            )[..2],
+            vec![
+                CheckResult::summary(
+                    State::Crit,
+                    "Clock skew (server ahead): 90 seconds (warn/crit at 30 seconds/60 seconds)"
+                ),
+                CheckResult::details(
+                    State::Crit,
+                    "Clock skew (server ahead): 90 seconds (warn/crit at 30 seconds/60 seconds)"
+                ),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_check_handshake {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn handshake(cipher: &str, bits: i32, ocsp_stapling: OcspStapling) -> Handshake {
+        Handshake {
+            version: "TLSv1.2".to_string(),
+            cipher: cipher.to_string(),
+            cipher_bits: bits,
+            alpn: Some("h2".to_string()),
+            ocsp_stapling,
+        }
+    }
+
+    #[test]
+    fn test_not_collected() {
+        assert!(check_handshake(None, State::Warn, None).is_empty());
+    }
+
+    #[test]
+    fn test_ok() {
+        assert_eq!(
+            check_handshake(
+                Some(Ok(handshake(
+                    "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+                    256,
+                    OcspStapling::Good
+                ))),
+                State::Warn,
+                Some(State::Warn)
+            ),
+            vec![
+                CheckResult::details(State::Ok, "TLS probe version: TLSv1.2"),
+                CheckResult::details(
+                    State::Ok,
+                    "TLS probe cipher suite: TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384 (256 bits)"
+                ),
+                CheckResult::details(State::Ok, "TLS probe ALPN protocol: h2"),
+                CheckResult::details(State::Ok, "TLS probe OCSP stapling: certificate good"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weak_cipher_and_missing_stapling() {
+        let cipher =
+            "TLS probe cipher suite: TLS_RSA_WITH_3DES_EDE_CBC_SHA (112 bits), considered weak";
+        assert_eq!(
+            check_handshake(
+                Some(Ok(handshake(
+                    "TLS_RSA_WITH_3DES_EDE_CBC_SHA",
+                    112,
+                    OcspStapling::Missing
+                ))),
+                State::Crit,
+                Some(State::Warn)
+            ),
+            vec![
+                CheckResult::details(State::Ok, "TLS probe version: TLSv1.2"),
+                CheckResult::summary(State::Crit, cipher),
+                CheckResult::details(State::Crit, cipher),
+                CheckResult::details(State::Ok, "TLS probe ALPN protocol: h2"),
+                CheckResult::summary(State::Warn, "TLS probe OCSP stapling: no response stapled"),
+                CheckResult::details(State::Warn, "TLS probe OCSP stapling: no response stapled"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_stapling_ignored() {
+        assert_eq!(
+            check_handshake(
+                Some(Ok(handshake(
+                    "TLS_AES_128_GCM_SHA256",
+                    128,
+                    OcspStapling::Missing
+                ))),
+                State::Warn,
+                None
+            )[3],
+            CheckResult::details(State::Ok, "TLS probe OCSP stapling: no response stapled"),
+        );
+    }
+
+    #[test]
+    fn test_invalid_stapling() {
+        let text = "TLS probe OCSP stapling: invalid response (signature verification failed)";
+        assert_eq!(
+            check_handshake(
+                Some(Ok(handshake(
+                    "TLS_AES_128_GCM_SHA256",
+                    128,
+                    OcspStapling::Invalid("signature verification failed".to_string())
+                ))),
+                State::Warn,
+                None
+            )[3..],
+            [
+                CheckResult::summary(State::Crit, text),
+                CheckResult::details(State::Crit, text),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed() {
+        assert_eq!(
+            check_handshake(Some(Err(anyhow!("timeout"))), State::Warn, None),
+            vec![
+                CheckResult::summary(State::Warn, "TLS probe unavailable: timeout"),
+                CheckResult::details(State::Warn, "TLS probe unavailable: timeout"),
+            ]
+        );
+    }
+}
+
 #[cfg(test)]
 mod test_check_user_agent {
     use std::vec;
@@ -1690,3 +3845,101 @@ mod test_check_user_agent {
         )
     }
 }
+
+#[cfg(test)]
+mod test_check_dual_stack_differences {
+    use super::*;
+
+    fn response(status: u16, text: &str, millis: u64) -> ProcessedResponse {
+        ProcessedResponse {
+            version: Version::HTTP_11,
+            status: StatusCode::from_u16(status).unwrap(),
+            headers: HeaderMap::new(),
+            body: Some(Ok(Body {
+                text: text.to_string(),
+                length: text.len(),
+            })),
+            final_url: Url::parse("https://example.com/").unwrap(),
+            redirect_target: None,
+            tls_info: None,
+            time_headers: Duration::from_millis(millis),
+            headers_received: SystemTime::UNIX_EPOCH,
+            time_body: None,
+            proxy_connect: None,
+            handshake: None,
+        }
+    }
+
+    fn levels() -> DualStackLevels {
+        DualStackLevels {
+            difference_state: State::Crit,
+            response_time_difference: Some(UpperLevels::warn(0.5)),
+        }
+    }
+
+    #[test]
+    fn test_same() {
+        assert_eq!(
+            check_dual_stack_differences(
+                &response(200, "foo", 100),
+                &response(200, "foo", 200),
+                levels()
+            ),
+            vec![
+                CheckResult::details(
+                    State::Ok,
+                    "Response time difference between IPv4 and IPv6 (IPv6 slower): 0.1 seconds"
+                ),
+                CheckResult::metric("response_time_ipv6", 0.2, Some('s'), None, Some(0.), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_different() {
+        assert_eq!(
+            check_dual_stack_differences(
+                &response(200, "foo", 1000),
+                &response(503, "bar", 200),
+                levels()
+            ),
+            vec![
+                CheckResult::summary(
+                    State::Crit,
+                    "Status differs between IPv4 and IPv6: 200 OK vs. 503 Service Unavailable"
+                ),
+                CheckResult::details(
+                    State::Crit,
+                    "Status differs between IPv4 and IPv6: 200 OK vs. 503 Service Unavailable"
+                ),
+                CheckResult::summary(State::Crit, "Body differs between IPv4 and IPv6"),
+                CheckResult::details(State::Crit, "Body differs between IPv4 and IPv6"),
+                CheckResult::summary(
+                    State::Warn,
+                    "Response time difference between IPv4 and IPv6 (IPv6 faster): 0.8 seconds (warn at 0.5 seconds)"
+                ),
+                CheckResult::details(
+                    State::Warn,
+                    "Response time difference between IPv4 and IPv6 (IPv6 faster): 0.8 seconds (warn at 0.5 seconds)"
+                ),
+                CheckResult::metric("response_time_ipv6", 0.2, Some('s'), None, Some(0.), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_body() {
+        let mut ipv6 = response(200, "bar", 100);
+        ipv6.body = None;
+        assert_eq!(
+            check_dual_stack_differences(&response(200, "foo", 100), &ipv6, levels()),
+            vec![
+                CheckResult::details(
+                    State::Ok,
+                    "Response time difference between IPv4 and IPv6 (IPv6 faster): 0 seconds"
+                ),
+                CheckResult::metric("response_time_ipv6", 0.1, Some('s'), None, Some(0.), None),
+            ]
+        );
+    }
+}
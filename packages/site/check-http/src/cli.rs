@@ -2,19 +2,25 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
-use crate::http::Server;
-use crate::pwstore::password_from_store;
-use anyhow::{bail, Result as AnyhowResult};
+use crate::http::{NoProxyList, PacScript, Server};
+use anyhow::{anyhow, bail, Result as AnyhowResult};
+use check_http::checks::{DataExpectation, Outcome};
+use check_http::html::CssSelector;
+use check_http::prometheus::SeriesSelector;
 use clap::{Args, Parser, ValueEnum};
+use cmk_pwstore::password_from_store;
 use regex::{Regex, RegexBuilder};
 use reqwest::{
     header::{HeaderName, HeaderValue},
     Method, StatusCode, Url,
 };
-use std::{str::FromStr, time::Duration};
+use std::{path::PathBuf, str::FromStr, time::Duration};
 use tracing_subscriber::filter::LevelFilter;
 
 #[derive(Parser, Debug)]
+#[command(
+    after_help = "Pass --legacy as first argument to use the options of the classic check_http instead.\nPass --batch FILE as first argument to run many checks defined in FILE concurrently."
+)]
 /// check_httpv2
 pub struct Cli {
     /// Username for HTTP Basic Auth
@@ -50,6 +56,22 @@ pub struct Cli {
     #[arg(short = 'p', long)]
     pub server: Option<Server>,
 
+    /// Server name to send via SNI in the TLS handshake, instead of the host of the URL.
+    ///
+    /// The server certificate is verified against this name, and the connection still goes
+    /// to the host of the URL or to --server. Unless --host-header is given, the Host header
+    /// keeps naming the host of the URL.
+    #[arg(long, value_parser = parse_sni_name, verbatim_doc_comment)]
+    pub sni: Option<String>,
+
+    /// Don't send SNI in the TLS handshake.
+    #[arg(long, conflicts_with = "sni")]
+    pub no_sni: bool,
+
+    /// Value of the Host header, instead of the host (and port) of the URL.
+    #[arg(long)]
+    pub host_header: Option<HeaderValue>,
+
     /// URL to check
     #[arg(short, long)]
     pub url: Url,
@@ -105,6 +127,28 @@ pub struct Cli {
     #[command(flatten)]
     pub proxy_pw: ProxyPw,
 
+    /// Proxy auto-config (PAC) file to determine the proxy like a browser would.
+    ///
+    /// The result of FindProxyForURL is evaluated for every request, including redirects.
+    /// Only the first entry of the result is used. If the evaluation fails, the request
+    /// is sent directly.
+    #[arg(long, conflicts_with = "proxy_url", value_parser = parse_pac_file, verbatim_doc_comment)]
+    pub proxy_pac_file: Option<PacScript>,
+
+    /// Comma separated list of hosts that are never contacted via proxy.
+    ///
+    /// Entries may be domains (also matching their subdomains), IP addresses,
+    /// networks in CIDR notation or "*" for all hosts, like in the NO_PROXY environment variable.
+    /// E.g. <intranet.example.com,10.0.0.0/8>
+    #[arg(long, verbatim_doc_comment)]
+    pub no_proxy: Option<NoProxyList>,
+
+    /// Report the time of the CONNECT request for HTTPS URLs via HTTP proxy.
+    ///
+    /// The CONNECT request is passed through a relay on localhost to measure it.
+    #[arg(long, verbatim_doc_comment)]
+    pub proxy_connect_time: bool,
+
     /// How to handle redirected pages.
     ///
     /// sticky is like follow but stick to the specified IP address.
@@ -112,14 +156,57 @@ pub struct Cli {
     #[arg(short = 'f', long, default_value = "follow", verbatim_doc_comment)]
     pub onredirect: OnRedirect,
 
+    /// Number of retries before a failed request is reported.
+    ///
+    /// Which failures are retried is set with --retry-on.
+    /// Every attempt may take up to the specified timeout.
+    #[arg(long, default_value_t = 0, verbatim_doc_comment)]
+    pub retries: usize,
+
+    /// Delay before the first retry in seconds, doubling with every further retry.
+    #[arg(long, default_value = "1", value_parser = parse_seconds)]
+    pub retry_delay: Duration,
+
+    /// Time limit in seconds for all attempts together, including the delays.
+    ///
+    /// A retry is only started if its delay and the timeout still fit into the limit.
+    #[arg(long, default_value = "60", value_parser = parse_seconds, verbatim_doc_comment)]
+    pub retry_time_limit: Duration,
+
+    /// Failures to retry, separated by comma.
+    ///
+    /// connect: Connection errors, like refused or reset connections.
+    /// timeout: No response within the specified timeout.
+    /// 5xx: Responses with a server error status code.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "connect,timeout,5xx",
+        verbatim_doc_comment
+    )]
+    pub retry_on: Vec<RetryOn>,
+
     /// Maximal number of redirects
     #[arg(long, default_value_t = 15)]
     pub max_redirs: usize,
 
     /// Force IP version for connection
-    #[arg(long)]
+    ///
+    /// With "both", the URL is checked via IPv4 and IPv6 separately. The regular checks apply
+    /// to the IPv4 response, and both results are reported along with their differences
+    /// in status, body and response time.
+    #[arg(long, verbatim_doc_comment)]
     pub force_ip_version: Option<ForceIP>,
 
+    /// State if status or body differ between IPv4 and IPv6 (with --force-ip-version both)
+    #[arg(long, default_value = "warning", requires = "force_ip_version")]
+    pub ip_version_difference_state: CheckState,
+
+    /// WARN/CRIT levels for the difference of the response times via IPv4 and IPv6 in seconds
+    /// (with --force-ip-version both, Format: WARN\[,CRIT\])
+    #[arg(long, requires = "force_ip_version", value_parser = parse_optional_pair::<f64>)]
+    pub ip_version_response_time_difference: Option<ResponseTimeLevels>,
+
     /// Minimum/Maximum expected page size in bytes (Format: MIN\[,MAX\])
     #[arg(long, conflicts_with = "without_body", value_parser = parse_optional_pair::<usize>)]
     pub page_size: Option<PageSizeLimits>,
@@ -134,12 +221,92 @@ pub struct Cli {
     #[arg(long)]
     pub document_age_levels: Option<u64>,
 
+    /// WARN/CRIT levels for the clock skew of the server in seconds (Format: WARN\[,CRIT\])
+    ///
+    /// The Date header of the response is compared to the local time, regardless of
+    /// whether the server is ahead or behind. A missing Date header leads to state CRIT.
+    #[arg(long, value_parser = parse_optional_pair::<u64>)]
+    pub clock_skew_levels: Option<(u64, Option<u64>)>,
+
     /// WARN/CRIT levels for server certificate validity
     ///
     /// Not relevant for HTTP connections without TLS.
     #[arg(long, value_parser = parse_optional_pair::<u64>)]
     pub certificate_levels: Option<(u64, Option<u64>)>,
 
+    /// Directory for the results of previous runs, needed for the checks on changes below.
+    ///
+    /// Every check (as identified by its command line) keeps its own state file there.
+    #[arg(long, verbatim_doc_comment)]
+    pub state_dir: Option<PathBuf>,
+
+    /// WARN/CRIT levels for the increase of the response time in percent (Format: WARN\[,CRIT\])
+    ///
+    /// The response time is compared to the average of the previous runs.
+    #[arg(long, requires = "state_dir", value_parser = parse_optional_pair::<f64>)]
+    pub response_time_increase: Option<(f64, Option<f64>)>,
+
+    /// Number of previous runs to average the response time over.
+    #[arg(long, default_value_t = 10, requires = "state_dir", value_parser = clap::value_parser!(u16).range(1..))]
+    pub response_time_average_runs: u16,
+
+    /// State if the serial number of the server certificate changed since the previous run.
+    #[arg(long, requires = "state_dir")]
+    pub certificate_change_state: Option<CheckState>,
+
+    /// State if the response body changed since the previous run.
+    #[arg(long, requires = "state_dir", conflicts_with = "without_body")]
+    pub body_change_state: Option<CheckState>,
+
+    /// State if the final URL (after following redirects) changed since the previous run.
+    #[arg(long, requires = "state_dir")]
+    pub final_url_change_state: Option<CheckState>,
+
+    /// Report TLS version, cipher suite, ALPN protocol and OCSP stapling status.
+    ///
+    /// The details don't come from the connection of the request, but from a separate
+    /// TLS handshake with the server of the final URL (reported as "TLS probe").
+    /// As this handshake can't go through a proxy, it is skipped for requests via proxy.
+    #[arg(long, verbatim_doc_comment)]
+    pub tls_details: bool,
+
+    /// State if the negotiated cipher suite is considered weak.
+    ///
+    /// This applies to NULL, export grade, RC4, (3)DES, MD5 and anonymous cipher suites,
+    /// and to all cipher suites with less than 128 bits of key strength.
+    #[arg(
+        long,
+        default_value = "warning",
+        requires = "tls_details",
+        verbatim_doc_comment
+    )]
+    pub tls_weak_cipher_state: CheckState,
+
+    /// State if the server doesn't staple an OCSP response to the handshake.
+    #[arg(long, requires = "tls_details")]
+    pub tls_missing_stapling_state: Option<CheckState>,
+
+    /// Override the state of an outcome (Format: OUTCOME=STATE), can be used multiple times.
+    ///
+    /// Outcomes:
+    /// connect: Connection errors, like refused or reset connections.
+    /// timeout: No response within the specified timeout.
+    /// tls: Errors during the TLS handshake, including certificate verification.
+    /// redirect: Redirect errors, like exceeding the maximal number of redirects.
+    /// body_match: Failed body string or regex matches.
+    /// header_match: Failed header string or regex matches.
+    /// html_match: Failed HTML element, title or element text matches.
+    /// series: Missing Prometheus series, or metrics that can't be parsed.
+    /// json_schema: Invalid JSON or schema violations, instead of --json-schema-state.
+    /// graphql: GraphQL errors and failed data expectations, instead of --graphql-errors-state.
+    /// Status codes, as single code (404), class (4xx) or range (500-504).
+    /// Status codes only apply without --status-code, which then decides on its own.
+    /// States from levels (like response time or Prometheus series values) can't be overridden.
+    ///
+    /// If an outcome is given multiple times, the last one wins.
+    #[arg(long, value_parser = parse_state_override, verbatim_doc_comment)]
+    pub state_on: Vec<(Outcome, CheckState)>,
+
     /// Text to send in HTTP body.
     ///
     /// This will set the HTTP method to POST if unset,
@@ -182,6 +349,135 @@ pub struct Cli {
     #[arg(long, requires = "body_regex", default_value_t = false)]
     pub body_regex_invert: bool,
 
+    /// CSS selector(s) of HTML elements to expect in the response body.
+    ///
+    /// Specify multiple times for additional elements.
+    /// E.g., "form#login input[type=password]" expects a password field
+    /// within the form with the id "login".
+    #[arg(long, conflicts_with = "without_body", verbatim_doc_comment)]
+    pub html_element: Vec<CssSelector>,
+
+    /// Expected text of the HTML title element.
+    ///
+    /// Whitespace within the title is normalized before comparing.
+    #[arg(long, conflicts_with = "without_body")]
+    pub html_title: Option<String>,
+
+    /// Text to expect within the HTML element(s) matching a CSS selector.
+    ///
+    /// Format: SELECTOR TEXT
+    /// Specify multiple times for additional element texts.
+    /// The check is OK if the text is contained in at least one of the matching elements.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["SELECTOR", "TEXT"],
+        conflicts_with = "without_body",
+        verbatim_doc_comment
+    )]
+    pub html_element_text: Vec<String>,
+
+    /// Scan same-origin links on the page for broken targets.
+    ///
+    /// Links from a, link, img and script elements are fetched with GET,
+    /// and every link answered with a 4xx/5xx status or an error is reported.
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "without_body",
+        verbatim_doc_comment
+    )]
+    pub html_check_links: bool,
+
+    /// Maximum link depth for --html-check-links.
+    ///
+    /// 1 only checks the links found on the checked page,
+    /// 2 also checks the links found on the linked pages, and so on.
+    #[arg(
+        long,
+        default_value_t = 1,
+        requires = "html_check_links",
+        verbatim_doc_comment
+    )]
+    pub html_links_max_depth: usize,
+
+    /// Maximum number of links to fetch for --html-check-links.
+    #[arg(long, default_value_t = 20, requires = "html_check_links")]
+    pub html_links_max_count: usize,
+
+    /// Maximum number of links to fetch concurrently for --html-check-links.
+    #[arg(
+        long,
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u16).range(1..),
+        requires = "html_check_links"
+    )]
+    pub html_links_parallelism: u16,
+
+    /// Time limit in seconds for the whole scan of --html-check-links.
+    ///
+    /// Links that haven't been checked by then are not reported.
+    #[arg(
+        long,
+        default_value = "30",
+        value_parser = parse_seconds,
+        requires = "html_check_links",
+        verbatim_doc_comment
+    )]
+    pub html_links_time_limit: Duration,
+
+    /// Series to evaluate in a Prometheus/OpenMetrics text exposition response.
+    ///
+    /// Format: SELECTOR[;upper=WARN[,CRIT]][;lower=WARN[,CRIT]]
+    /// SELECTOR is a metric name, optionally followed by labels that need to match exactly,
+    /// e.g. 'http_requests_total{code="500",handler="/api"}'.
+    /// Every series matching the selector is reported as Checkmk metric.
+    /// Specify multiple times for additional series.
+    #[arg(long, conflicts_with = "without_body", value_parser = parse_series_levels, verbatim_doc_comment)]
+    pub prometheus_series: Vec<SeriesLevels>,
+
+    /// Validate the response body against the JSON schema in this file.
+    ///
+    /// Every violation is reported with the path of the offending value.
+    #[arg(long, conflicts_with = "without_body")]
+    pub json_schema: Option<PathBuf>,
+
+    /// State to report for JSON schema violations and non-JSON responses.
+    #[arg(long, default_value = "warning", requires = "json_schema")]
+    pub json_schema_state: CheckState,
+
+    /// GraphQL query to send.
+    ///
+    /// The query is sent as JSON document (Content-Type: application/json) via POST
+    /// (unless --method says otherwise).
+    /// The response has to be a GraphQL response without errors, see --graphql-errors-state.
+    #[arg(
+        long,
+        conflicts_with = "body",
+        conflicts_with = "content_type",
+        conflicts_with = "without_body",
+        verbatim_doc_comment
+    )]
+    pub graphql_query: Option<String>,
+
+    /// File with the variables for the GraphQL query, as JSON object.
+    #[arg(long, requires = "graphql_query")]
+    pub graphql_variables: Option<PathBuf>,
+
+    /// State to report for errors in the GraphQL response and non-JSON responses.
+    #[arg(long, default_value = "critical", requires = "graphql_query")]
+    pub graphql_errors_state: CheckState,
+
+    /// Value to expect in the data of the GraphQL response (Format: POINTER\[=VALUE\]).
+    ///
+    /// The value is addressed by a JSON pointer relative to "data", e.g. /user/name.
+    /// VALUE is compared as JSON, so 'true', '42' and '"42"' are different values.
+    /// Anything that is no valid JSON is taken as string.
+    /// Without VALUE, the value only has to be present and not null.
+    /// Specify multiple times for additional values.
+    #[arg(long, requires = "graphql_query", verbatim_doc_comment)]
+    pub graphql_expect: Vec<DataExpectation>,
+
     /// Strings to expect in the headers.
     ///
     /// Format: \[KEY\]:\[VALUE\]
@@ -209,6 +505,7 @@ pub struct Cli {
 
     /// Expected HTTP status code.
     ///
+    /// Any other status code is CRIT, regardless of the status codes given to --state-on.
     /// Note: Avoid setting this to a 3xx code while setting "--onredirect=warning/critical"
     #[arg(short = 'e', long)]
     pub status_code: Vec<StatusCode>,
@@ -235,6 +532,20 @@ pub struct Cli {
     #[arg(long, default_value_t = false)]
     pub debug_content: bool,
 
+    /// Write the requests and responses to a HAR file (HTTP Archive), e.g. to share them.
+    ///
+    /// All attempts are recorded, including redirects and retries.
+    /// Credentials and cookies in the headers are hidden, also if given with --header.
+    /// Limitation: Redirects are followed internally by the HTTP client. So the entries of
+    /// redirect responses only contain the status and the Location header, and the entries
+    /// of the requests following a redirect don't contain any request headers.
+    #[arg(long, value_name = "FILE", verbatim_doc_comment)]
+    pub har: Option<PathBuf>,
+
+    /// Maximal size of the bodies written to the HAR file in bytes.
+    #[arg(long, default_value_t = 102400, requires = "har")]
+    pub har_max_body_size: usize,
+
     /// Enable verbosity output to stderr.
     ///
     /// Specify up to three times for INFO/DEBUG/TRACE log output.
@@ -261,6 +572,11 @@ impl Cli {
 
 type PageSizeLimits = (usize, Option<usize>);
 type ResponseTimeLevels = (f64, Option<f64>);
+type SeriesLevels = (
+    SeriesSelector,
+    Option<(f64, Option<f64>)>,
+    Option<(f64, Option<f64>)>,
+);
 
 // Only support HTTP/1.1 and HTTP/2 for now.
 // HTTP/0.9 is deprecated for over two decades, and while it's settable,
@@ -293,10 +609,29 @@ pub enum OnRedirect {
     Stickyport,
 }
 
+#[derive(Clone, Debug, ValueEnum)]
+pub enum RetryOn {
+    Connect,
+    Timeout,
+    #[value(name = "5xx")]
+    ServerError,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+pub enum CheckState {
+    Ok,
+    #[value(alias = "warn")]
+    Warning,
+    #[value(alias = "crit")]
+    Critical,
+    Unknown,
+}
+
 #[derive(Clone, Debug, ValueEnum)]
 pub enum ForceIP {
     Ipv4,
     Ipv6,
+    Both,
 }
 
 #[derive(Args, Debug)]
@@ -390,6 +725,52 @@ where
     }
 }
 
+fn parse_pac_file(path: &str) -> AnyhowResult<PacScript> {
+    std::fs::read_to_string(path)
+        .map_err(|err| anyhow!("Can't read PAC file {}: {}", path, err))?
+        .parse()
+}
+
+fn parse_state_override(spec: &str) -> AnyhowResult<(Outcome, CheckState)> {
+    let (outcome, state) = spec
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid state override: {} (expected OUTCOME=STATE)", spec))?;
+    Ok((
+        outcome.parse()?,
+        CheckState::from_str(state, true).map_err(|err| anyhow!(err))?,
+    ))
+}
+
+fn parse_sni_name(name: &str) -> AnyhowResult<String> {
+    match Url::parse(&format!("https://{}/", name))?.domain() {
+        Some(domain) if domain == name.to_ascii_lowercase() => Ok(domain.to_string()),
+        _ => bail!("Invalid SNI name: {} (expected a DNS name)", name),
+    }
+}
+
+fn parse_series_levels(spec: &str) -> AnyhowResult<SeriesLevels> {
+    // Label values may contain ';', so we only look for levels after the label set.
+    let labels_end = spec.rfind('}').map(|pos| pos + 1).unwrap_or_default();
+    let (selector, levels) = match spec[labels_end..].find(';') {
+        Some(pos) => spec.split_at(labels_end + pos),
+        None => (spec, ""),
+    };
+
+    let mut upper = None;
+    let mut lower = None;
+    for level in levels.split(';').filter(|l| !l.is_empty()) {
+        match level.split_once('=') {
+            Some(("upper", values)) => upper = Some(parse_optional_pair::<f64>(values)?),
+            Some(("lower", values)) => lower = Some(parse_optional_pair::<f64>(values)?),
+            _ => bail!(
+                "Invalid levels: {} (expected upper=... or lower=...)",
+                level
+            ),
+        }
+    }
+    Ok((selector.parse()?, upper, lower))
+}
+
 fn parse_regex_pattern_header_pair(pattern_pair: &str) -> AnyhowResult<(Regex, Regex)> {
     let (name, value): (String, String) = split_header(pattern_pair)?;
     Ok((
@@ -454,6 +835,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_series_levels() {
+        assert!(parse_series_levels("up;warn=1").is_err());
+        assert!(parse_series_levels("up;upper=a,b").is_err());
+        let (selector, upper, lower) = parse_series_levels("up").unwrap();
+        assert_eq!(selector.to_string(), "up");
+        assert_eq!((upper, lower), (None, None));
+        let (selector, upper, lower) =
+            parse_series_levels(r#"queue{name="a;b"};upper=10,20;lower=1"#).unwrap();
+        assert_eq!(selector.to_string(), r#"queue{name="a;b"}"#);
+        assert_eq!((upper, lower), (Some((10., Some(20.))), Some((1., None))));
+    }
+
+    #[test]
+    fn test_parse_state_override() {
+        assert!(parse_state_override("timeout").is_err());
+        assert!(parse_state_override("timeout=bad").is_err());
+        assert!(parse_state_override("nonsense=warn").is_err());
+        assert!(matches!(
+            parse_state_override("timeout=warn").unwrap(),
+            (Outcome::Timeout, CheckState::Warning)
+        ));
+        assert!(matches!(
+            parse_state_override("4xx=crit").unwrap(),
+            (Outcome::Status(400, 499), CheckState::Critical)
+        ));
+    }
+
+    #[test]
+    fn test_graphql_content_type() {
+        let err = Cli::try_parse_from([
+            "check_httpv2",
+            "--url",
+            "https://example.com/graphql",
+            "--graphql-query",
+            "{ user { name } }",
+            "--content-type",
+            "application/graphql",
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_parse_sni_name() {
+        assert_eq!(parse_sni_name("Example.com").unwrap(), "example.com");
+        assert!(parse_sni_name("10.0.0.1").is_err());
+        assert!(parse_sni_name("example.com:443").is_err());
+        assert!(parse_sni_name("example.com/path").is_err());
+        assert!(parse_sni_name("").is_err());
+    }
+
     #[test]
     fn test_parse_header_pair() {
         assert!(split_header("name value").is_err());
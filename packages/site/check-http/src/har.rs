@@ -0,0 +1,421 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// HAR files (HTTP Archive, see http://www.softwareishard.com/blog/har-12-spec/) record the
+// requests of a check, including retries, so they can be shared and inspected with the
+// developer tools of any browser.
+// reqwest follows redirects internally and only tells us the status and the target of the
+// intermediate responses, so their entries lack the remaining headers, bodies and timings.
+
+use crate::http::{Exchange, ProcessedResponse};
+use anyhow::Result as AnyhowResult;
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, AUTHORIZATION, CONTENT_TYPE, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE,
+    },
+    Method, StatusCode, Url,
+};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const HIDDEN: &str = "<hidden>";
+// Besides the values reqwest marks as sensitive, e.g. from basic auth, headers given on the
+// command line may carry credentials as well.
+const SENSITIVE_HEADERS: [HeaderName; 4] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+
+pub struct Har {
+    pub path: PathBuf,
+    /// Bodies are cut off after this many bytes
+    pub max_body_size: usize,
+}
+
+impl Har {
+    /// The entries for a single attempt: one per redirect hop, plus the final one.
+    pub fn entries(
+        &self,
+        response: &Result<ProcessedResponse, reqwest::Error>,
+        exchange: &Exchange,
+    ) -> Vec<Value> {
+        let http_version = match response {
+            Ok(resp) => format!("{:?}", resp.version),
+            Err(_) => "HTTP/1.1".to_string(),
+        };
+        let mut entries = vec![];
+        let mut method = exchange.method.clone();
+        let mut url = exchange.url.clone();
+        let mut since = exchange.start;
+        for hop in &exchange.redirects {
+            let request = if entries.is_empty() {
+                self.request(exchange, &http_version)
+            } else {
+                redirected_request(&method, &url, &http_version)
+            };
+            entries.push(json!({
+                "startedDateTime": iso8601(at(exchange, since)),
+                "time": millis(hop.time - since),
+                "request": request,
+                "response": {
+                    "status": hop.status.as_u16(),
+                    "statusText": hop.status.canonical_reason().unwrap_or_default(),
+                    "httpVersion": http_version,
+                    "cookies": [],
+                    "headers": [{"name": "location", "value": hop.location.as_str()}],
+                    "content": {"size": -1, "mimeType": ""},
+                    "redirectURL": hop.location.as_str(),
+                    "headersSize": -1,
+                    "bodySize": -1,
+                    "comment": "Redirect followed internally, only status and location known",
+                },
+                "cache": {},
+                "timings": {"send": 0, "wait": millis(hop.time - since), "receive": 0},
+            }));
+            method = redirect_method(&method, hop.status);
+            url = hop.location.clone();
+            since = hop.time;
+        }
+
+        let request = if entries.is_empty() {
+            self.request(exchange, &http_version)
+        } else {
+            redirected_request(&method, &url, &http_version)
+        };
+        let (response, wait, receive) = match response {
+            Ok(resp) => (
+                self.response(resp, &http_version),
+                // The time to the headers includes the redirects.
+                (exchange.start + resp.time_headers).saturating_duration_since(since),
+                resp.time_body.unwrap_or_default(),
+            ),
+            Err(err) => (
+                json!({
+                    "status": 0,
+                    "statusText": "",
+                    "httpVersion": "",
+                    "cookies": [],
+                    "headers": [],
+                    "content": {"size": 0, "mimeType": ""},
+                    "redirectURL": "",
+                    "headersSize": -1,
+                    "bodySize": -1,
+                    "_error": err.to_string(),
+                }),
+                Duration::ZERO,
+                Duration::ZERO,
+            ),
+        };
+        entries.push(json!({
+            "startedDateTime": iso8601(at(exchange, since)),
+            "time": millis(wait + receive),
+            "request": request,
+            "response": response,
+            "cache": {},
+            "timings": {"send": 0, "wait": millis(wait), "receive": millis(receive)},
+        }));
+        entries
+    }
+
+    /// Write the HAR file, replacing it atomically.
+    pub fn save(&self, entries: Vec<Value>) -> AnyhowResult<()> {
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": {"name": "check_httpv2", "version": env!("CARGO_PKG_VERSION")},
+                "entries": entries,
+            }
+        });
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&har)?)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    fn request(&self, exchange: &Exchange, http_version: &str) -> Value {
+        let mut request = initial_request(&exchange.method, &exchange.url, http_version);
+        request["headers"] = headers(&exchange.headers);
+        if let Some(body) = &exchange.body {
+            request["bodySize"] = json!(body.len());
+            request["postData"] = json!({
+                "mimeType": header_text(&exchange.headers, CONTENT_TYPE.as_str()),
+                "text": self.truncate(body).0,
+            });
+        }
+        request
+    }
+
+    fn response(&self, response: &ProcessedResponse, http_version: &str) -> Value {
+        let mut content = json!({
+            "size": -1,
+            "mimeType": header_text(&response.headers, CONTENT_TYPE.as_str()),
+        });
+        match &response.body {
+            Some(Ok(body)) => {
+                let (text, truncated) = self.truncate(&body.text);
+                content["size"] = json!(body.length);
+                content["text"] = json!(text);
+                if truncated {
+                    content["comment"] =
+                        json!(format!("Truncated to {} bytes", self.max_body_size));
+                }
+            }
+            Some(Err(err)) => content["comment"] = json!(format!("Body not readable: {}", err)),
+            None => content["comment"] = json!("Body not fetched"),
+        }
+        json!({
+            "status": response.status.as_u16(),
+            "statusText": response.status.canonical_reason().unwrap_or_default(),
+            "httpVersion": http_version,
+            "cookies": [],
+            "headers": headers(&response.headers),
+            "content": content,
+            "redirectURL": response.redirect_target.as_ref().map(Url::as_str).unwrap_or_default(),
+            "headersSize": -1,
+            "bodySize": match &response.body {
+                Some(Ok(body)) => json!(body.length),
+                _ => json!(-1),
+            },
+        })
+    }
+
+    fn truncate<'a>(&self, text: &'a str) -> (&'a str, bool) {
+        if text.len() <= self.max_body_size {
+            return (text, false);
+        }
+        let mut end = self.max_body_size;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        (&text[..end], true)
+    }
+}
+
+// We only know the headers of the request we started with.
+fn redirected_request(method: &Method, url: &Url, http_version: &str) -> Value {
+    let mut request = initial_request(method, url, http_version);
+    request["comment"] = json!("Request after a redirect, headers unknown");
+    request
+}
+
+fn initial_request(method: &Method, url: &Url, http_version: &str) -> Value {
+    json!({
+        "method": method.as_str(),
+        "url": url.as_str(),
+        "httpVersion": http_version,
+        "cookies": [],
+        "headers": [],
+        "queryString": url
+            .query_pairs()
+            .map(|(name, value)| json!({"name": name, "value": value}))
+            .collect::<Vec<_>>(),
+        "headersSize": -1,
+        "bodySize": -1,
+    })
+}
+
+// Same as reqwest: 301, 302 and 303 turn the request into a GET (and drop the body).
+fn redirect_method(method: &Method, status: StatusCode) -> Method {
+    match status {
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER
+            if *method != Method::HEAD =>
+        {
+            Method::GET
+        }
+        _ => method.clone(),
+    }
+}
+
+fn headers(headers: &HeaderMap) -> Value {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.as_str(),
+                "value": if value.is_sensitive() || SENSITIVE_HEADERS.contains(name) {
+                    HIDDEN.to_string()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).to_string()
+                },
+            })
+        })
+        .collect()
+}
+
+fn header_text(headers: &HeaderMap, name: &str) -> String {
+    headers
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string())
+        .unwrap_or_default()
+}
+
+fn at(exchange: &Exchange, instant: Instant) -> SystemTime {
+    exchange.started + instant.saturating_duration_since(exchange.start)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+/// Format as ISO 8601 (UTC, with milliseconds) as required by HAR.
+fn iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Body, RedirectHop};
+    use reqwest::{header::HeaderValue, Version};
+
+    fn har(max_body_size: usize) -> Har {
+        Har {
+            path: PathBuf::from("/tmp/check.har"),
+            max_body_size,
+        }
+    }
+
+    fn exchange() -> Exchange {
+        let mut headers = HeaderMap::new();
+        let mut auth = HeaderValue::from_static("Basic c2VjcmV0");
+        auth.set_sensitive(true);
+        headers.insert("authorization", auth);
+        headers.insert("x-custom", HeaderValue::from_static("foo"));
+        // Like from --header, so not marked as sensitive
+        headers.insert("cookie", HeaderValue::from_static("session=secret"));
+        headers.insert(
+            "proxy-authorization",
+            HeaderValue::from_static("Basic c2VjcmV0"),
+        );
+        Exchange {
+            started: UNIX_EPOCH,
+            start: Instant::now(),
+            method: Method::POST,
+            url: Url::parse("https://example.com/login?next=home").unwrap(),
+            headers,
+            body: Some("user=me".to_string()),
+            redirects: vec![],
+        }
+    }
+
+    fn response(exchange: &Exchange, text: &str) -> ProcessedResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert("set-cookie", HeaderValue::from_static("session=secret"));
+        ProcessedResponse {
+            version: Version::HTTP_11,
+            status: StatusCode::OK,
+            headers,
+            body: Some(Ok(Body {
+                text: text.to_string(),
+                length: text.len(),
+            })),
+            final_url: exchange.url.clone(),
+            redirect_target: None,
+            tls_info: None,
+            time_headers: Duration::from_millis(300),
+            headers_received: exchange.started + Duration::from_millis(300),
+            time_body: Some(Duration::from_millis(20)),
+            proxy_connect: None,
+            handshake: None,
+        }
+    }
+
+    #[test]
+    fn test_single_entry() {
+        let exchange = exchange();
+        let entries = har(100).entries(&Ok(response(&exchange, "hello")), &exchange);
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry["startedDateTime"], "1970-01-01T00:00:00.000Z");
+        assert_eq!(entry["time"], 320.);
+        assert_eq!(entry["request"]["method"], "POST");
+        assert_eq!(
+            entry["request"]["headers"],
+            json!([
+                {"name": "authorization", "value": "<hidden>"},
+                {"name": "x-custom", "value": "foo"},
+                {"name": "cookie", "value": "<hidden>"},
+                {"name": "proxy-authorization", "value": "<hidden>"},
+            ])
+        );
+        assert_eq!(
+            entry["request"]["queryString"],
+            json!([{"name": "next", "value": "home"}])
+        );
+        assert_eq!(entry["request"]["postData"]["text"], "user=me");
+        assert_eq!(entry["response"]["status"], 200);
+        assert_eq!(
+            entry["response"]["headers"],
+            json!([{"name": "set-cookie", "value": "<hidden>"}])
+        );
+        assert_eq!(entry["response"]["content"]["text"], "hello");
+        assert_eq!(entry["response"]["content"].get("comment"), None);
+        assert_eq!(entry["request"].get("comment"), None);
+    }
+
+    #[test]
+    fn test_redirects() {
+        let mut exchange = exchange();
+        let target = Url::parse("https://example.com/home").unwrap();
+        exchange.redirects.push(RedirectHop {
+            url: exchange.url.clone(),
+            status: StatusCode::FOUND,
+            location: target.clone(),
+            time: exchange.start + Duration::from_millis(100),
+        });
+        let entries = har(100).entries(&Ok(response(&exchange, "hello")), &exchange);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["time"], 100.);
+        assert_eq!(entries[0]["response"]["status"], 302);
+        assert_eq!(entries[0]["response"]["redirectURL"], target.as_str());
+        assert_eq!(entries[1]["startedDateTime"], "1970-01-01T00:00:00.100Z");
+        assert_eq!(entries[1]["request"]["method"], "GET");
+        assert_eq!(entries[1]["request"]["url"], target.as_str());
+        assert_eq!(entries[1]["request"]["headers"], json!([]));
+        assert_eq!(
+            entries[1]["request"]["comment"],
+            "Request after a redirect, headers unknown"
+        );
+        assert_eq!(entries[1]["timings"]["wait"], 200.);
+    }
+
+    #[test]
+    fn test_truncated_body() {
+        let exchange = exchange();
+        let entries = har(2).entries(&Ok(response(&exchange, "hällo")), &exchange);
+        let content = &entries[0]["response"]["content"];
+        assert_eq!(content["text"], "h");
+        assert_eq!(content["size"], 6);
+        assert_eq!(content["comment"], "Truncated to 2 bytes");
+    }
+
+    #[test]
+    fn test_iso8601() {
+        assert_eq!(iso8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            iso8601(UNIX_EPOCH + Duration::from_millis(1_709_210_096_789)),
+            "2024-02-29T12:34:56.789Z"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use anyhow::anyhow;
+use reqwest::Url;
+use scraper::{Html, Selector};
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::str::FromStr;
+
+// Elements and attributes we follow when scanning a page for links.
+const LINK_ATTRIBUTES: [(&str, &str); 4] = [
+    ("a[href]", "href"),
+    ("link[href]", "href"),
+    ("img[src]", "src"),
+    ("script[src]", "src"),
+];
+
+// scraper::Selector doesn't give us back the original pattern in a readable form,
+// so we keep it for the check output.
+#[derive(Clone, Debug)]
+pub struct CssSelector {
+    pattern: String,
+    selector: Selector,
+}
+
+impl FromStr for CssSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            pattern: s.to_string(),
+            selector: Selector::parse(s)
+                .map_err(|err| anyhow!("Invalid CSS selector: {} ({})", s, err))?,
+        })
+    }
+}
+
+impl Display for CssSelector {
+    fn fmt(&self, f: &mut Formatter) -> FormatResult {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+// Note: scraper::Html is neither Send nor Sync, so we don't hand out the parsed document
+// and only ever return owned data. This way, callers can safely use the results in async code.
+pub struct Document(Html);
+
+impl Document {
+    pub fn parse(text: &str) -> Self {
+        Self(Html::parse_document(text))
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.0
+            .select(&Selector::parse("title").unwrap())
+            .next()
+            .map(|title| normalize_whitespace(&title.text().collect::<String>()))
+    }
+
+    pub fn count(&self, selector: &CssSelector) -> usize {
+        self.0.select(&selector.selector).count()
+    }
+
+    /// Text content of all elements matching the selector, with whitespace normalized.
+    pub fn texts(&self, selector: &CssSelector) -> Vec<String> {
+        self.0
+            .select(&selector.selector)
+            .map(|element| normalize_whitespace(&element.text().collect::<String>()))
+            .collect()
+    }
+
+    /// All links pointing to the same origin as base, resolved and without fragment.
+    pub fn same_origin_links(&self, base: &Url) -> Vec<Url> {
+        let mut links: Vec<Url> = vec![];
+        for (selector, attribute) in LINK_ATTRIBUTES {
+            for element in self.0.select(&Selector::parse(selector).unwrap()) {
+                let Some(Ok(mut url)) = element.value().attr(attribute).map(|l| base.join(l))
+                else {
+                    continue;
+                };
+                url.set_fragment(None);
+                if url.origin() == base.origin() && !links.contains(&url) {
+                    links.push(url);
+                }
+            }
+        }
+        links
+    }
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>
+      My   Page
+    </title>
+    <link rel="stylesheet" href="/style.css">
+  </head>
+  <body>
+    <h1 class="headline">Welcome <em>home</em></h1>
+    <a href="about.html#team">About</a>
+    <a href="/about.html">About again</a>
+    <a href="https://example.org/">Elsewhere</a>
+    <a href="mailto:info@example.com">Mail</a>
+    <img src="//example.com/logo.png">
+  </body>
+</html>"#;
+
+    fn selector(pattern: &str) -> CssSelector {
+        CssSelector::from_str(pattern).unwrap()
+    }
+
+    #[test]
+    fn test_invalid_selector() {
+        assert!(CssSelector::from_str("h1[").is_err());
+    }
+
+    #[test]
+    fn test_title() {
+        assert_eq!(Document::parse(PAGE).title(), Some("My Page".to_string()));
+        assert_eq!(Document::parse("<p>no title</p>").title(), None);
+    }
+
+    #[test]
+    fn test_count() {
+        let doc = Document::parse(PAGE);
+        assert_eq!(doc.count(&selector("a")), 4);
+        assert_eq!(doc.count(&selector("h1.headline > em")), 1);
+        assert_eq!(doc.count(&selector("table")), 0);
+    }
+
+    #[test]
+    fn test_texts() {
+        assert_eq!(
+            Document::parse(PAGE).texts(&selector("h1")),
+            vec!["Welcome home".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_same_origin_links() {
+        let base = Url::parse("https://example.com/dir/index.html").unwrap();
+        assert_eq!(
+            Document::parse(PAGE).same_origin_links(&base),
+            vec![
+                Url::parse("https://example.com/dir/about.html").unwrap(),
+                Url::parse("https://example.com/about.html").unwrap(),
+                Url::parse("https://example.com/style.css").unwrap(),
+                Url::parse("https://example.com/logo.png").unwrap(),
+            ]
+        );
+    }
+}
@@ -1,14 +1,278 @@
-pub use client::{ClientConfig, ForceIP, OnRedirect};
-pub use request::{Body, ProcessedResponse, RequestConfig, Server};
+pub use client::{ClientConfig, ForceIP, OnRedirect, RedirectHop};
+pub use handshake::{Handshake, OcspStapling};
+pub use proxy::{NoProxyList, PacScript, ProxyConnect};
+pub use request::{Body, Exchange, ProcessedResponse, RequestConfig, Server};
+
+use crate::html::Document;
+use mime::Mime;
+use reqwest::{
+    header::{HeaderValue, CONTENT_TYPE},
+    Method, StatusCode, Url,
+};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::{timeout_at, Instant};
 
 mod client;
+mod handshake;
+mod proxy;
 mod request;
 
+pub struct LinkScan {
+    pub max_depth: usize,
+    pub max_links: usize,
+    pub max_parallel: usize,
+    pub time_limit: Duration,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum RetryOn {
+    Connect,
+    Timeout,
+    ServerError,
+}
+
+pub struct Retry {
+    pub retries: usize,
+    pub delay: Duration,
+    pub on: Vec<RetryOn>,
+    /// Time limit for all attempts together, including the delays
+    pub time_limit: Duration,
+}
+
+impl Retry {
+    pub fn applies_to(&self, response: &Result<ProcessedResponse, reqwest::Error>) -> bool {
+        let outcome = match response {
+            Err(err) if err.is_timeout() => RetryOn::Timeout,
+            Err(err) if err.is_connect() || is_connection_reset(err) => RetryOn::Connect,
+            Ok(resp) if resp.status.is_server_error() => RetryOn::ServerError,
+            _ => return false,
+        };
+        self.on.contains(&outcome)
+    }
+
+    /// Delay before the given retry (starting at 1), doubling with every retry.
+    pub fn delay(&self, retry: usize) -> Duration {
+        self.delay
+            .saturating_mul(2_u32.saturating_pow(retry.saturating_sub(1) as u32))
+    }
+}
+
+// A connection reset by the peer only shows as I/O error somewhere down the sources.
+fn is_connection_reset(err: &reqwest::Error) -> bool {
+    let mut source = err.source();
+    while let Some(err) = source {
+        if err
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::ConnectionReset)
+        {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+pub struct LinkStatus {
+    pub url: Url,
+    pub status: Result<StatusCode, reqwest::Error>,
+}
+
+pub struct LinkScanResult {
+    pub statuses: Vec<LinkStatus>,
+    pub time_limit_reached: bool,
+}
+
 pub async fn perform_request(
     client_cfg: ClientConfig,
     request_cfg: RequestConfig,
 ) -> Result<ProcessedResponse, reqwest::Error> {
-    let client = client::ClientAdapter::new(client_cfg)?;
-    let response = request::send(client, request_cfg).await?;
-    Ok(response)
+    perform_recorded_request(client_cfg, request_cfg).await.0
+}
+
+/// Like perform_request, but also returns what we sent, as far as we got to send anything.
+pub async fn perform_recorded_request(
+    client_cfg: ClientConfig,
+    request_cfg: RequestConfig,
+) -> (Result<ProcessedResponse, reqwest::Error>, Option<Exchange>) {
+    let request_cfg = with_sni_name(&client_cfg, request_cfg);
+
+    let handshake_probe = client_cfg
+        .collect_handshake_info
+        .then(|| handshake::HandshakeProbe::new(&client_cfg));
+    let sni_name = client_cfg.sni_name.clone();
+    let measure_proxy_connect = client_cfg.measure_proxy_connect;
+    let original_host = client_cfg.url.host_str().map(str::to_string);
+
+    let client = match client::ClientAdapter::new(client_cfg) {
+        Ok(client) => client,
+        Err(err) => return (Err(err), None),
+    };
+    let (response, exchange) = request::send_recorded(&client, request_cfg).await;
+    let mut response = match response {
+        Ok(response) => response,
+        Err(err) => return (Err(err), exchange),
+    };
+    if sni_name.is_some() && response.final_url.host_str() == sni_name.as_deref() {
+        // Unless we got redirected elsewhere, we're still talking to the original host.
+        let _ = response.final_url.set_host(original_host.as_deref());
+    }
+    if measure_proxy_connect {
+        response.proxy_connect = client
+            .connect_relay
+            .as_ref()
+            .and_then(|relay| relay.take_connect());
+    }
+    if let Some(probe) = handshake_probe {
+        response.handshake = probe.run(&response.final_url).await;
+    }
+    (Ok(response), exchange)
+}
+
+// reqwest takes the SNI name from the URL, so we request the SNI name instead. The client
+// resolves it to the actual target, and tells proxies about the actual target instead.
+// The Host header still names the original host.
+fn with_sni_name(client_cfg: &ClientConfig, mut request_cfg: RequestConfig) -> RequestConfig {
+    let Some(sni_name) = &client_cfg.sni_name else {
+        return request_cfg;
+    };
+    if request_cfg.url.scheme() != "https"
+        || request_cfg.url.host_str() != client_cfg.url.host_str()
+    {
+        return request_cfg;
+    }
+    let mut url = request_cfg.url.clone();
+    if url.set_host(Some(sni_name)).is_err() {
+        return request_cfg;
+    }
+    request_cfg.url = url;
+    if request_cfg.host_header.is_none() {
+        request_cfg.host_header = authority(&client_cfg.url);
+    }
+    request_cfg
+}
+
+fn authority(url: &Url) -> Option<HeaderValue> {
+    let host = url.host_str()?;
+    let authority = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    HeaderValue::from_str(&authority).ok()
+}
+
+// Breadth-first scan of the given links. Links found on the pages of the current level are
+// only followed if max_depth allows it, and never more than max_links are fetched in total.
+// Up to max_parallel links are fetched concurrently. Once the time limit is reached, the
+// links still pending are dropped, and the scan reports what it got so far.
+pub async fn scan_links(
+    client_cfg: ClientConfig,
+    request_cfg: RequestConfig,
+    origin: Url,
+    links: Vec<Url>,
+    scan: LinkScan,
+) -> Result<LinkScanResult, reqwest::Error> {
+    let deadline = Instant::now() + scan.time_limit;
+    let client = Arc::new(client::ClientAdapter::new(client_cfg.clone())?);
+    let mut visited = vec![origin];
+    visited.extend(links.iter().cloned());
+
+    let mut statuses: Vec<LinkStatus> = vec![];
+    let mut current_level = links;
+    let mut depth = 1;
+    while !current_level.is_empty() && depth <= scan.max_depth {
+        let follow = depth < scan.max_depth;
+        current_level.truncate(scan.max_links - statuses.len());
+        let mut pending = current_level.into_iter().enumerate();
+        let mut running = JoinSet::new();
+        let mut level_results = vec![];
+        loop {
+            while running.len() < scan.max_parallel {
+                let Some((index, url)) = pending.next() else {
+                    break;
+                };
+                let link_cfg = RequestConfig {
+                    url: url.clone(),
+                    method: Method::GET,
+                    body: None,
+                    content_type: None,
+                    without_body: !follow,
+                    ..request_cfg.clone()
+                };
+                let link_cfg = with_sni_name(&client_cfg, link_cfg);
+                let client = client.clone();
+                running.spawn(async move {
+                    let response = request::send(&client, link_cfg).await;
+                    (index, url, response)
+                });
+            }
+            match timeout_at(deadline, running.join_next()).await {
+                // Nothing is left to fetch on this level.
+                Ok(None) => break,
+                Ok(Some(Ok(result))) => level_results.push(result),
+                // Link requests don't panic, and we never cancel them while running.
+                Ok(Some(Err(_))) => {}
+                // Dropping the JoinSet aborts the requests still running.
+                Err(_) => {
+                    statuses.extend(in_order(level_results).map(|(url, response)| LinkStatus {
+                        url,
+                        status: response.map(|response| response.status),
+                    }));
+                    return Ok(LinkScanResult {
+                        statuses,
+                        time_limit_reached: true,
+                    });
+                }
+            }
+        }
+
+        let mut next_level: Vec<Url> = vec![];
+        for (url, response) in in_order(level_results) {
+            if let (true, Ok(response)) = (follow, &response) {
+                for link in html_links(response) {
+                    if !visited.contains(&link) {
+                        visited.push(link.clone());
+                        next_level.push(link);
+                    }
+                }
+            }
+            statuses.push(LinkStatus {
+                url,
+                status: response.map(|response| response.status),
+            });
+        }
+        current_level = next_level;
+        depth += 1;
+    }
+    Ok(LinkScanResult {
+        statuses,
+        time_limit_reached: false,
+    })
+}
+
+// Concurrent requests finish in any order, but we report the links in the order of the page.
+fn in_order(
+    mut results: Vec<(usize, Url, Result<ProcessedResponse, reqwest::Error>)>,
+) -> impl Iterator<Item = (Url, Result<ProcessedResponse, reqwest::Error>)> {
+    results.sort_by_key(|(index, _, _)| *index);
+    results
+        .into_iter()
+        .map(|(_, url, response)| (url, response))
+}
+
+pub fn html_links(response: &ProcessedResponse) -> Vec<Url> {
+    let is_html = response
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Mime>().ok())
+        .is_some_and(|mime| mime.subtype() == mime::HTML);
+    match response.body.as_ref() {
+        Some(Ok(body)) if is_html => {
+            Document::parse(&body.text).same_origin_links(&response.final_url)
+        }
+        _ => vec![],
+    }
 }
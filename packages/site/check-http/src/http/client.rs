@@ -2,13 +2,15 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
+use crate::http::proxy::{ConnectRelay, NoProxyList, PacScript, ProxyResolver};
 use crate::http::Server;
 use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
     redirect::{Action, Attempt, Policy},
     tls::Version as TlsVersion,
-    Client, Proxy, Result as ReqwestResult, Url, Version,
+    Client, Proxy, Result as ReqwestResult, StatusCode, Url, Version,
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     sync::{Arc, Mutex},
@@ -29,6 +31,8 @@ pub enum ForceIP {
     Ipv4,
     Ipv6,
 }
+
+#[derive(Clone)]
 pub struct ClientConfig {
     pub version: Option<Version>,
     pub user_agent: String,
@@ -39,57 +43,130 @@ pub struct ClientConfig {
     pub min_tls_version: Option<TlsVersion>,
     pub max_tls_version: Option<TlsVersion>,
     pub collect_tls_info: bool,
+    pub collect_handshake_info: bool,
+    pub measure_proxy_connect: bool,
     pub ignore_proxy_env: bool,
     pub proxy_url: Option<String>,
     pub proxy_auth: Option<(String, String)>,
+    pub proxy_pac: Option<PacScript>,
+    pub no_proxy: Option<NoProxyList>,
     pub disable_certificate_verification: bool,
     pub url: Url,
     pub server: Option<Server>,
+    pub sni_name: Option<String>,
+    pub disable_sni: bool,
+}
+
+/// A redirect response, whether we followed it or not
+#[derive(Clone)]
+pub struct RedirectHop {
+    pub url: Url,
+    pub status: StatusCode,
+    pub location: Url,
+    pub time: Instant,
 }
 
 pub struct ClientAdapter {
     pub client: Client,
     pub redirect_recorder: Arc<Mutex<Option<Url>>>,
+    pub redirect_hops: Arc<Mutex<Vec<RedirectHop>>>,
+    pub connect_relay: Option<Arc<ConnectRelay>>,
 }
 
 impl ClientAdapter {
     pub fn new(cfg: ClientConfig) -> ReqwestResult<Self> {
         let redirect_recorder = Arc::new(Mutex::<Option<Url>>::new(None));
+        let redirect_hops = Arc::new(Mutex::<Vec<RedirectHop>>::new(vec![]));
+        // Besides measuring the CONNECT, the relay tells proxies about the actual target
+        // instead of the SNI name.
+        let connect_relay = (cfg.measure_proxy_connect || cfg.sni_name.is_some())
+            .then(|| Arc::new(ConnectRelay::default()));
         Ok(Self {
-            client: build(cfg, redirect_recorder.clone())?,
+            client: build(
+                cfg,
+                redirect_recorder.clone(),
+                redirect_hops.clone(),
+                connect_relay.clone(),
+            )?,
             redirect_recorder,
+            redirect_hops,
+            connect_relay,
         })
     }
 }
 
-fn build(cfg: ClientConfig, record_redirect: Arc<Mutex<Option<Url>>>) -> ReqwestResult<Client> {
+fn build(
+    cfg: ClientConfig,
+    record_redirect: Arc<Mutex<Option<Url>>>,
+    record_hops: Arc<Mutex<Vec<RedirectHop>>>,
+    connect_relay: Option<Arc<ConnectRelay>>,
+) -> ReqwestResult<Client> {
     let client = reqwest::Client::builder()
         .danger_accept_invalid_certs(cfg.disable_certificate_verification);
 
-    let client = if let Some(server) = cfg.server {
-        let port = cfg.url.port().unwrap_or_else(|| match cfg.url.scheme() {
-            "http" => 80,
-            "https" => 443,
-            _ => panic!("Unsupported URL scheme"),
-        });
-        let server_socket_addr = server.to_socket_addr(port).unwrap();
-        let domain = cfg.url.domain().unwrap_or_default();
-        client.resolve(domain, server_socket_addr)
-    } else {
-        client
-    };
-
-    let client = if cfg.ignore_proxy_env {
-        client.no_proxy()
-    } else {
-        client
-    };
-
-    let client = if let Some(proxy) = get_proxy(cfg.proxy_url, cfg.proxy_auth) {
-        client.proxy(proxy?)
-    } else {
-        client
+    // With a custom SNI name, we request the SNI name (see with_sni_name) and resolve it
+    // to the server or to the host given in the URL.
+    let alias = match (&cfg.server, &cfg.sni_name) {
+        (Some(server), sni_name) => sni_name
+            .clone()
+            .or(cfg.url.domain().map(str::to_string))
+            .map(|name| (name, server.clone())),
+        (None, Some(sni_name)) => Some((
+            sni_name.clone(),
+            match cfg
+                .url
+                .host_str()
+                .unwrap_or_default()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+            {
+                Ok(ip) => Server::IpAddr(ip),
+                Err(_) => Server::Url(cfg.url.clone()),
+            },
+        )),
+        (None, None) => None,
     };
+    // Proxies get to see the host of the URL instead of the SNI name.
+    let sni_host = cfg
+        .sni_name
+        .clone()
+        .zip(cfg.url.host_str().map(str::to_string));
+    let client = client.dns_resolver(Arc::new(HostResolver {
+        alias,
+        force_ip: cfg.force_ip.clone(),
+    }));
+
+    if let Some(proxy_url) = &cfg.proxy_url {
+        // Fail early on an invalid proxy URL instead of silently connecting directly
+        Proxy::all(proxy_url)?;
+    }
+    // We decide on the proxy per request, which also replaces reqwest's handling of the
+    // proxy environment variables.
+    let resolver = proxy_resolver(&cfg);
+    let measure_proxy_connect = cfg.measure_proxy_connect;
+    // Like browsers, we fall back to a direct connection if the PAC script fails.
+    let proxy = Proxy::custom(move |url| {
+        let target = match &sni_host {
+            Some((sni_name, host)) if url.host_str() == Some(sni_name) => {
+                let mut target = url.clone();
+                target.set_host(Some(host)).ok()?;
+                target
+            }
+            _ => url.clone(),
+        };
+        let proxy = resolver.resolve(&target).ok().flatten()?;
+        Some(match &connect_relay {
+            Some(relay) if measure_proxy_connect || target != *url => {
+                relay.route(url, &target, proxy)
+            }
+            _ => proxy,
+        })
+    });
+    let client = client.proxy(match &cfg.proxy_auth {
+        Some((proxy_user, proxy_pw)) => proxy.basic_auth(proxy_user, proxy_pw),
+        None => proxy,
+    });
 
     let client = if let Some(version) = cfg.min_tls_version {
         match version {
@@ -131,34 +208,77 @@ fn build(cfg: ClientConfig, record_redirect: Arc<Mutex<Option<Url>>>) -> Reqwest
         },
     };
 
+    let policy = get_policy(
+        cfg.onredirect,
+        cfg.max_redirs,
+        cfg.force_ip,
+        record_redirect,
+    );
     client
         .timeout(cfg.timeout)
         .user_agent(cfg.user_agent)
-        .redirect(get_policy(
-            cfg.onredirect,
-            cfg.max_redirs,
-            cfg.force_ip,
-            record_redirect,
-        ))
+        // reqwest doesn't hand out the intermediate responses of redirects, so we note down
+        // what the policy gets to see.
+        .redirect(Policy::custom(move |attempt| {
+            record_hops.lock().unwrap().push(RedirectHop {
+                // There's always at least the URL we started with.
+                url: attempt.previous().last().unwrap().clone(),
+                status: attempt.status(),
+                location: attempt.url().clone(),
+                time: Instant::now(),
+            });
+            policy.redirect(attempt)
+        }))
         .tls_info(cfg.collect_tls_info)
+        .tls_sni(!cfg.disable_sni)
         .build()
 }
 
-fn get_proxy(
-    proxy_url: Option<String>,
-    proxy_auth: Option<(String, String)>,
-) -> Option<ReqwestResult<Proxy>> {
-    let proxy_url = proxy_url?;
-
-    let proxy = Proxy::all(proxy_url);
+/// Resolves host names just like reqwest, but the alias (the SNI name, or the host of the URL
+/// if only --server is given) resolves to the given server.
+/// All addresses are filtered by the forced IP version.
+struct HostResolver {
+    alias: Option<(String, Server)>,
+    force_ip: Option<ForceIP>,
+}
 
-    if let Some((proxy_user, proxy_pw)) = proxy_auth {
-        Some(proxy.map(|p| p.basic_auth(&proxy_user, &proxy_pw)))
-    } else {
-        Some(proxy)
+impl Resolve for HostResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = match &self.alias {
+            Some((alias, server)) if alias.eq_ignore_ascii_case(name.as_str()) => match server {
+                Server::IpAddr(ip) => ip.to_string(),
+                Server::Url(url) => url
+                    .host_str()
+                    .unwrap_or_default()
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string(),
+            },
+            _ => name.as_str().to_string(),
+        };
+        let force_ip = self.force_ip.clone();
+        Box::pin(async move {
+            let addrs = filter_socket_addrs(
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect(),
+                force_ip,
+            );
+            if addrs.is_empty() {
+                return Err(format!("No matching address for {}", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
     }
 }
 
+pub fn proxy_resolver(cfg: &ClientConfig) -> ProxyResolver {
+    ProxyResolver::new(
+        cfg.proxy_url.clone(),
+        cfg.proxy_pac.clone(),
+        !cfg.ignore_proxy_env,
+        cfg.no_proxy.clone(),
+    )
+}
+
 fn get_policy(
     onredirect: OnRedirect,
     max_redirs: usize,
@@ -0,0 +1,383 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// reqwest only hands out the peer certificate of a connection, so we get the remaining
+// session parameters from a separate handshake using OpenSSL.
+
+use super::client::{self, ClientConfig, ForceIP};
+use super::proxy::ProxyResolver;
+use super::Server;
+use anyhow::{anyhow, Result as AnyhowResult};
+use openssl::hash::MessageDigest;
+use openssl::ocsp::{OcspCertId, OcspCertStatus, OcspFlag, OcspResponse, OcspResponseStatus};
+use openssl::ssl::{SslConnector, SslMethod, SslRef, SslVerifyMode, SslVersion, StatusType};
+use openssl::stack::{Stack, StackRef};
+use openssl::x509::store::{X509Store, X509StoreBuilder};
+use openssl::x509::{X509Ref, X509VerifyResult, X509};
+use reqwest::{tls::Version as TlsVersion, Url, Version};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+// Tolerated clock skew for the thisUpdate and nextUpdate of stapled OCSP responses
+const OCSP_MAX_SKEW_SECONDS: u32 = 300;
+
+// Substrings of OpenSSL and IANA cipher suite names we consider broken.
+const WEAK_CIPHER_PARTS: [&str; 8] = ["NULL", "EXP", "RC4", "DES", "MD5", "anon", "ADH", "AECDH"];
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug)]
+pub enum OcspStapling {
+    Missing,
+    Good,
+    Revoked,
+    Unknown,
+    Invalid(String),
+}
+
+#[derive(Debug)]
+pub struct Handshake {
+    pub version: String,
+    pub cipher: String,
+    pub cipher_bits: i32,
+    pub alpn: Option<String>,
+    pub ocsp_stapling: OcspStapling,
+}
+
+impl Handshake {
+    pub fn weak_cipher(&self) -> bool {
+        self.cipher_bits < 128 || WEAK_CIPHER_PARTS.iter().any(|w| self.cipher.contains(w))
+    }
+}
+
+#[derive(Clone)]
+pub struct HandshakeProbe {
+    url: Url,
+    server: Option<Server>,
+    force_ip: Option<ForceIP>,
+    min_version: Option<SslVersion>,
+    max_version: Option<SslVersion>,
+    alpn: Vec<u8>,
+    timeout: Duration,
+    sni_name: Option<String>,
+    disable_sni: bool,
+    verify_certificates: bool,
+    proxy_resolver: ProxyResolver,
+}
+
+impl HandshakeProbe {
+    pub fn new(cfg: &ClientConfig) -> Self {
+        // ALPN protocols in wire format, offered like reqwest does.
+        let alpn = match cfg.version {
+            Some(Version::HTTP_11) => b"\x08http/1.1".to_vec(),
+            Some(Version::HTTP_2) => b"\x02h2".to_vec(),
+            _ => b"\x02h2\x08http/1.1".to_vec(),
+        };
+        Self {
+            url: cfg.url.clone(),
+            server: cfg.server.clone(),
+            force_ip: cfg.force_ip.clone(),
+            min_version: cfg.min_tls_version.and_then(map_tls_version),
+            max_version: cfg.max_tls_version.and_then(map_tls_version),
+            alpn,
+            timeout: cfg.timeout,
+            sni_name: cfg.sni_name.clone(),
+            disable_sni: cfg.disable_sni,
+            verify_certificates: !cfg.disable_certificate_verification,
+            proxy_resolver: client::proxy_resolver(cfg),
+        }
+    }
+
+    /// Handshake with the host of target, which should be the final URL of the request.
+    /// This connection is always direct, so we skip it if the request went via proxy:
+    /// It would tell nothing about the connection of the request.
+    pub async fn run(&self, target: &Url) -> Option<AnyhowResult<Handshake>> {
+        // Like the client, we fall back to a direct connection if the PAC script fails.
+        if target.scheme() != "https"
+            || self.proxy_resolver.resolve(target).ok().flatten().is_some()
+        {
+            return None;
+        }
+        let probe = self.clone();
+        let target = target.clone();
+        Some(
+            tokio::task::spawn_blocking(move || probe.handshake(&target))
+                .await
+                .unwrap_or_else(|err| Err(anyhow!(err))),
+        )
+    }
+
+    fn handshake(&self, target: &Url) -> AnyhowResult<Handshake> {
+        let host = match &self.sni_name {
+            Some(sni_name) if target.host_str() == self.url.host_str() => sni_name,
+            _ => target
+                .host_str()
+                .ok_or_else(|| anyhow!("Invalid URL: no host"))?
+                .trim_start_matches('[')
+                .trim_end_matches(']'),
+        };
+        let addr = self.socket_addr(target)?;
+        let stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        // Certificate verification is done (or deliberately skipped) by the actual request.
+        let mut builder = SslConnector::builder(SslMethod::tls_client())?;
+        builder.set_verify(SslVerifyMode::NONE);
+        builder.set_min_proto_version(self.min_version)?;
+        builder.set_max_proto_version(self.max_version)?;
+        builder.set_alpn_protos(&self.alpn)?;
+        let mut config = builder.build().configure()?;
+        config.set_verify_hostname(false);
+        config.set_use_server_name_indication(!self.disable_sni);
+        config.set_status_type(StatusType::OCSP)?;
+        let stream = config
+            .connect(host, stream)
+            .map_err(|err| anyhow!("TLS handshake with {} failed: {}", addr, err))?;
+
+        let ssl = stream.ssl();
+        let cipher = ssl
+            .current_cipher()
+            .ok_or_else(|| anyhow!("No cipher negotiated"))?;
+        Ok(Handshake {
+            version: ssl.version_str().to_string(),
+            cipher: cipher.standard_name().unwrap_or(cipher.name()).to_string(),
+            cipher_bits: cipher.bits().secret,
+            alpn: ssl
+                .selected_alpn_protocol()
+                .map(|alpn| String::from_utf8_lossy(alpn).to_string()),
+            ocsp_stapling: ocsp_stapling(ssl, self.verify_certificates),
+        })
+    }
+
+    fn socket_addr(&self, target: &Url) -> AnyhowResult<SocketAddr> {
+        let port = target.port_or_known_default().unwrap_or(443);
+        // --server only applies to the host given in the URL, not to redirect targets.
+        if let Some(server) = self
+            .server
+            .as_ref()
+            .filter(|_| target.host_str() == self.url.host_str())
+        {
+            return server.to_socket_addr(port);
+        }
+        target
+            .socket_addrs(|| Some(port))?
+            .into_iter()
+            .find(|addr| match self.force_ip {
+                None => true,
+                Some(ForceIP::Ipv4) => addr.is_ipv4(),
+                Some(ForceIP::Ipv6) => addr.is_ipv6(),
+            })
+            .ok_or_else(|| anyhow!("Unable to resolve host: {}", target))
+    }
+}
+
+fn map_tls_version(version: TlsVersion) -> Option<SslVersion> {
+    match version {
+        TlsVersion::TLS_1_0 => Some(SslVersion::TLS1),
+        TlsVersion::TLS_1_1 => Some(SslVersion::TLS1_1),
+        TlsVersion::TLS_1_2 => Some(SslVersion::TLS1_2),
+        TlsVersion::TLS_1_3 => Some(SslVersion::TLS1_3),
+        _ => None,
+    }
+}
+
+fn ocsp_stapling(ssl: &SslRef, verify_certificates: bool) -> OcspStapling {
+    let Some(der) = ssl.ocsp_status() else {
+        return OcspStapling::Missing;
+    };
+    let status = (|| {
+        let cert = ssl
+            .peer_certificate()
+            .ok_or_else(|| anyhow!("no peer certificate"))?;
+        let chain = ssl
+            .peer_cert_chain()
+            .ok_or_else(|| anyhow!("no peer certificate chain"))?;
+        let mut trust_store = X509StoreBuilder::new()?;
+        trust_store.set_default_paths()?;
+        verified_status(der, &cert, chain, trust_store.build(), verify_certificates)
+    })();
+    match status {
+        Ok(OcspCertStatus::GOOD) => OcspStapling::Good,
+        Ok(OcspCertStatus::REVOKED) => OcspStapling::Revoked,
+        Ok(_) => OcspStapling::Unknown,
+        Err(err) => OcspStapling::Invalid(err.to_string()),
+    }
+}
+
+// Like the certificate itself, the signer of the response is only verified against the trust
+// store if certificate verification is enabled. The signature is verified in any case.
+fn verified_status(
+    der: &[u8],
+    cert: &X509Ref,
+    chain: &StackRef<X509>,
+    trust_store: X509Store,
+    verify_certificates: bool,
+) -> AnyhowResult<OcspCertStatus> {
+    let response = OcspResponse::from_der(der).map_err(|_| anyhow!("invalid response"))?;
+    if response.status() != OcspResponseStatus::SUCCESSFUL {
+        return Err(anyhow!("responder returned an error"));
+    }
+    let issuer = chain
+        .iter()
+        .find(|candidate| candidate.issued(cert) == X509VerifyResult::OK)
+        .ok_or_else(|| anyhow!("issuer not in certificate chain"))?;
+
+    let basic = response.basic()?;
+    let mut certs = Stack::new()?;
+    for cert in chain {
+        certs.push(cert.to_owned())?;
+    }
+    let flags = if verify_certificates {
+        OcspFlag::empty()
+    } else {
+        OcspFlag::NO_VERIFY
+    };
+    basic
+        .verify(&certs, &trust_store, flags)
+        .map_err(|_| anyhow!("signature verification failed"))?;
+
+    let cert_id = OcspCertId::from_cert(MessageDigest::sha1(), cert, issuer)?;
+    let status = basic
+        .find_status(&cert_id)
+        .ok_or_else(|| anyhow!("response does not cover the certificate"))?;
+    status
+        .check_validity(OCSP_MAX_SKEW_SECONDS, None)
+        .map_err(|_| anyhow!("response outdated"))?;
+    Ok(status.status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake(cipher: &str, bits: i32) -> Handshake {
+        Handshake {
+            version: "TLSv1.2".to_string(),
+            cipher: cipher.to_string(),
+            cipher_bits: bits,
+            alpn: None,
+            ocsp_stapling: OcspStapling::Missing,
+        }
+    }
+
+    #[test]
+    fn test_weak_cipher() {
+        assert!(!handshake("TLS_AES_256_GCM_SHA384", 256).weak_cipher());
+        assert!(!handshake("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256", 128).weak_cipher());
+        assert!(handshake("TLS_RSA_WITH_3DES_EDE_CBC_SHA", 112).weak_cipher());
+        assert!(handshake("TLS_RSA_WITH_RC4_128_SHA", 128).weak_cipher());
+        assert!(handshake("TLS_DH_anon_WITH_AES_128_CBC_SHA", 128).weak_cipher());
+        assert!(handshake("TLS_RSA_WITH_NULL_SHA256", 0).weak_cipher());
+    }
+
+    #[test]
+    fn test_map_tls_version() {
+        assert_eq!(
+            map_tls_version(TlsVersion::TLS_1_2),
+            Some(SslVersion::TLS1_2)
+        );
+        assert_eq!(
+            map_tls_version(TlsVersion::TLS_1_3),
+            Some(SslVersion::TLS1_3)
+        );
+    }
+
+    mod test_verified_status {
+        use super::*;
+
+        // Canned responses of a test CA valid until 2126, see the OCSP tests of check-cert.
+        static CA: &[u8] = include_bytes!("../../assets/ocsp/ca.der");
+        static LEAF: &[u8] = include_bytes!("../../assets/ocsp/leaf.der");
+        static GOOD: &[u8] = include_bytes!("../../assets/ocsp/good.der");
+        static REVOKED: &[u8] = include_bytes!("../../assets/ocsp/revoked.der");
+        static OUTDATED: &[u8] = include_bytes!("../../assets/ocsp/outdated.der");
+
+        fn status(
+            der: &[u8],
+            trusted: bool,
+            verify_certificates: bool,
+        ) -> AnyhowResult<OcspCertStatus> {
+            let ca = X509::from_der(CA).unwrap();
+            let leaf = X509::from_der(LEAF).unwrap();
+            let mut chain = Stack::new().unwrap();
+            chain.push(leaf.clone()).unwrap();
+            chain.push(ca.clone()).unwrap();
+            let mut trust_store = X509StoreBuilder::new().unwrap();
+            if trusted {
+                trust_store.add_cert(ca).unwrap();
+            }
+            verified_status(der, &leaf, &chain, trust_store.build(), verify_certificates)
+        }
+
+        #[test]
+        fn test_good() {
+            assert_eq!(status(GOOD, true, true).unwrap(), OcspCertStatus::GOOD);
+        }
+
+        #[test]
+        fn test_revoked() {
+            assert_eq!(
+                status(REVOKED, true, true).unwrap(),
+                OcspCertStatus::REVOKED
+            );
+        }
+
+        #[test]
+        fn test_outdated() {
+            assert_eq!(
+                status(OUTDATED, true, true).unwrap_err().to_string(),
+                "response outdated"
+            );
+        }
+
+        #[test]
+        fn test_untrusted_signer() {
+            assert_eq!(
+                status(GOOD, false, true).unwrap_err().to_string(),
+                "signature verification failed"
+            );
+            // Without certificate verification, only the signature itself has to be valid.
+            assert_eq!(status(GOOD, false, false).unwrap(), OcspCertStatus::GOOD);
+        }
+
+        #[test]
+        fn test_forged_signature() {
+            // Flip the first byte of the signature value.
+            let mut forged = GOOD.to_vec();
+            let signature = forged
+                .windows(4)
+                .position(|window| window == [0x5c, 0xd4, 0x1e, 0x4f])
+                .unwrap();
+            forged[signature] ^= 0xff;
+            assert_eq!(
+                status(&forged, true, false).unwrap_err().to_string(),
+                "signature verification failed"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skipped_via_proxy() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let probe = HandshakeProbe {
+            url: url.clone(),
+            server: None,
+            force_ip: None,
+            min_version: None,
+            max_version: None,
+            alpn: vec![],
+            timeout: Duration::from_secs(1),
+            sni_name: None,
+            disable_sni: false,
+            verify_certificates: true,
+            proxy_resolver: ProxyResolver::new(
+                Some("http://proxy:3128".to_string()),
+                None,
+                false,
+                None,
+            ),
+        };
+        assert!(probe.run(&url).await.is_none());
+    }
+}
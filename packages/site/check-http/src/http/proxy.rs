@@ -0,0 +1,869 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use anyhow::{anyhow, bail, Result as AnyhowResult};
+use ipnet::IpNet;
+use reqwest::Url;
+use rquickjs::{Context, Ctx, Function, Runtime};
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::task::{block_in_place, JoinHandle};
+
+// The helper functions every PAC script may rely on, see
+// https://developer.mozilla.org/en-US/docs/Web/HTTP/Proxy_servers_and_tunneling/Proxy_Auto-Configuration_PAC_file
+// dnsResolve and myIpAddress need access to the system and are backed by native functions.
+const PAC_PRELUDE: &str = r#"
+function dnsResolve(host) {
+    var ip = __dnsResolve(String(host));
+    return ip == '' ? null : ip;
+}
+
+function myIpAddress() {
+    return __myIpAddress();
+}
+
+function dnsDomainIs(host, domain) {
+    return host.length >= domain.length &&
+        host.substring(host.length - domain.length) == domain;
+}
+
+function dnsDomainLevels(host) {
+    return host.split('.').length - 1;
+}
+
+function isPlainHostName(host) {
+    return host.indexOf('.') == -1;
+}
+
+function localHostOrDomainIs(host, hostdom) {
+    return host == hostdom || hostdom.lastIndexOf(host + '.', 0) == 0;
+}
+
+function isResolvable(host) {
+    return dnsResolve(host) != null;
+}
+
+function __ipToNumber(ip) {
+    var octets = ip.split('.');
+    return ((octets[0] << 24) | (octets[1] << 16) | (octets[2] << 8) | octets[3]) >>> 0;
+}
+
+function isInNet(host, pattern, mask) {
+    var ip = /^\d+\.\d+\.\d+\.\d+$/.test(host) ? host : dnsResolve(host);
+    if (ip == null) {
+        return false;
+    }
+    var m = __ipToNumber(mask);
+    return ((__ipToNumber(ip) & m) >>> 0) == ((__ipToNumber(pattern) & m) >>> 0);
+}
+
+function shExpMatch(str, shexp) {
+    var pattern = shexp
+        .replace(/[.+^${}()|[\]\\]/g, '\\$&')
+        .replace(/\*/g, '.*')
+        .replace(/\?/g, '.');
+    return new RegExp('^' + pattern + '$').test(str);
+}
+
+function __inRange(from, value, to) {
+    return from <= to ? from <= value && value <= to : value >= from || value <= to;
+}
+
+function __splitGmt(args) {
+    args = Array.prototype.slice.call(args);
+    var gmt = args[args.length - 1] == 'GMT';
+    if (gmt) {
+        args.pop();
+    }
+    return { args: args, now: new Date(), gmt: gmt };
+}
+
+var __weekdays = ['SUN', 'MON', 'TUE', 'WED', 'THU', 'FRI', 'SAT'];
+var __months = ['JAN', 'FEB', 'MAR', 'APR', 'MAY', 'JUN', 'JUL', 'AUG', 'SEP', 'OCT', 'NOV', 'DEC'];
+
+function weekdayRange() {
+    var c = __splitGmt(arguments);
+    var today = c.gmt ? c.now.getUTCDay() : c.now.getDay();
+    var from = __weekdays.indexOf(c.args[0]);
+    var to = c.args.length > 1 ? __weekdays.indexOf(c.args[1]) : from;
+    return __inRange(from, today, to);
+}
+
+// Every argument is a day (1-31), a month name or a year. Either one value of each kind is
+// given for the start and the end of the range, or a single value of each kind.
+function dateRange() {
+    var c = __splitGmt(arguments);
+    var current = {
+        day: c.gmt ? c.now.getUTCDate() : c.now.getDate(),
+        month: c.gmt ? c.now.getUTCMonth() : c.now.getMonth(),
+        year: c.gmt ? c.now.getUTCFullYear() : c.now.getFullYear()
+    };
+    function key(values) {
+        var k = 0;
+        for (var i = 0; i < values.length; i++) {
+            var v = values[i];
+            var month = __months.indexOf(v);
+            if (month != -1) {
+                k += month * 100;
+            } else if (v > 31) {
+                k += v * 10000;
+            } else {
+                k += v;
+            }
+        }
+        return k;
+    }
+    function currentKey(values) {
+        var k = 0;
+        for (var i = 0; i < values.length; i++) {
+            var v = values[i];
+            if (__months.indexOf(v) != -1) {
+                k += current.month * 100;
+            } else if (v > 31) {
+                k += current.year * 10000;
+            } else {
+                k += current.day;
+            }
+        }
+        return k;
+    }
+    if (c.args.length == 1) {
+        return key(c.args) == currentKey(c.args);
+    }
+    var half = c.args.length / 2;
+    var start = c.args.slice(0, half);
+    return __inRange(key(start), currentKey(start), key(c.args.slice(half)));
+}
+
+function timeRange() {
+    var c = __splitGmt(arguments);
+    var a = c.args;
+    function seconds(h, m, s) {
+        return h * 3600 + m * 60 + s;
+    }
+    var now = c.gmt
+        ? seconds(c.now.getUTCHours(), c.now.getUTCMinutes(), c.now.getUTCSeconds())
+        : seconds(c.now.getHours(), c.now.getMinutes(), c.now.getSeconds());
+    switch (a.length) {
+        case 1:
+            return __inRange(seconds(a[0], 0, 0), now, seconds(a[0], 59, 59));
+        case 2:
+            return __inRange(seconds(a[0], 0, 0), now, seconds(a[1], 0, 0) - 1);
+        case 4:
+            return __inRange(seconds(a[0], a[1], 0), now, seconds(a[2], a[3], 0) - 1);
+        case 6:
+            return __inRange(seconds(a[0], a[1], a[2]), now, seconds(a[3], a[4], a[5]));
+    }
+    return false;
+}
+"#;
+
+/// A proxy auto-config script, see
+/// https://developer.mozilla.org/en-US/docs/Web/HTTP/Proxy_servers_and_tunneling/Proxy_Auto-Configuration_PAC_file
+#[derive(Clone, Debug)]
+pub struct PacScript {
+    source: String,
+    // Backs dnsResolve, replaceable so that tests don't depend on the DNS.
+    resolve_host: fn(&str) -> Option<IpAddr>,
+    timeout: Duration,
+}
+
+// Evaluating a PAC script must not hold up the request indefinitely.
+const PAC_TIMEOUT: Duration = Duration::from_secs(5);
+const PAC_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+impl FromStr for PacScript {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let script = Self {
+            source: s.to_string(),
+            resolve_host: resolve_ipv4,
+            timeout: PAC_TIMEOUT,
+        };
+        if script.evaluate("typeof FindProxyForURL")? != "function" {
+            bail!("PAC script does not define FindProxyForURL")
+        }
+        Ok(script)
+    }
+}
+
+impl PacScript {
+    /// Evaluates FindProxyForURL for the given URL and returns the first proxy of the result,
+    /// or None for DIRECT.
+    ///
+    /// reqwest asks for the proxy from within the async runtime, while the script may block on
+    /// DNS lookups or take up to PAC_TIMEOUT.
+    pub fn find_proxy(&self, url: &Url) -> AnyhowResult<Option<Url>> {
+        let call = format!(
+            "FindProxyForURL({}, {})",
+            serde_json::Value::from(url.as_str()),
+            serde_json::Value::from(url.host_str().unwrap_or_default())
+        );
+        let find_proxy = || parse_pac_result(&self.evaluate(&call)?);
+        match Handle::try_current() {
+            // Let the runtime hand its other tasks to another worker while we wait.
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                block_in_place(find_proxy)
+            }
+            _ => find_proxy(),
+        }
+    }
+
+    // Evaluates the expression after the script and returns its value as string.
+    // The JS runtime interrupts the evaluation once the timeout has passed, so a runaway
+    // script neither holds up the request nor keeps running in the background.
+    // A runtime is cheap to set up, so we start from scratch for every evaluation.
+    fn evaluate(&self, expression: &str) -> AnyhowResult<String> {
+        let runtime = Runtime::new()?;
+        runtime.set_memory_limit(PAC_MEMORY_LIMIT);
+        let deadline = Instant::now() + self.timeout;
+        runtime.set_interrupt_handler(Some(Box::new(move || Instant::now() >= deadline)));
+        let context = Context::full(&runtime)?;
+        let resolve_host = self.resolve_host;
+        context.with(|ctx| {
+            let globals = ctx.globals();
+            globals.set(
+                "__dnsResolve",
+                Function::new(ctx.clone(), move |host: String| {
+                    resolve_host(&host).map_or(String::new(), |ip| ip.to_string())
+                })?,
+            )?;
+            globals.set("__myIpAddress", Function::new(ctx.clone(), my_ip_address)?)?;
+            for source in [PAC_PRELUDE, &self.source] {
+                ctx.eval::<(), _>(source)
+                    .map_err(|err| anyhow!("Invalid PAC script: {}", js_error(&ctx, err)))?;
+            }
+            ctx.eval::<String, _>(format!("String({})", expression))
+                .map_err(|err| {
+                    if Instant::now() >= deadline {
+                        anyhow!("PAC evaluation timed out")
+                    } else {
+                        anyhow!("PAC evaluation failed: {}", js_error(&ctx, err))
+                    }
+                })
+        })
+    }
+}
+
+// Exceptions are left in the context, the error only tells that there was one.
+fn js_error(ctx: &Ctx<'_>, err: rquickjs::Error) -> String {
+    match err {
+        rquickjs::Error::Exception => {
+            let exception = ctx.catch();
+            match exception.as_exception() {
+                Some(exception) => exception.message().unwrap_or_default(),
+                None => format!("{:?}", exception),
+            }
+        }
+        err => err.to_string(),
+    }
+}
+
+// PAC scripts only know about IPv4 addresses.
+fn resolve_ipv4(host: &str) -> Option<IpAddr> {
+    (host, 0)
+        .to_socket_addrs()
+        .ok()?
+        .find(|addr| addr.is_ipv4())
+        .map(|addr| addr.ip())
+}
+
+fn my_ip_address() -> String {
+    // Connecting a UDP socket doesn't send anything, but makes the OS pick the
+    // interface address it would use for outgoing traffic.
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| socket.connect("192.0.2.1:80").map(|_| socket))
+        .and_then(|socket| socket.local_addr())
+        .map_or("127.0.0.1".to_string(), |addr| addr.ip().to_string())
+}
+
+// A PAC result is a list of entries like "PROXY host:port; DIRECT".
+// We don't probe for availability, so only the first entry is relevant.
+fn parse_pac_result(result: &str) -> AnyhowResult<Option<Url>> {
+    let entry = result
+        .split(';')
+        .map(str::trim)
+        .find(|entry| !entry.is_empty())
+        .unwrap_or("DIRECT");
+    let mut parts = entry.split_whitespace();
+    let keyword = parts.next().unwrap_or_default().to_ascii_uppercase();
+    let scheme = match (keyword.as_str(), parts.next()) {
+        ("DIRECT", _) => return Ok(None),
+        ("PROXY" | "HTTP", Some(_)) => "http",
+        ("HTTPS", Some(_)) => "https",
+        ("SOCKS" | "SOCKS5", Some(_)) => "socks5",
+        _ => bail!("Unsupported PAC result: {}", entry),
+    };
+    let host_port = entry.split_whitespace().nth(1).unwrap_or_default();
+    Url::parse(&format!("{}://{}", scheme, host_port))
+        .map(Some)
+        .map_err(|err| anyhow!("Invalid proxy in PAC result \"{}\": {}", entry, err))
+}
+
+/// Hosts that are always contacted directly, in the format of the NO_PROXY environment variable:
+/// A comma separated list of domains (matching all their subdomains), IP addresses,
+/// networks in CIDR notation, or "*" for all hosts.
+#[derive(Clone, Debug)]
+pub struct NoProxyList(Vec<NoProxyEntry>);
+
+#[derive(Clone, Debug)]
+enum NoProxyEntry {
+    Any,
+    Network(IpNet),
+    Address(IpAddr),
+    Domain(String),
+}
+
+impl FromStr for NoProxyList {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    if entry == "*" {
+                        NoProxyEntry::Any
+                    } else if let Ok(net) = entry.parse() {
+                        NoProxyEntry::Network(net)
+                    } else if let Ok(addr) = entry.parse() {
+                        NoProxyEntry::Address(addr)
+                    } else {
+                        NoProxyEntry::Domain(entry.trim_start_matches('.').to_ascii_lowercase())
+                    }
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl NoProxyList {
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = url
+            .host_str()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_ascii_lowercase();
+        let ip = host.parse::<IpAddr>().ok();
+        self.0.iter().any(|entry| match (entry, ip) {
+            (NoProxyEntry::Any, _) => true,
+            (NoProxyEntry::Network(net), Some(ip)) => net.contains(&ip),
+            (NoProxyEntry::Address(addr), Some(ip)) => *addr == ip,
+            (NoProxyEntry::Domain(domain), None) => {
+                host == *domain || host.ends_with(&format!(".{}", domain))
+            }
+            _ => false,
+        })
+    }
+}
+
+/// Decides which proxy (if any) to use for a URL, in this order:
+/// --no-proxy, explicit proxy URL, PAC script, proxy environment variables.
+#[derive(Clone)]
+pub struct ProxyResolver {
+    proxy_url: Option<String>,
+    pac: Option<PacScript>,
+    use_env: bool,
+    no_proxy: Option<NoProxyList>,
+}
+
+impl ProxyResolver {
+    pub fn new(
+        proxy_url: Option<String>,
+        pac: Option<PacScript>,
+        use_env: bool,
+        no_proxy: Option<NoProxyList>,
+    ) -> Self {
+        Self {
+            proxy_url,
+            pac,
+            use_env,
+            no_proxy,
+        }
+    }
+
+    pub fn resolve(&self, url: &Url) -> AnyhowResult<Option<Url>> {
+        if self.no_proxy.as_ref().is_some_and(|np| np.matches(url)) {
+            return Ok(None);
+        }
+        if let Some(proxy_url) = &self.proxy_url {
+            return parse_proxy_url(proxy_url).map(Some);
+        }
+        if let Some(pac) = &self.pac {
+            return pac.find_proxy(url);
+        }
+        if !self.use_env {
+            return Ok(None);
+        }
+        // Without explicit --no-proxy, the environment's NO_PROXY applies to the environment's
+        // proxies, just as with reqwest's own system proxy handling.
+        if self.no_proxy.is_none()
+            && env::var("NO_PROXY")
+                .or_else(|_| env::var("no_proxy"))
+                .is_ok_and(|np| NoProxyList::from_str(&np).is_ok_and(|np| np.matches(url)))
+        {
+            return Ok(None);
+        }
+        let scheme = url.scheme();
+        [
+            format!("{}_proxy", scheme),
+            format!("{}_PROXY", scheme.to_ascii_uppercase()),
+            "all_proxy".to_string(),
+            "ALL_PROXY".to_string(),
+        ]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()))
+        .map(|value| parse_proxy_url(&value))
+        .transpose()
+    }
+}
+
+// Like reqwest, we assume an HTTP proxy if no scheme is given.
+fn parse_proxy_url(proxy_url: &str) -> AnyhowResult<Url> {
+    let proxy_url = if proxy_url.contains("://") {
+        proxy_url.to_string()
+    } else {
+        format!("http://{}", proxy_url)
+    };
+    Url::parse(&proxy_url).map_err(|err| anyhow!("Invalid proxy URL {}: {}", proxy_url, err))
+}
+
+pub struct ProxyConnect {
+    pub proxy: Url,
+    pub status: u16,
+    pub reason: String,
+    pub time: Duration,
+}
+
+// The proxy and the actual target for the authority reqwest sends a CONNECT request for
+type Routes = Arc<Mutex<HashMap<String, (Url, String)>>>;
+
+/// A relay on localhost for CONNECT tunnels through HTTP proxies.
+/// reqwest sets up tunnels deep inside its connection handling, so we hand it the relay as
+/// proxy and forward its CONNECT requests to the actual proxy. This way we get to measure the
+/// CONNECT of the actual request.
+/// The relay only starts listening once a request actually goes through an HTTP proxy.
+#[derive(Default)]
+pub struct ConnectRelay {
+    routes: Routes,
+    connect: Arc<Mutex<Option<AnyhowResult<ProxyConnect>>>>,
+    listener: OnceLock<Option<(SocketAddr, JoinHandle<()>)>>,
+}
+
+impl ConnectRelay {
+    /// The proxy reqwest should use for the requested URL, given the actual proxy and target.
+    /// Only HTTPS requests through HTTP proxies use CONNECT and go through the relay.
+    /// The target differs from the requested URL with a custom SNI name, see with_sni_name.
+    /// Must be called from within the async runtime.
+    pub fn route(&self, requested: &Url, target: &Url, proxy: Url) -> Url {
+        if requested.scheme() != "https" || proxy.scheme() != "http" {
+            return proxy;
+        }
+        // If we can't listen on localhost, we talk to the proxy directly and
+        // don't get to measure the CONNECT.
+        let Some(addr) = self.addr() else {
+            return proxy;
+        };
+        let mut relay = Url::parse(&format!("http://{}", addr)).unwrap();
+        // reqwest still authenticates against the actual proxy, we just pass this on.
+        let _ = relay.set_username(proxy.username());
+        let _ = relay.set_password(proxy.password());
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(authority(requested), (proxy, authority(target)));
+        relay
+    }
+
+    /// The outcome of the first CONNECT request, or None if there was none.
+    pub fn take_connect(&self) -> Option<AnyhowResult<ProxyConnect>> {
+        self.connect.lock().unwrap().take()
+    }
+
+    fn addr(&self) -> Option<SocketAddr> {
+        self.listener
+            .get_or_init(|| self.listen().ok())
+            .as_ref()
+            .map(|(addr, _)| *addr)
+    }
+
+    fn listen(&self) -> std::io::Result<(SocketAddr, JoinHandle<()>)> {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        let addr = listener.local_addr()?;
+        let task = tokio::spawn(serve(listener, self.routes.clone(), self.connect.clone()));
+        Ok((addr, task))
+    }
+}
+
+impl Drop for ConnectRelay {
+    fn drop(&mut self) {
+        if let Some(Some((_, task))) = self.listener.get() {
+            task.abort();
+        }
+    }
+}
+
+// The authority as reqwest puts it into the CONNECT request
+fn authority(url: &Url) -> String {
+    format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or(443)
+    )
+}
+
+async fn serve(
+    listener: TcpListener,
+    routes: Routes,
+    connect: Arc<Mutex<Option<AnyhowResult<ProxyConnect>>>>,
+) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let routes = routes.clone();
+        let connect = connect.clone();
+        tokio::spawn(async move {
+            let result = relay(stream, &routes).await;
+            let mut connect = connect.lock().unwrap();
+            if connect.is_none() {
+                *connect = result;
+            }
+        });
+    }
+}
+
+// Returns None if the client didn't send a CONNECT request we know where to forward to.
+async fn relay(mut client: TcpStream, routes: &Routes) -> Option<AnyhowResult<ProxyConnect>> {
+    let request = read_head(&mut client).await.ok()?;
+    let authority = String::from_utf8_lossy(&request)
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("CONNECT "))
+        .and_then(|line| line.split_whitespace().next())
+        .map(str::to_string)?;
+    let (proxy, target) = routes.lock().unwrap().get(&authority).cloned()?;
+    let request = if target == authority {
+        request
+    } else {
+        String::from_utf8_lossy(&request)
+            .replacen(
+                &format!("CONNECT {} ", authority),
+                &format!("CONNECT {} ", target),
+                1,
+            )
+            .replacen(
+                &format!("\r\nHost: {}\r\n", authority),
+                &format!("\r\nHost: {}\r\n", target),
+                1,
+            )
+            .into_bytes()
+    };
+
+    let (mut upstream, response, status, reason, time) = match send_connect(&proxy, &request).await
+    {
+        Ok(connect) => connect,
+        Err(err) => {
+            let _ = client.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").await;
+            return Some(Err(anyhow!("{}: {}", proxy, err)));
+        }
+    };
+    if client.write_all(&response).await.is_ok() && (200..300).contains(&status) {
+        tokio::spawn(async move {
+            let _ = copy_bidirectional(&mut client, &mut upstream).await;
+        });
+    }
+    Some(Ok(ProxyConnect {
+        proxy,
+        status,
+        reason,
+        time,
+    }))
+}
+
+async fn send_connect(
+    proxy: &Url,
+    request: &[u8],
+) -> AnyhowResult<(TcpStream, Vec<u8>, u16, String, Duration)> {
+    let proxy_addr = (
+        proxy.host_str().unwrap_or_default(),
+        proxy.port_or_known_default().unwrap_or(80),
+    );
+    let mut stream = TcpStream::connect(proxy_addr).await?;
+    let start = Instant::now();
+    stream.write_all(request).await?;
+    let response = read_head(&mut stream).await?;
+    let time = start.elapsed();
+
+    let head = String::from_utf8_lossy(&response);
+    let mut status_line = head.lines().next().unwrap_or_default().splitn(3, ' ');
+    match (
+        status_line.next(),
+        status_line.next().map(str::parse::<u16>),
+    ) {
+        (Some(version), Some(Ok(status))) if version.starts_with("HTTP/") => {
+            let reason = status_line.next().unwrap_or_default().to_string();
+            Ok((stream, response, status, reason, time))
+        }
+        _ => bail!("invalid response to CONNECT"),
+    }
+}
+
+// Reads up to the end of the head of an HTTP message. Neither side of a CONNECT sends anything
+// else before it got an answer.
+async fn read_head(stream: &mut TcpStream) -> AnyhowResult<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() >= 8192 {
+            bail!("HTTP head too long");
+        }
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            bail!("connection closed");
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAC: &str = r#"
+        function FindProxyForURL(url, host) {
+            if (isPlainHostName(host) || dnsDomainIs(host, ".intranet.example.com")) {
+                return "DIRECT";
+            }
+            if (isInNet(host, "10.0.0.0", "255.0.0.0")) {
+                return "PROXY internal-proxy:3128";
+            }
+            if (shExpMatch(url, "https://*.example.org/*")) {
+                return "HTTPS secure-proxy:443; DIRECT";
+            }
+            return "PROXY proxy.example.com:8080; PROXY fallback:8080";
+        }
+    "#;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn resolve_fake(host: &str) -> Option<IpAddr> {
+        match host {
+            "wiki" | "app.intranet.example.com" => Some(IpAddr::from([10, 0, 0, 1])),
+            "build.internal.example.com" => Some(IpAddr::from([10, 1, 2, 3])),
+            "www.example.org" | "checkmk.com" => Some(IpAddr::from([192, 0, 2, 1])),
+            _ => None,
+        }
+    }
+
+    fn pac_script(source: &str) -> PacScript {
+        PacScript {
+            resolve_host: resolve_fake,
+            ..PacScript::from_str(source).unwrap()
+        }
+    }
+
+    fn find_proxy(pac: &PacScript, target: &str) -> Option<String> {
+        pac.find_proxy(&url(target))
+            .unwrap()
+            .map(|proxy| proxy.to_string())
+    }
+
+    #[test]
+    fn test_pac() {
+        let pac = pac_script(PAC);
+        assert_eq!(find_proxy(&pac, "http://wiki/"), None);
+        assert_eq!(find_proxy(&pac, "http://app.intranet.example.com/"), None);
+        assert_eq!(
+            find_proxy(&pac, "http://10.1.2.3/"),
+            Some("http://internal-proxy:3128/".to_string())
+        );
+        assert_eq!(
+            find_proxy(&pac, "http://build.internal.example.com/"),
+            Some("http://internal-proxy:3128/".to_string())
+        );
+        assert_eq!(
+            find_proxy(&pac, "https://www.example.org/index.html"),
+            Some("https://secure-proxy/".to_string())
+        );
+        assert_eq!(
+            find_proxy(&pac, "https://checkmk.com/"),
+            Some("http://proxy.example.com:8080/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_pac_helpers() {
+        let pac = PacScript::from_str(
+            r#"function FindProxyForURL(url, host) {
+                return [
+                    dnsDomainLevels("a.b.c"),
+                    localHostOrDomainIs("www", "www.example.com"),
+                    weekdayRange("SUN", "SAT"),
+                    dateRange("JAN", "DEC"),
+                    timeRange(0, 24),
+                    typeof myIpAddress(),
+                    dnsResolve("unknown.example.com"),
+                ].join(",");
+            }"#,
+        )
+        .unwrap();
+        let pac = PacScript {
+            resolve_host: resolve_fake,
+            ..pac
+        };
+        assert_eq!(
+            pac.evaluate("FindProxyForURL('', '')").unwrap(),
+            "2,true,true,true,true,string,"
+        );
+    }
+
+    #[test]
+    fn test_invalid_pac() {
+        assert!(PacScript::from_str("function FindProxyForURL(url, host) {").is_err());
+        assert!(PacScript::from_str("var x = 1;").is_err());
+        let pac =
+            PacScript::from_str("function FindProxyForURL(u, h) { return 'FTP x'; }").unwrap();
+        assert!(pac.find_proxy(&url("http://example.com/")).is_err());
+    }
+
+    #[test]
+    fn test_endless_pac() {
+        // Nested loops and recursion are interrupted just as well.
+        let pac = PacScript {
+            timeout: Duration::from_millis(100),
+            ..PacScript::from_str(
+                "function f() { for (;;) { for (;;) {} } }
+                 function FindProxyForURL(u, h) { return f(); }",
+            )
+            .unwrap()
+        };
+        let start = Instant::now();
+        let err = pac.find_proxy(&url("http://example.com/")).unwrap_err();
+        assert_eq!(err.to_string(), "PAC evaluation timed out");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pac_in_runtime() {
+        let pac = pac_script(PAC);
+        assert_eq!(
+            find_proxy(&pac, "https://checkmk.com/"),
+            Some("http://proxy.example.com:8080/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_no_proxy_list() {
+        let no_proxy = NoProxyList::from_str("example.com, .local.net,10.0.0.0/8, ::1").unwrap();
+        assert!(no_proxy.matches(&url("http://example.com/")));
+        assert!(no_proxy.matches(&url("http://www.example.com/")));
+        assert!(!no_proxy.matches(&url("http://notexample.com/")));
+        assert!(no_proxy.matches(&url("http://local.net/")));
+        assert!(no_proxy.matches(&url("http://host.local.net/")));
+        assert!(no_proxy.matches(&url("http://10.20.30.40/")));
+        assert!(!no_proxy.matches(&url("http://11.0.0.1/")));
+        assert!(no_proxy.matches(&url("http://[::1]:8080/")));
+        assert!(NoProxyList::from_str("*")
+            .unwrap()
+            .matches(&url("https://checkmk.com/")));
+    }
+
+    #[test]
+    fn test_resolver_precedence() {
+        let pac = pac_script(PAC);
+        let resolver = ProxyResolver::new(
+            None,
+            Some(pac.clone()),
+            false,
+            Some(NoProxyList::from_str("checkmk.com").unwrap()),
+        );
+        assert!(resolver
+            .resolve(&url("https://checkmk.com/"))
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            resolver.resolve(&url("https://example.com/")).unwrap(),
+            Some(url("http://proxy.example.com:8080"))
+        );
+        let resolver = ProxyResolver::new(Some("proxy:3128".to_string()), Some(pac), false, None);
+        assert_eq!(
+            resolver.resolve(&url("https://example.com/")).unwrap(),
+            Some(url("http://proxy:3128"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_relay() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = url(&format!("http://{}", listener.local_addr().unwrap()));
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_head(&mut socket).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let relay = ConnectRelay::default();
+        let target = url("http://example.com/");
+        assert_eq!(relay.route(&target, &target, proxy.clone()), proxy);
+        assert!(relay.listener.get().is_none());
+        let relay_url = relay.route(
+            &url("https://sni.example.com/"),
+            &url("https://example.com/"),
+            proxy.clone(),
+        );
+        assert_ne!(relay_url, proxy);
+
+        let mut client = TcpStream::connect(relay.addr().unwrap()).await.unwrap();
+        client
+            .write_all(b"CONNECT sni.example.com:443 HTTP/1.1\r\nHost: sni.example.com:443\r\n\r\n")
+            .await
+            .unwrap();
+        let response = read_head(&mut client).await.unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 407 "));
+        assert_eq!(
+            server.await.unwrap(),
+            "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n"
+        );
+
+        // The connection task records the outcome after answering the client.
+        let mut connect = None;
+        while connect.is_none() {
+            tokio::task::yield_now().await;
+            connect = relay.take_connect();
+        }
+        let connect = connect.unwrap().unwrap();
+        assert_eq!(connect.proxy, proxy);
+        assert_eq!(connect.status, 407);
+        assert_eq!(connect.reason, "Proxy Authentication Required");
+    }
+
+    #[tokio::test]
+    async fn test_connect_relay_unknown_target() {
+        let relay = ConnectRelay::default();
+        let mut client = TcpStream::connect(relay.addr().unwrap()).await.unwrap();
+        client
+            .write_all(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(read_head(&mut client).await.is_err());
+        assert!(relay.take_connect().is_none());
+    }
+}
@@ -2,20 +2,22 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 use bytes::Bytes;
 use encoding_rs::{Encoding, UTF_8};
 use mime::Mime;
 use reqwest::{
-    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, HOST},
     tls::TlsInfo,
-    Client, Method, RequestBuilder, Result as ReqwestResult, StatusCode, Url, Version,
+    Client, Method, Request, RequestBuilder, Result as ReqwestResult, StatusCode, Url, Version,
 };
 use tracing::{event, span, Level};
 
-use super::client::ClientAdapter;
-use anyhow::bail;
+use super::client::{ClientAdapter, RedirectHop};
+use super::handshake::Handshake;
+use super::proxy::ProxyConnect;
+use anyhow::{bail, Result as AnyhowResult};
 use std::fmt;
 use std::net::IpAddr;
 use std::str::FromStr;
@@ -66,6 +68,7 @@ impl Server {
     }
 }
 
+#[derive(Clone)]
 pub struct RequestConfig {
     pub url: Url,
     pub method: Method,
@@ -77,6 +80,7 @@ pub struct RequestConfig {
     pub auth_pw: Option<String>,
     pub without_body: bool,
     pub token_auth: Option<(HeaderName, HeaderValue)>,
+    pub host_header: Option<HeaderValue>,
 }
 
 pub struct ProcessedResponse {
@@ -88,7 +92,11 @@ pub struct ProcessedResponse {
     pub redirect_target: Option<Url>,
     pub tls_info: Option<TlsInfo>,
     pub time_headers: Duration,
+    /// When the headers arrived, to relate the Date header to our clock.
+    pub headers_received: SystemTime,
     pub time_body: Option<Duration>,
+    pub proxy_connect: Option<AnyhowResult<ProxyConnect>>,
+    pub handshake: Option<AnyhowResult<Handshake>>,
 }
 #[cfg_attr(test, derive(PartialEq, Debug))]
 pub struct Body {
@@ -96,18 +104,64 @@ pub struct Body {
     pub length: usize,
 }
 
+/// What we sent, along with the redirects we got, e.g. for writing a HAR file.
+/// Headers added by the client itself (like the user agent) are not included.
+pub struct Exchange {
+    pub started: SystemTime,
+    pub start: Instant,
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Option<String>,
+    pub redirects: Vec<RedirectHop>,
+}
+
 pub async fn send(
-    client_adapter: ClientAdapter,
+    client_adapter: &ClientAdapter,
     cfg: RequestConfig,
 ) -> ReqwestResult<ProcessedResponse> {
-    let span = span!(Level::INFO, "send_request");
-    let _guard = span.enter();
+    send_recorded(client_adapter, cfg).await.0
+}
 
+pub async fn send_recorded(
+    client_adapter: &ClientAdapter,
+    cfg: RequestConfig,
+) -> (ReqwestResult<ProcessedResponse>, Option<Exchange>) {
     let fetch_body = !cfg.without_body;
+    let body = cfg.body.clone();
+    let request = match prepare_request(&client_adapter.client, cfg).build() {
+        Ok(request) => request,
+        Err(err) => return (Err(err), None),
+    };
+    let mut exchange = Exchange {
+        started: SystemTime::now(),
+        start: Instant::now(),
+        method: request.method().clone(),
+        url: request.url().clone(),
+        headers: request.headers().clone(),
+        body,
+        redirects: vec![],
+    };
+
+    client_adapter.redirect_hops.lock().unwrap().clear();
+    let response = receive(client_adapter, request, fetch_body).await;
+    exchange.redirects = std::mem::take(&mut client_adapter.redirect_hops.lock().unwrap());
+    (response, Some(exchange))
+}
 
+async fn receive(
+    client_adapter: &ClientAdapter,
+    request: Request,
+    fetch_body: bool,
+) -> ReqwestResult<ProcessedResponse> {
+    let span = span!(Level::INFO, "send_request");
+    let _guard = span.enter();
+
+    let started = SystemTime::now();
     let start = Instant::now();
-    let mut response = prepare_request(client_adapter.client, cfg).send().await?;
+    let mut response = client_adapter.client.execute(request).await?;
     let time_headers = start.elapsed();
+    let headers_received = started + time_headers;
 
     let headers = response.headers().to_owned();
     let version = response.version();
@@ -140,18 +194,27 @@ pub async fn send(
         redirect_target,
         tls_info,
         time_headers,
+        headers_received,
         time_body,
+        proxy_connect: None,
+        handshake: None,
     })
 }
 
-fn prepare_request(client: Client, request_cfg: RequestConfig) -> RequestBuilder {
+fn prepare_request(client: &Client, request_cfg: RequestConfig) -> RequestBuilder {
     let mut headers = HeaderMap::from_iter(request_cfg.headers);
-    if let Some((token_header, token_key)) = request_cfg.token_auth {
+    if let Some((token_header, mut token_key)) = request_cfg.token_auth {
+        // Like the basic auth credentials, the token must not show up in any records.
+        token_key.set_sensitive(true);
         headers.insert(token_header, token_key);
     }
     if let Some(content_type) = request_cfg.content_type {
         headers.insert(CONTENT_TYPE, content_type);
     }
+    // hyper only sets the Host header from the URL if it's not set already.
+    if let Some(host) = request_cfg.host_header {
+        headers.insert(HOST, host);
+    }
 
     let req = client
         .request(request_cfg.method, request_cfg.url)
@@ -0,0 +1,670 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// Translation of the command line of the classic check_http (monitoring-plugins/nagios-plugins)
+// into our own arguments, invoked via "check_httpv2 --legacy <classic arguments>".
+// Options without an equivalent are collected, so that they can be reported instead of
+// silently changing the meaning of a check.
+// Note: In contrast to the classic plugin, the HTTP request is always checked, even if
+// only the certificate is of interest (i.e., -C without --continue-after-certificate).
+
+use anyhow::{anyhow, bail, Result as AnyhowResult};
+use reqwest::header::HeaderName;
+use std::str::FromStr;
+
+pub const LEGACY_FLAG: &str = "--legacy";
+
+enum Arity {
+    Flag,
+    Value,
+    // getopt only accepts optional values attached to the option, i.e. "-S1.2" or "--ssl=1.2"
+    OptionalValue,
+}
+
+struct LegacyOption {
+    short: Option<char>,
+    long: &'static str,
+    arity: Arity,
+}
+
+const fn opt(short: Option<char>, long: &'static str, arity: Arity) -> LegacyOption {
+    LegacyOption { short, long, arity }
+}
+
+const LEGACY_OPTIONS: [LegacyOption; 44] = [
+    opt(Some('H'), "hostname", Arity::Value),
+    opt(Some('I'), "IP-address", Arity::Value),
+    opt(Some('p'), "port", Arity::Value),
+    opt(Some('4'), "use-ipv4", Arity::Flag),
+    opt(Some('6'), "use-ipv6", Arity::Flag),
+    opt(Some('S'), "ssl", Arity::OptionalValue),
+    opt(None, "sni", Arity::Flag),
+    opt(None, "verify-host", Arity::Flag),
+    opt(Some('C'), "certificate", Arity::Value),
+    opt(None, "continue-after-certificate", Arity::Flag),
+    opt(Some('J'), "client-cert", Arity::Value),
+    opt(Some('K'), "private-key", Arity::Value),
+    opt(Some('e'), "expect", Arity::Value),
+    opt(Some('d'), "header-string", Arity::Value),
+    opt(Some('s'), "string", Arity::Value),
+    opt(Some('u'), "url", Arity::Value),
+    opt(Some('P'), "post", Arity::Value),
+    opt(Some('j'), "method", Arity::Value),
+    opt(Some('N'), "no-body", Arity::Flag),
+    opt(Some('M'), "max-age", Arity::Value),
+    opt(Some('T'), "content-type", Arity::Value),
+    opt(Some('l'), "linespan", Arity::Flag),
+    opt(Some('r'), "regex", Arity::Value),
+    opt(None, "ereg", Arity::Value),
+    opt(Some('R'), "eregi", Arity::Value),
+    opt(None, "invert-regex", Arity::Flag),
+    opt(None, "state-regex", Arity::Value),
+    opt(Some('a'), "authorization", Arity::Value),
+    opt(Some('b'), "proxy-authorization", Arity::Value),
+    opt(Some('A'), "useragent", Arity::Value),
+    opt(Some('k'), "header", Arity::Value),
+    opt(Some('E'), "extended-perfdata", Arity::Flag),
+    opt(Some('L'), "link", Arity::Flag),
+    opt(Some('f'), "onredirect", Arity::Value),
+    opt(None, "max-redirs", Arity::Value),
+    opt(Some('m'), "pagesize", Arity::Value),
+    opt(Some('w'), "warning", Arity::Value),
+    opt(Some('c'), "critical", Arity::Value),
+    opt(Some('t'), "timeout", Arity::Value),
+    opt(Some('v'), "verbose", Arity::Flag),
+    opt(None, "cookie-jar", Arity::Value),
+    opt(None, "haproxy-protocol", Arity::Flag),
+    opt(Some('h'), "help", Arity::Flag),
+    opt(Some('V'), "version", Arity::Flag),
+];
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Translation {
+    /// Arguments for our own Cli, including the program name
+    pub args: Vec<String>,
+    /// Legacy options (or values) we can't translate
+    pub unsupported: Vec<String>,
+}
+
+/// Returns None if we're not invoked in legacy mode.
+pub fn translate_if_requested(args: &[String]) -> Option<AnyhowResult<Translation>> {
+    match args {
+        [program, flag, legacy_args @ ..] if flag == LEGACY_FLAG => {
+            Some(translate(program, legacy_args))
+        }
+        _ => None,
+    }
+}
+
+// An option as given on the command line (for reporting), its long name and its value.
+type ParsedOption = (String, &'static str, Option<String>);
+
+fn parse(legacy_args: &[String]) -> AnyhowResult<(Vec<ParsedOption>, Vec<String>)> {
+    let mut options = vec![];
+    let mut unknown = vec![];
+    let mut args = legacy_args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, attached) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let given = format!("--{}", name);
+            match LEGACY_OPTIONS.iter().find(|o| o.long == name) {
+                Some(option) => {
+                    let value = option_value(option, &given, attached, &mut args)?;
+                    options.push((given, option.long, value));
+                }
+                None => unknown.push(given),
+            }
+        } else if let Some(mut shorts) = arg.strip_prefix('-').filter(|s| !s.is_empty()) {
+            // Like getopt, we accept grouped short options like "-4N". The first one that
+            // takes a value takes the rest of the argument as its value.
+            while let Some(short) = shorts.chars().next() {
+                shorts = &shorts[short.len_utf8()..];
+                let given = format!("-{}", short);
+                let Some(option) = LEGACY_OPTIONS.iter().find(|o| o.short == Some(short)) else {
+                    unknown.push(given);
+                    continue;
+                };
+                let attached = match option.arity {
+                    Arity::Flag => None,
+                    Arity::Value | Arity::OptionalValue => {
+                        Some(std::mem::take(&mut shorts).to_string()).filter(|v| !v.is_empty())
+                    }
+                };
+                let value = option_value(option, &given, attached, &mut args)?;
+                options.push((given, option.long, value));
+            }
+        } else {
+            unknown.push(arg.to_string());
+        }
+    }
+    Ok((options, unknown))
+}
+
+fn option_value(
+    option: &LegacyOption,
+    given: &str,
+    attached: Option<String>,
+    args: &mut std::slice::Iter<String>,
+) -> AnyhowResult<Option<String>> {
+    Ok(match option.arity {
+        Arity::Flag | Arity::OptionalValue => attached,
+        Arity::Value => match attached {
+            Some(value) => Some(value),
+            None => Some(
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing value for {}", given))?
+                    .to_string(),
+            ),
+        },
+    })
+}
+
+fn translate(program: &str, legacy_args: &[String]) -> AnyhowResult<Translation> {
+    let (options, mut unsupported) = parse(legacy_args)?;
+    let mut args = vec![program.to_string()];
+
+    let mut host: Option<String> = None;
+    let mut ip: Option<String> = None;
+    let mut port: Option<u16> = None;
+    let mut ssl = false;
+    let mut path = "/".to_string();
+    let mut connect_method = false;
+    let mut post_data = false;
+    let mut content_type: Option<String> = None;
+    let mut onredirect = "ok".to_string();
+    let mut linespan = false;
+    let mut body_strings: Vec<String> = vec![];
+    let mut body_regexes: Vec<String> = vec![];
+    let mut invert_regex = false;
+    // Classic check_http reports failed matches as CRIT.
+    let mut regex_state = "critical";
+    let mut header_string = false;
+    let mut warning: Option<String> = None;
+    let mut critical: Option<String> = None;
+
+    for (given, long, value) in options {
+        let value = value.unwrap_or_default();
+        match long {
+            "hostname" => host = Some(value),
+            "IP-address" => ip = Some(value),
+            "port" => {
+                port = Some(
+                    value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid port: {}", value))?,
+                )
+            }
+            "use-ipv4" => push(&mut args, "--force-ip-version", "ipv4"),
+            "use-ipv6" => push(&mut args, "--force-ip-version", "ipv6"),
+            "ssl" => {
+                ssl = true;
+                if !value.is_empty() {
+                    match map_tls_version(&value) {
+                        Some((option, version)) => push(&mut args, option, version),
+                        None => unsupported.push(format!("{} {}", given, value)),
+                    }
+                }
+            }
+            "certificate" => {
+                ssl = true;
+                push(&mut args, "--certificate-levels", &value)
+            }
+            // check_httpv2 always sends SNI and extended performance data, and never stops
+            // after the certificate check.
+            "sni" | "verify-host" | "extended-perfdata" | "continue-after-certificate" => {}
+            "expect" => {
+                for expectation in value.split(',').map(str::trim) {
+                    match expectation
+                        .split_whitespace()
+                        .find(|part| part.len() == 3 && part.chars().all(|c| c.is_ascii_digit()))
+                    {
+                        Some(status) => push(&mut args, "--status-code", status),
+                        // The classic default, i.e. any valid status line
+                        None if expectation.starts_with("HTTP/") => {}
+                        None => unsupported.push(format!("{} {}", given, expectation)),
+                    }
+                }
+            }
+            "header-string" => {
+                header_string = true;
+                push(&mut args, "--header-strings", &map_header_string(&value))
+            }
+            "string" => body_strings.push(value),
+            "url" => path = value,
+            "post" => {
+                post_data = true;
+                push(&mut args, "--body", &value)
+            }
+            "method" if value.eq_ignore_ascii_case("CONNECT") => connect_method = true,
+            "method" => push(&mut args, "--method", &value),
+            "no-body" => args.push("--without-body".to_string()),
+            "max-age" => push(
+                &mut args,
+                "--document-age-levels",
+                &parse_max_age(&value)?.to_string(),
+            ),
+            "content-type" => content_type = Some(value),
+            "linespan" => linespan = true,
+            "regex" | "ereg" => body_regexes.push(value),
+            "eregi" => body_regexes.push(format!("(?i){}", value)),
+            "invert-regex" => invert_regex = true,
+            "state-regex" => match map_state(&value) {
+                Some(state) => regex_state = state,
+                None => unsupported.push(format!("{} {}", given, value)),
+            },
+            "authorization" | "proxy-authorization" => {
+                let (user, pw) = value
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("Invalid {} (expected USER:PASSWORD)", given))?;
+                let prefix = if long == "authorization" {
+                    "auth"
+                } else {
+                    "proxy"
+                };
+                push(&mut args, &format!("--{}-user", prefix), user);
+                push(&mut args, &format!("--{}-pw-plain", prefix), pw);
+            }
+            "useragent" => push(&mut args, "--user-agent", &value),
+            "header" => push(&mut args, "--header", &value),
+            "onredirect" => match value.as_str() {
+                "ok" | "warning" | "critical" | "follow" | "sticky" | "stickyport" => {
+                    onredirect = value
+                }
+                _ => unsupported.push(format!("{} {}", given, value)),
+            },
+            "max-redirs" => push(&mut args, "--max-redirs", &value),
+            "pagesize" => push(&mut args, "--page-size", &value.replacen(':', ",", 1)),
+            "warning" => warning = Some(value),
+            "critical" => critical = Some(value),
+            "timeout" => match value.split_once(':') {
+                Some((timeout, state)) => {
+                    push(&mut args, "--timeout", timeout);
+                    match map_state(state) {
+                        Some(state) => push(&mut args, "--state-on", &format!("timeout={}", state)),
+                        None => unsupported.push(format!("{} {}:{}", given, timeout, state)),
+                    }
+                }
+                None => push(&mut args, "--timeout", &value),
+            },
+            "verbose" => args.push("-v".to_string()),
+            _ => unsupported.push(given),
+        }
+    }
+
+    // Classic check_http only sends the content type along with POST data.
+    if let (Some(content_type), true) = (content_type, post_data) {
+        push(&mut args, "--content-type", &content_type);
+    }
+
+    push(&mut args, "--onredirect", &onredirect);
+
+    match (warning, critical) {
+        (Some(warn), Some(crit)) => push(
+            &mut args,
+            "--response-time-levels",
+            &format!("{},{}", warn, crit),
+        ),
+        (Some(warn), None) => push(&mut args, "--response-time-levels", &warn),
+        (None, Some(crit)) => push(
+            &mut args,
+            "--response-time-levels",
+            &format!("{},{}", crit, crit),
+        ),
+        (None, None) => {}
+    }
+
+    // Our body strings and regexes exclude each other, but a string is easily made a regex.
+    // Only the regexes are inverted, though.
+    if !body_regexes.is_empty() {
+        if invert_regex {
+            unsupported.extend(
+                body_strings
+                    .drain(..)
+                    .map(|_| "-s with --invert-regex".to_string()),
+            );
+        } else {
+            body_regexes.extend(body_strings.drain(..).map(|s| regex::escape(&s)));
+        }
+    }
+    for string in &body_strings {
+        push(&mut args, "--body-string", string);
+    }
+    // Without --linespan, the classic check_http matches line by line (REG_NEWLINE).
+    let flags = if linespan { "(?s)" } else { "(?m)" };
+    for regex in &body_regexes {
+        push(&mut args, "--body-regex", &format!("{}{}", flags, regex));
+    }
+    if invert_regex && !body_regexes.is_empty() {
+        args.push("--body-regex-invert".to_string());
+    }
+    if !body_strings.is_empty() || !body_regexes.is_empty() {
+        push(
+            &mut args,
+            "--state-on",
+            &format!("body_match={}", regex_state),
+        );
+    }
+    if header_string {
+        push(&mut args, "--state-on", "header_match=critical");
+    }
+
+    let target = |default_port: u16| -> AnyhowResult<String> {
+        host.clone()
+            .or(ip.clone())
+            .map(|host| with_port(&host, port, default_port))
+            .ok_or_else(|| anyhow!("Either -H or -I is required"))
+    };
+    if connect_method {
+        // Tunnel through the proxy given by -I (or -H) and -p to the virtual host -H.
+        let proxy = ip
+            .clone()
+            .or(host.clone())
+            .map(|proxy| with_port(&proxy, port, 80))
+            .ok_or_else(|| anyhow!("Either -H or -I is required"))?;
+        let host = host
+            .clone()
+            .ok_or_else(|| anyhow!("-j CONNECT requires -H"))?;
+        push(&mut args, "--proxy-url", &format!("http://{}", proxy));
+        push(&mut args, "--url", &format!("https://{}{}", host, path));
+    } else if path.starts_with("http://") || path.starts_with("https://") {
+        // An absolute URL means we're talking to a proxy.
+        push(&mut args, "--proxy-url", &format!("http://{}", target(80)?));
+        push(&mut args, "--url", &path);
+    } else {
+        let scheme = if ssl { "https" } else { "http" };
+        let default_port = if ssl { 443 } else { 80 };
+        if !path.starts_with('/') {
+            path.insert(0, '/');
+        }
+        push(
+            &mut args,
+            "--url",
+            &format!("{}://{}{}", scheme, target(default_port)?, path),
+        );
+        if let (Some(_), Some(ip)) = (&host, &ip) {
+            push(&mut args, "--server", ip);
+        }
+    }
+
+    Ok(Translation { args, unsupported })
+}
+
+fn push(args: &mut Vec<String>, option: &str, value: &str) {
+    args.push(option.to_string());
+    args.push(value.to_string());
+}
+
+fn map_state(state: &str) -> Option<&'static str> {
+    match state.to_ascii_uppercase().as_str() {
+        "0" | "OK" => Some("ok"),
+        "1" | "WARNING" => Some("warning"),
+        "2" | "CRITICAL" => Some("critical"),
+        "3" | "UNKNOWN" => Some("unknown"),
+        _ => None,
+    }
+}
+
+fn map_tls_version(version: &str) -> Option<(&'static str, &'static str)> {
+    let (version, min) = match version.strip_suffix('+') {
+        Some(version) => (version, true),
+        None => (version, false),
+    };
+    let version = match version {
+        "1" => "tls10",
+        "1.1" => "tls11",
+        "1.2" => "tls12",
+        "1.3" => "tls13",
+        // SSLv2 and SSLv3
+        _ => return None,
+    };
+    Some((
+        if min {
+            "--min-tls-version"
+        } else {
+            "--tls-version"
+        },
+        version,
+    ))
+}
+
+// The classic check_http searches the whole header section, we search name and value.
+fn map_header_string(header_string: &str) -> String {
+    match header_string.split_once(':') {
+        Some((name, value)) if HeaderName::from_str(name.trim()).is_ok() => {
+            format!("{}:{}", name.trim(), value.trim())
+        }
+        _ => format!(":{}", header_string),
+    }
+}
+
+fn parse_max_age(max_age: &str) -> AnyhowResult<u64> {
+    let (number, factor) = match max_age.chars().last() {
+        Some('s') => (&max_age[..max_age.len() - 1], 1),
+        Some('m') => (&max_age[..max_age.len() - 1], 60),
+        Some('h') => (&max_age[..max_age.len() - 1], 60 * 60),
+        Some('d') => (&max_age[..max_age.len() - 1], 60 * 60 * 24),
+        _ => (max_age, 1),
+    };
+    match number.parse::<u64>() {
+        Ok(number) => Ok(number * factor),
+        Err(_) => bail!("Invalid max age: {}", max_age),
+    }
+}
+
+// Hosts may already contain a port (like "example.com:8080"), which -p overrides.
+fn with_port(host: &str, port: Option<u16>, default_port: u16) -> String {
+    let (host, host_port) = match host.rsplit_once(':') {
+        Some((name, p)) if !name.contains(':') || name.ends_with(']') => {
+            (name.to_string(), p.parse::<u16>().ok())
+        }
+        // An IPv6 address without brackets
+        _ if host.contains(':') && !host.starts_with('[') => (format!("[{}]", host), None),
+        _ => (host.to_string(), None),
+    };
+    match port.or(host_port) {
+        Some(port) if port != default_port => format!("{}:{}", host, port),
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::Parser;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn translated(legacy: &str) -> Translation {
+        translate_if_requested(&args(&format!("check_httpv2 --legacy {}", legacy)))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_not_requested() {
+        assert!(translate_if_requested(&args("check_httpv2 -u http://foo")).is_none());
+    }
+
+    #[test]
+    fn test_simple() {
+        assert_eq!(
+            translated("-H www.example.com -w 1.5 -c 3 -t 20"),
+            Translation {
+                args: args(
+                    "check_httpv2 --timeout 20 --onredirect ok \
+                    --response-time-levels 1.5,3 --url http://www.example.com/"
+                ),
+                unsupported: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_ssl_and_server() {
+        assert_eq!(
+            translated("-H example.com -I 10.0.0.1 -p 8443 --ssl=1.2+ --sni -C 30,14 -u /status")
+                .args,
+            args(
+                "check_httpv2 --min-tls-version tls12 --certificate-levels 30,14 \
+                --onredirect ok --url https://example.com:8443/status --server 10.0.0.1"
+            )
+        );
+    }
+
+    #[test]
+    fn test_attached_values() {
+        assert_eq!(
+            translated("-Iexample.com -S -e200,302 -fsticky").args,
+            args(
+                "check_httpv2 --status-code 200 --status-code 302 \
+                --onredirect sticky --url https://example.com/"
+            )
+        );
+    }
+
+    #[test]
+    fn test_grouped_flags() {
+        assert_eq!(
+            translated("-4NIexample.com -Lvt 20").args,
+            args(
+                "check_httpv2 --force-ip-version ipv4 --without-body -v --timeout 20 \
+                --onredirect ok --url http://example.com/"
+            )
+        );
+        assert_eq!(translated("-H example.com -4xN").unsupported, vec!["-x"]);
+    }
+
+    #[test]
+    fn test_content_type() {
+        // --content-type requires --body, so we must not pass it on alone.
+        let without_body = translated("-H example.com -T application/json").args;
+        assert_eq!(
+            without_body,
+            args("check_httpv2 --onredirect ok --url http://example.com/")
+        );
+        assert!(Cli::try_parse_from(without_body).is_ok());
+        assert_eq!(
+            translated("-H example.com -T application/json -P {}").args,
+            args(
+                "check_httpv2 --body {} --content-type application/json \
+                --onredirect ok --url http://example.com/"
+            )
+        );
+    }
+
+    #[test]
+    fn test_body_matching() {
+        assert_eq!(
+            translated("-H example.com -s a.b -R foo --invert-regex"),
+            Translation {
+                args: args(
+                    "check_httpv2 --onredirect ok --body-regex (?m)(?i)foo \
+                    --body-regex-invert --state-on body_match=critical --url http://example.com/"
+                ),
+                unsupported: vec!["-s with --invert-regex".to_string()],
+            }
+        );
+        assert_eq!(
+            translated("-H example.com -s a.b -r foo -l").args,
+            args(
+                "check_httpv2 --onredirect ok --body-regex (?s)foo --body-regex (?s)a\\.b \
+                --state-on body_match=critical --url http://example.com/"
+            )
+        );
+        assert_eq!(
+            translated("-H example.com -s foo --state-regex 1").args,
+            args(
+                "check_httpv2 --onredirect ok --body-string foo --state-on body_match=warning \
+                --url http://example.com/"
+            )
+        );
+    }
+
+    #[test]
+    fn test_states() {
+        assert_eq!(
+            translated("-H example.com -t 10:UNKNOWN -d Server:nginx").args,
+            args(
+                "check_httpv2 --timeout 10 --state-on timeout=unknown \
+                --header-strings Server:nginx --onredirect ok \
+                --state-on header_match=critical --url http://example.com/"
+            )
+        );
+        assert_eq!(map_state("2"), Some("critical"));
+        assert_eq!(map_state("ok"), Some("ok"));
+        assert_eq!(map_state("bad"), None);
+    }
+
+    #[test]
+    fn test_proxy() {
+        assert_eq!(
+            translated("-I proxy -p 3128 -u http://example.com/ -b user:pass").args,
+            args(
+                "check_httpv2 --proxy-user user --proxy-pw-plain pass --onredirect ok \
+                --proxy-url http://proxy:3128 --url http://example.com/"
+            )
+        );
+        assert_eq!(
+            translated("-j CONNECT -H example.com -I proxy -p 3128 --ssl").args,
+            args(
+                "check_httpv2 --onredirect ok --proxy-url http://proxy:3128 \
+                --url https://example.com/"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let translation = translated("-H example.com -L -J cert.pem -S3 -t 10:BAD foo");
+        assert_eq!(
+            translation.unsupported,
+            vec!["foo", "-L", "-J", "-S 3", "-t 10:BAD"]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(translate_if_requested(&args("check_httpv2 --legacy -H"))
+            .unwrap()
+            .is_err());
+        assert!(translate_if_requested(&args("check_httpv2 --legacy -w 1"))
+            .unwrap()
+            .is_err());
+        assert!(
+            translate_if_requested(&args("check_httpv2 --legacy -H foo -a user"))
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_header_string() {
+        assert_eq!(map_header_string("Server: nginx"), "Server:nginx");
+        assert_eq!(map_header_string("nginx"), ":nginx");
+        assert_eq!(map_header_string("no header: x"), ":no header: x");
+    }
+
+    #[test]
+    fn test_max_age() {
+        assert_eq!(parse_max_age("30").unwrap(), 30);
+        assert_eq!(parse_max_age("5m").unwrap(), 300);
+        assert_eq!(parse_max_age("2d").unwrap(), 172800);
+        assert!(parse_max_age("x").is_err());
+    }
+
+    #[test]
+    fn test_with_port() {
+        assert_eq!(with_port("example.com", None, 80), "example.com");
+        assert_eq!(with_port("example.com:8080", None, 80), "example.com:8080");
+        assert_eq!(with_port("example.com:8080", Some(443), 443), "example.com");
+        assert_eq!(with_port("::1", Some(8080), 80), "[::1]:8080");
+        assert_eq!(with_port("[::1]:8080", None, 80), "[::1]:8080");
+    }
+}
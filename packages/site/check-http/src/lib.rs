@@ -4,6 +4,10 @@
 
 pub mod checking_types;
 pub mod checks;
+pub mod har;
+pub mod html;
 pub mod http;
 pub mod output;
+pub mod prometheus;
 pub mod runner;
+pub mod trend;
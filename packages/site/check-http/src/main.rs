@@ -2,14 +2,21 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
-use check_http::checking_types::{Bounds, LowerLevels, UpperLevels};
-use check_http::checks::{CheckParameters, RequestInformation, TextMatcher};
-use check_http::http::{self, ClientConfig, RequestConfig};
+use check_http::checking_types::{notice, Bounds, LowerLevels, State, UpperLevels};
+use check_http::checks::{
+    CheckParameters, DualStackLevels, GraphQlCheck, HtmlMatcher, RequestInformation, SeriesCheck,
+    StateMapping, TextMatcher, TrendLevels,
+};
+use check_http::har::Har;
+use check_http::html::CssSelector;
+use check_http::http::{self, ClientConfig, LinkScan, RequestConfig, Retry};
 use check_http::output::Output;
 use check_http::runner::collect_checks;
-use clap::Parser;
+use check_http::trend::{self, Trend};
+use clap::{error::ErrorKind, CommandFactory, Parser};
 use cli::Cli;
-use reqwest::{tls::Version as TlsVersion, Method, Version};
+use jsonschema::JSONSchema;
+use reqwest::{header::HeaderValue, tls::Version as TlsVersion, Method, Version};
 use tracing_subscriber::{
     self,
     filter::{EnvFilter, FilterFn, LevelFilter},
@@ -17,25 +24,72 @@ use tracing_subscriber::{
     prelude::*,
 };
 
+mod batch;
 mod cli;
-mod pwstore;
+mod legacy;
 
 const DEFAULT_USER_AGENT: &str = "checkmk-active-httpv2/2.4.0";
 
 #[tokio::main]
 async fn main() {
-    let args = Cli::parse();
+    let raw_args = std::env::args().collect::<Vec<_>>();
+    if batch::is_requested(&raw_args) {
+        batch::run(&raw_args).await.unwrap_or_else(|err| err.exit());
+        return;
+    }
+
+    let (args, unsupported_legacy_options) = parse_args(&raw_args).unwrap_or_else(|err| err.exit());
 
     init_tracing(args.logging_level(), args.debug_headers, args.debug_content);
 
-    let (client_cfg, request_cfg, request_information, check_params) = make_configs(args);
-    let output = Output::from_check_results(
-        collect_checks(client_cfg, request_cfg, request_information, check_params).await,
-    );
+    let output = check(args, &raw_args[1..], unsupported_legacy_options)
+        .await
+        .unwrap_or_else(|err| err.exit());
     println!("{}", output);
     std::process::exit(output.worst_state.into());
 }
 
+/// Parse the command line, translating the options of the classic check_http if requested.
+/// Returns the arguments along with the unsupported legacy options.
+fn parse_args(raw_args: &[String]) -> Result<(Cli, Vec<String>), clap::Error> {
+    match legacy::translate_if_requested(raw_args) {
+        None => Ok((Cli::try_parse_from(raw_args)?, vec![])),
+        Some(Ok(translation)) => Ok((
+            Cli::try_parse_from(translation.args)?,
+            translation.unsupported,
+        )),
+        Some(Err(err)) => Err(Cli::command().error(
+            ErrorKind::ValueValidation,
+            format!("Invalid legacy check_http arguments: {}", err),
+        )),
+    }
+}
+
+async fn check(
+    args: Cli,
+    raw_args: &[String],
+    unsupported_legacy_options: Vec<String>,
+) -> Result<Output, clap::Error> {
+    let (client_cfg, request_cfg, request_information, check_params) =
+        make_configs(args, raw_args)?;
+    let mut check_results =
+        collect_checks(client_cfg, request_cfg, request_information, check_params).await;
+    if !unsupported_legacy_options.is_empty() {
+        check_results.extend(
+            notice(
+                State::Warn,
+                &format!(
+                    "Unsupported legacy options ignored: {}",
+                    unsupported_legacy_options.join(", ")
+                ),
+            )
+            .into_iter()
+            .flatten(),
+        );
+    }
+    Ok(Output::from_check_results(check_results))
+}
+
 fn init_tracing(logging_level: LevelFilter, debug_headers: bool, debug_content: bool) {
     // General tracing/logging.
     // The directives from env and the logging_level are additive, so the RUST_LOG env var
@@ -78,17 +132,51 @@ fn init_tracing(logging_level: LevelFilter, debug_headers: bool, debug_content:
         .init();
 }
 
-fn make_configs(
-    args: Cli,
-) -> (
+type Configs = (
     ClientConfig,
     RequestConfig,
     RequestInformation,
     CheckParameters,
-) {
+);
+
+/// Build the configs from the parsed arguments.
+/// The raw arguments identify the check, e.g. for its state file.
+fn make_configs(args: Cli, raw_args: &[String]) -> Result<Configs, clap::Error> {
+    let validation_error = |msg: String| Cli::command().error(ErrorKind::ValueValidation, msg);
     let user_agent = args.user_agent.unwrap_or(DEFAULT_USER_AGENT.to_string());
+    let graphql_body = args
+        .graphql_query
+        .as_ref()
+        .map(|query| {
+            graphql_request_body(query, args.graphql_variables.as_deref())
+                .map_err(|err| validation_error(format!("Invalid GraphQL variables: {}", err)))
+        })
+        .transpose()?;
+    let html_element_texts = args
+        .html_element_text
+        .chunks(2)
+        .map(|pair| match pair {
+            [selector, text] => Ok((
+                selector
+                    .parse::<CssSelector>()
+                    .map_err(|err| validation_error(err.to_string()))?,
+                text.to_string(),
+            )),
+            // clap ensures that we always get pairs (num_args = 2)
+            _ => unreachable!(),
+        })
+        .collect::<Result<Vec<_>, clap::Error>>()?;
+    let json_schema = args
+        .json_schema
+        .as_ref()
+        .map(|path| {
+            load_json_schema(path).map_err(|err| {
+                validation_error(format!("Invalid JSON schema {}: {}", path.display(), err))
+            })
+        })
+        .transpose()?;
     let method = args.method.unwrap_or_else(|| {
-        if args.body.is_some() {
+        if args.body.is_some() || graphql_body.is_some() {
             Method::POST
         } else {
             Method::GET
@@ -103,7 +191,7 @@ fn make_configs(
         cli::OnRedirect::Stickyport => http::OnRedirect::Stickyport,
     };
 
-    (
+    Ok((
         ClientConfig {
             version: args.http_version.clone().map(|ver| match ver {
                 cli::HttpVersion::Http11 => Version::HTTP_11,
@@ -117,6 +205,8 @@ fn make_configs(
                 None => None,
                 Some(cli::ForceIP::Ipv4) => Some(http::ForceIP::Ipv4),
                 Some(cli::ForceIP::Ipv6) => Some(http::ForceIP::Ipv6),
+                // The runner takes care of the requests via both IP versions
+                Some(cli::ForceIP::Both) => None,
             },
             min_tls_version: args
                 .min_tls_version
@@ -124,7 +214,10 @@ fn make_configs(
                 .map(map_tls_version)
                 .or(args.tls_version.as_ref().map(map_tls_version)),
             max_tls_version: args.tls_version.as_ref().map(map_tls_version),
-            collect_tls_info: args.certificate_levels.is_some(),
+            collect_tls_info: args.certificate_levels.is_some()
+                || args.certificate_change_state.is_some(),
+            collect_handshake_info: args.tls_details,
+            measure_proxy_connect: args.proxy_connect_time,
             ignore_proxy_env: args.ignore_proxy_env,
             proxy_url: args.proxy_url,
             proxy_auth: if let (Some(proxy_user), Some(proxy_pw)) = (
@@ -137,9 +230,13 @@ fn make_configs(
             } else {
                 None
             },
+            proxy_pac: args.proxy_pac_file,
+            no_proxy: args.no_proxy,
             disable_certificate_verification: args.disable_certificate_verification,
             url: args.url.clone(),
             server: args.server.clone(),
+            sni_name: args.sni,
+            disable_sni: args.no_sni,
         },
         RequestConfig {
             url: args.url.clone(),
@@ -149,7 +246,11 @@ fn make_configs(
                 cli::HttpVersion::Http11 => Version::HTTP_11,
                 cli::HttpVersion::Http2 => Version::HTTP_2,
             }),
-            body: args.body,
+            content_type: match graphql_body {
+                Some(_) => Some(HeaderValue::from_static("application/json")),
+                None => args.content_type,
+            },
+            body: graphql_body.or(args.body),
             auth_user: args.auth_user,
             auth_pw: args.auth_pw.auth_pw_plain.or(args.auth_pw.auth_pw_pwstore),
             token_auth: if let (Some(token_header), Some(token_key)) = (
@@ -162,8 +263,8 @@ fn make_configs(
             } else {
                 None
             },
-            content_type: args.content_type,
             without_body: args.without_body,
+            host_header: args.host_header,
         },
         RequestInformation {
             request_url: args.url,
@@ -184,6 +285,10 @@ fn make_configs(
                 (x, Some(y)) => UpperLevels::warn_crit(x, y),
             }),
             document_age_levels: args.document_age_levels.map(UpperLevels::warn),
+            clock_skew_levels: args.clock_skew_levels.map(|val| match val {
+                (x, None) => UpperLevels::warn(x),
+                (x, Some(y)) => UpperLevels::warn_crit(x, y),
+            }),
             body_matchers: args
                 .body_string
                 .into_iter()
@@ -214,8 +319,130 @@ fn make_configs(
                 (x, Some(y)) => LowerLevels::warn_crit(x, y),
             }),
             disable_certificate_verification: args.disable_certificate_verification,
+            html_matchers: args
+                .html_element
+                .into_iter()
+                .map(HtmlMatcher::Element)
+                .chain(args.html_title.into_iter().map(HtmlMatcher::Title))
+                .chain(
+                    html_element_texts
+                        .into_iter()
+                        .map(|(selector, text)| HtmlMatcher::ElementText(selector, text)),
+                )
+                .collect(),
+            link_scan: args.html_check_links.then_some(LinkScan {
+                max_depth: args.html_links_max_depth,
+                max_links: args.html_links_max_count,
+                max_parallel: args.html_links_parallelism.into(),
+                time_limit: args.html_links_time_limit,
+            }),
+            retry: (args.retries > 0).then(|| Retry {
+                retries: args.retries,
+                delay: args.retry_delay,
+                time_limit: args.retry_time_limit,
+                on: args
+                    .retry_on
+                    .iter()
+                    .map(|on| match on {
+                        cli::RetryOn::Connect => http::RetryOn::Connect,
+                        cli::RetryOn::Timeout => http::RetryOn::Timeout,
+                        cli::RetryOn::ServerError => http::RetryOn::ServerError,
+                    })
+                    .collect(),
+            }),
+            prometheus_series: args
+                .prometheus_series
+                .into_iter()
+                .map(|(selector, upper, lower)| SeriesCheck {
+                    selector,
+                    upper_levels: upper.map(|val| match val {
+                        (x, None) => UpperLevels::warn(x),
+                        (x, Some(y)) => UpperLevels::warn_crit(x, y),
+                    }),
+                    lower_levels: lower.map(|val| match val {
+                        (x, None) => LowerLevels::warn(x),
+                        (x, Some(y)) => LowerLevels::warn_crit(x, y),
+                    }),
+                })
+                .collect(),
+            json_schema: json_schema.map(|schema| (schema, map_state(&args.json_schema_state))),
+            graphql: args.graphql_query.is_some().then(|| GraphQlCheck {
+                errors_state: map_state(&args.graphql_errors_state),
+                expectations: args.graphql_expect,
+            }),
+            trend: args.state_dir.map(|dir| {
+                (
+                    Trend {
+                        state_file: trend::state_file(&dir, raw_args),
+                        max_runs: args.response_time_average_runs.into(),
+                    },
+                    TrendLevels {
+                        response_time_increase: args.response_time_increase.map(|val| match val {
+                            (x, None) => UpperLevels::warn(x),
+                            (x, Some(y)) => UpperLevels::warn_crit(x, y),
+                        }),
+                        certificate_change_state: args
+                            .certificate_change_state
+                            .as_ref()
+                            .map(map_state),
+                        body_change_state: args.body_change_state.as_ref().map(map_state),
+                        final_url_change_state: args.final_url_change_state.as_ref().map(map_state),
+                    },
+                )
+            }),
+            dual_stack: matches!(args.force_ip_version, Some(cli::ForceIP::Both)).then(|| {
+                DualStackLevels {
+                    difference_state: map_state(&args.ip_version_difference_state),
+                    response_time_difference: args.ip_version_response_time_difference.map(|val| {
+                        match val {
+                            (x, None) => UpperLevels::warn(x),
+                            (x, Some(y)) => UpperLevels::warn_crit(x, y),
+                        }
+                    }),
+                }
+            }),
+            har: args.har.map(|path| Har {
+                path,
+                max_body_size: args.har_max_body_size,
+            }),
+            tls_weak_cipher_state: map_state(&args.tls_weak_cipher_state),
+            tls_missing_stapling_state: args.tls_missing_stapling_state.as_ref().map(map_state),
+            state_mapping: StateMapping(
+                args.state_on
+                    .iter()
+                    .map(|(outcome, state)| (outcome.clone(), map_state(state)))
+                    .collect(),
+            ),
+        },
+    ))
+}
+
+fn load_json_schema(path: &std::path::Path) -> anyhow::Result<JSONSchema> {
+    let schema: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+    JSONSchema::compile(&schema).map_err(|err| anyhow::anyhow!("{}", err))
+}
+
+fn graphql_request_body(
+    query: &str,
+    variables: Option<&std::path::Path>,
+) -> anyhow::Result<String> {
+    let variables = match variables {
+        Some(path) => match serde_json::from_slice(&std::fs::read(path)?)? {
+            variables @ serde_json::Value::Object(_) => variables,
+            _ => anyhow::bail!("{} doesn't contain a JSON object", path.display()),
         },
-    )
+        None => serde_json::json!({}),
+    };
+    Ok(serde_json::json!({ "query": query, "variables": variables }).to_string())
+}
+
+fn map_state(state: &cli::CheckState) -> State {
+    match *state {
+        cli::CheckState::Ok => State::Ok,
+        cli::CheckState::Warning => State::Warn,
+        cli::CheckState::Critical => State::Crit,
+        cli::CheckState::Unknown => State::Unknown,
+    }
 }
 
 fn map_tls_version(tls_version: &cli::TlsVersion) -> TlsVersion {
@@ -0,0 +1,299 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// Parsing of the Prometheus text exposition format and its successor OpenMetrics.
+// We're only interested in the samples, so metadata like "# TYPE" and "# HELP" are skipped,
+// and histograms/summaries are just a bunch of samples with special suffixes/labels to us.
+
+use anyhow::{anyhow, bail, Result as AnyhowResult};
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+#[cfg_attr(test, derive(PartialEq))]
+#[derive(Debug)]
+pub struct Sample {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+}
+
+impl Display for Sample {
+    fn fmt(&self, f: &mut Formatter) -> FormatResult {
+        write!(f, "{}", self.name)?;
+        write_labels(f, &self.labels)
+    }
+}
+
+impl Sample {
+    /// Name of the sample suitable as Checkmk metric name, e.g.
+    /// http_requests_total{code="200",path="/"} -> http_requests_total_code_200_path__
+    pub fn metric_name(&self) -> String {
+        std::iter::once(self.name.as_str())
+            .chain(
+                self.labels
+                    .iter()
+                    .flat_map(|(name, value)| [name.as_str(), value.as_str()]),
+            )
+            .collect::<Vec<_>>()
+            .join("_")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SeriesSelector {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+}
+
+impl SeriesSelector {
+    pub fn matches(&self, sample: &Sample) -> bool {
+        self.name == sample.name
+            && self
+                .labels
+                .iter()
+                .all(|label| sample.labels.contains(label))
+    }
+}
+
+impl FromStr for SeriesSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.trim().char_indices().peekable();
+        let name = parse_name(&mut chars)?;
+        skip_whitespace(&mut chars);
+        let labels = parse_labels(&mut chars)?;
+        if let Some((pos, _)) = chars.next() {
+            bail!("Unexpected trailing characters at position {}", pos)
+        }
+        Ok(Self { name, labels })
+    }
+}
+
+impl Display for SeriesSelector {
+    fn fmt(&self, f: &mut Formatter) -> FormatResult {
+        write!(f, "{}", self.name)?;
+        write_labels(f, &self.labels)
+    }
+}
+
+fn write_labels(f: &mut Formatter, labels: &[(String, String)]) -> FormatResult {
+    if labels.is_empty() {
+        return Ok(());
+    }
+    write!(
+        f,
+        "{{{}}}",
+        labels
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, value))
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+pub fn parse(text: &str) -> AnyhowResult<Vec<Sample>> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| {
+            parse_sample(line).map_err(|err| anyhow!("line {}: {}", index + 1, err))
+        })
+        .collect()
+}
+
+fn parse_sample(line: &str) -> AnyhowResult<Sample> {
+    let mut chars = line.char_indices().peekable();
+    let name = parse_name(&mut chars)?;
+    let labels = parse_labels(&mut chars)?;
+    let rest = match chars.peek() {
+        Some((pos, ' ')) => &line[*pos..],
+        _ => bail!("missing value"),
+    };
+    // The value may be followed by a timestamp (and in OpenMetrics by an exemplar),
+    // which we don't need.
+    let value = rest
+        .split_whitespace()
+        .next()
+        .ok_or(anyhow!("missing value"))?;
+    Ok(Sample {
+        name,
+        labels,
+        value: parse_value(value)?,
+    })
+}
+
+fn parse_value(value: &str) -> AnyhowResult<f64> {
+    match value {
+        "+Inf" | "Inf" => Ok(f64::INFINITY),
+        "-Inf" => Ok(f64::NEG_INFINITY),
+        "NaN" => Ok(f64::NAN),
+        _ => value
+            .parse()
+            .map_err(|_| anyhow!("invalid value: {}", value)),
+    }
+}
+
+fn parse_name(chars: &mut Peekable<CharIndices>) -> AnyhowResult<String> {
+    let mut name = String::new();
+    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || "_:".contains(*c))
+    {
+        name.push(c);
+    }
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        bail!("invalid metric name")
+    }
+    Ok(name)
+}
+
+fn parse_labels(chars: &mut Peekable<CharIndices>) -> AnyhowResult<Vec<(String, String)>> {
+    let mut labels = vec![];
+    if chars.next_if(|(_, c)| *c == '{').is_none() {
+        return Ok(labels);
+    }
+    loop {
+        skip_whitespace(chars);
+        if chars.next_if(|(_, c)| *c == '}').is_some() {
+            return Ok(labels);
+        }
+        let name = parse_name(chars)?;
+        skip_whitespace(chars);
+        if chars.next_if(|(_, c)| *c == '=').is_none() {
+            bail!("missing '=' after label name {}", name)
+        }
+        skip_whitespace(chars);
+        labels.push((name, parse_quoted(chars)?));
+        skip_whitespace(chars);
+        match chars.next() {
+            Some((_, ',')) => continue,
+            Some((_, '}')) => return Ok(labels),
+            _ => bail!("unterminated label set"),
+        }
+    }
+}
+
+fn parse_quoted(chars: &mut Peekable<CharIndices>) -> AnyhowResult<String> {
+    if chars.next_if(|(_, c)| *c == '"').is_none() {
+        bail!("label value must be quoted")
+    }
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some((_, '"')) => return Ok(value),
+            Some((_, '\\')) => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            Some((_, c)) => value.push(c),
+            None => break,
+        }
+    }
+    bail!("unterminated label value")
+}
+
+fn skip_whitespace(chars: &mut Peekable<CharIndices>) {
+    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPOSITION: &str = r#"# HELP http_requests_total The total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+http_requests_total{method="post",code="400"}    3 1395066363000
+
+# Escaping in label values:
+msdos_file_access_time_seconds{path="C:\\DIR\\FILE.TXT",error="Cannot find file:\n\"FILE.TXT\""} 1.458255915e9
+
+# Minimalistic line:
+metric_without_timestamp_and_labels 12.47
+http_request_duration_seconds_bucket{le="+Inf"} 144320
+something_weird{problem="division by zero"} +Inf -3982045
+# EOF
+"#;
+
+    fn labels(labels: &[(&str, &str)]) -> Vec<(String, String)> {
+        labels
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let samples = parse(EXPOSITION).unwrap();
+        assert_eq!(samples.len(), 6);
+        assert_eq!(
+            samples[1],
+            Sample {
+                name: "http_requests_total".to_string(),
+                labels: labels(&[("method", "post"), ("code", "400")]),
+                value: 3.,
+            }
+        );
+        assert_eq!(
+            samples[2].labels,
+            labels(&[
+                ("path", r"C:\DIR\FILE.TXT"),
+                ("error", "Cannot find file:\n\"FILE.TXT\"")
+            ])
+        );
+        assert_eq!(samples[2].value, 1.458255915e9);
+        assert_eq!(samples[3].value, 12.47);
+        assert!(samples[3].labels.is_empty());
+        assert_eq!(samples[5].value, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(parse("<html>").is_err());
+        assert!(parse("foo{bar=\"baz\" 1").is_err());
+        assert!(parse("foo{bar=baz} 1").is_err());
+        assert!(parse("foo").is_err());
+        assert!(parse("foo bar").is_err());
+    }
+
+    #[test]
+    fn test_selector() {
+        let samples = parse(EXPOSITION).unwrap();
+        let selector = SeriesSelector::from_str(r#"http_requests_total{code="400"}"#).unwrap();
+        assert_eq!(
+            samples
+                .iter()
+                .filter(|s| selector.matches(s))
+                .collect::<Vec<_>>(),
+            vec![&samples[1]]
+        );
+        let selector = SeriesSelector::from_str("http_requests_total").unwrap();
+        assert_eq!(samples.iter().filter(|s| selector.matches(s)).count(), 2);
+        assert!(SeriesSelector::from_str("foo{").is_err());
+        assert!(SeriesSelector::from_str("foo bar").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let selector = SeriesSelector::from_str(r#"up { job = "api" , env="prod" }"#).unwrap();
+        assert_eq!(selector.to_string(), r#"up{job="api",env="prod"}"#);
+    }
+
+    #[test]
+    fn test_metric_name() {
+        let samples = parse(EXPOSITION).unwrap();
+        assert_eq!(
+            samples[0].metric_name(),
+            "http_requests_total_method_post_code_200"
+        );
+        assert_eq!(
+            samples[4].metric_name(),
+            "http_request_duration_seconds_bucket_le__Inf"
+        );
+    }
+}
@@ -2,16 +2,170 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
-use crate::checking_types::CheckResult;
+use crate::checking_types::{notice, CheckResult, State};
 use crate::checks::{self, CheckParameters, RequestInformation};
-use crate::http::{self, ClientConfig, RequestConfig};
+use crate::har::Har;
+use crate::http::{self, ClientConfig, ForceIP, ProcessedResponse, RequestConfig, Retry};
+use crate::trend::{History, Snapshot};
+use serde_json::Value;
+use std::time::Instant;
 
 pub async fn collect_checks(
-    client_cfg: ClientConfig,
+    mut client_cfg: ClientConfig,
     request_cfg: RequestConfig,
     request_information: RequestInformation,
-    check_params: CheckParameters,
+    mut check_params: CheckParameters,
+) -> Vec<CheckResult> {
+    let dual_stack = check_params.dual_stack.take();
+    let ipv6_client_cfg = if dual_stack.is_some() {
+        client_cfg.force_ip = Some(ForceIP::Ipv4);
+        Some(ClientConfig {
+            force_ip: Some(ForceIP::Ipv6),
+            // The TLS handshake and the proxy CONNECT are only reported for the regular
+            // (IPv4) request.
+            collect_handshake_info: false,
+            measure_proxy_connect: false,
+            ..client_cfg.clone()
+        })
+    } else {
+        None
+    };
+    let link_scan = check_params.link_scan.take().map(|scan| {
+        let client_cfg = ClientConfig {
+            collect_handshake_info: false,
+            measure_proxy_connect: false,
+            ..client_cfg.clone()
+        };
+        (scan, client_cfg, request_cfg.clone())
+    });
+    let retry = check_params.retry.take();
+    let trend = check_params.trend.take();
+    let har = check_params.har.take();
+
+    let ((response, attempts, mut har_entries), ipv6_response) = tokio::join!(
+        perform_request_with_retry(
+            client_cfg,
+            request_cfg.clone(),
+            retry.as_ref(),
+            har.as_ref()
+        ),
+        async {
+            match ipv6_client_cfg {
+                Some(cfg) => Some(
+                    perform_request_with_retry(cfg, request_cfg, retry.as_ref(), har.as_ref())
+                        .await,
+                ),
+                None => None,
+            }
+        }
+    );
+    let ipv6_response = ipv6_response.map(|(response, _, entries)| {
+        har_entries.extend(entries);
+        response
+    });
+
+    let har_check_results = match har {
+        Some(har) => match har.save(har_entries) {
+            Ok(()) => vec![],
+            Err(err) => file_error("write", "HAR file", &har.path, err),
+        },
+        None => vec![],
+    };
+
+    let link_check_results = match (link_scan, &response) {
+        (Some((scan, client_cfg, request_cfg)), Ok(resp)) => {
+            let links = http::html_links(resp);
+            let origin = resp.final_url.clone();
+            checks::collect_link_checks(
+                http::scan_links(client_cfg, request_cfg, origin, links, scan).await,
+            )
+        }
+        _ => vec![],
+    };
+
+    // Failed requests neither count as change nor make it into the history.
+    let trend_check_results = match (trend, &response) {
+        (Some((trend, levels)), Ok(resp)) => {
+            let snapshot = Snapshot::from_response(resp);
+            let (mut history, mut results) = match History::load(&trend.state_file) {
+                Ok(history) => (history, vec![]),
+                Err(err) => (
+                    History::default(),
+                    file_error("read", "state file", &trend.state_file, err),
+                ),
+            };
+            results.extend(checks::collect_trend_checks(&history, &snapshot, levels));
+            history.record(snapshot, trend.max_runs);
+            if let Err(err) = history.save(&trend.state_file) {
+                results.extend(file_error("write", "state file", &trend.state_file, err));
+            }
+            results
+        }
+        _ => vec![],
+    };
+
+    let dual_stack_check_results = match (dual_stack, ipv6_response) {
+        (Some(levels), Some(ipv6_response)) => {
+            checks::collect_dual_stack_checks(&response, &ipv6_response, levels, &check_params)
+        }
+        _ => vec![],
+    };
+
+    let mut check_results =
+        checks::collect_response_checks(response, request_information, check_params);
+    check_results.extend(dual_stack_check_results);
+    check_results.extend(link_check_results);
+    check_results.extend(trend_check_results);
+    check_results.extend(har_check_results);
+    if let Some(retry) = retry {
+        check_results.extend(checks::collect_attempt_checks(attempts, retry.retries + 1));
+    }
+    check_results
+}
+
+// The HAR entries of all attempts are collected, if requested.
+// A retry is only started if its delay and its timeout still fit into the time limit.
+async fn perform_request_with_retry(
+    client_cfg: ClientConfig,
+    request_cfg: RequestConfig,
+    retry: Option<&Retry>,
+    har: Option<&Har>,
+) -> (Result<ProcessedResponse, reqwest::Error>, usize, Vec<Value>) {
+    let start = Instant::now();
+    let mut attempts = 1;
+    let mut har_entries = vec![];
+    loop {
+        let (response, exchange) =
+            http::perform_recorded_request(client_cfg.clone(), request_cfg.clone()).await;
+        if let (Some(har), Some(exchange)) = (har, exchange) {
+            har_entries.extend(har.entries(&response, &exchange));
+        }
+        match retry {
+            Some(retry)
+                if attempts <= retry.retries
+                    && retry.applies_to(&response)
+                    && start.elapsed() + retry.delay(attempts) + client_cfg.timeout
+                        <= retry.time_limit =>
+            {
+                tokio::time::sleep(retry.delay(attempts)).await;
+                attempts += 1;
+            }
+            _ => return (response, attempts, har_entries),
+        }
+    }
+}
+
+fn file_error(
+    action: &str,
+    what: &str,
+    path: &std::path::Path,
+    err: anyhow::Error,
 ) -> Vec<CheckResult> {
-    let response = http::perform_request(client_cfg, request_cfg).await;
-    checks::collect_response_checks(response, request_information, check_params)
+    notice(
+        State::Unknown,
+        &format!("Cannot {} {} {}: {}", action, what, path.display(), err),
+    )
+    .into_iter()
+    .flatten()
+    .collect()
 }
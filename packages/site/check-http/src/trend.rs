@@ -0,0 +1,213 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+// Results of previous runs are kept in a small JSON file per check, so we can alert on changes
+// between runs. The file name is derived from the command line, i.e. every configured check
+// gets its own history, and changing the check's options starts a new one.
+
+use crate::http::ProcessedResponse;
+use anyhow::{anyhow, Result as AnyhowResult};
+use openssl::sha::sha256;
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use x509_parser::{certificate::X509Certificate, prelude::FromDer};
+
+pub struct Trend {
+    pub state_file: PathBuf,
+    pub max_runs: usize,
+}
+
+/// The characteristics of a response we compare between runs.
+#[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(Clone)]
+pub struct Snapshot {
+    pub response_time: f64,
+    pub body_hash: Option<String>,
+    pub certificate_serial: Option<String>,
+    pub final_url: String,
+}
+
+impl Snapshot {
+    pub fn from_response(response: &ProcessedResponse) -> Self {
+        let response_time = response.time_headers + response.time_body.unwrap_or_default();
+        Self {
+            response_time: response_time.as_secs_f64(),
+            body_hash: match &response.body {
+                Some(Ok(body)) => Some(hex(&sha256(body.text.as_bytes()))),
+                _ => None,
+            },
+            certificate_serial: response
+                .tls_info
+                .as_ref()
+                .and_then(|tls_info| tls_info.peer_certificate())
+                .and_then(|der| X509Certificate::from_der(der).ok())
+                .map(|(_, cert)| cert.raw_serial_as_string()),
+            final_url: response.final_url.to_string(),
+        }
+    }
+}
+
+#[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(Default)]
+pub struct History {
+    /// Response times of the previous runs, oldest first
+    pub response_times: Vec<f64>,
+    /// The snapshot of the previous run
+    pub previous: Option<Snapshot>,
+}
+
+impl History {
+    /// Load the history, starting a new one if there is no state file yet.
+    pub fn load(path: &Path) -> AnyhowResult<Self> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+        Self::from_json(&serde_json::from_slice(&content)?)
+            .ok_or_else(|| anyhow!("Unexpected content"))
+    }
+
+    /// Save the history, replacing the state file atomically.
+    pub fn save(&self, path: &Path) -> AnyhowResult<()> {
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, self.to_json().to_string())?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn record(&mut self, snapshot: Snapshot, max_runs: usize) {
+        self.response_times.push(snapshot.response_time);
+        let excess = self.response_times.len().saturating_sub(max_runs);
+        self.response_times.drain(..excess);
+        self.previous = Some(snapshot);
+    }
+
+    pub fn average_response_time(&self) -> Option<f64> {
+        if self.response_times.is_empty() {
+            return None;
+        }
+        Some(self.response_times.iter().sum::<f64>() / self.response_times.len() as f64)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "response_times": self.response_times,
+            "previous": self.previous.as_ref().map(|previous| json!({
+                "response_time": previous.response_time,
+                "body_hash": previous.body_hash,
+                "certificate_serial": previous.certificate_serial,
+                "final_url": previous.final_url,
+            })),
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let optional_string = |value: &Value, key: &str| value.get(key)?.as_str().map(String::from);
+        let previous = match value.get("previous")? {
+            Value::Null => None,
+            previous => Some(Snapshot {
+                response_time: previous.get("response_time")?.as_f64()?,
+                body_hash: optional_string(previous, "body_hash"),
+                certificate_serial: optional_string(previous, "certificate_serial"),
+                final_url: optional_string(previous, "final_url")?,
+            }),
+        };
+        Some(Self {
+            response_times: value
+                .get("response_times")?
+                .as_array()?
+                .iter()
+                .map(Value::as_f64)
+                .collect::<Option<_>>()?,
+            previous,
+        })
+    }
+}
+
+/// Path of the state file for the check with the given command line arguments.
+pub fn state_file(dir: &Path, args: &[String]) -> PathBuf {
+    dir.join(format!(
+        "check_httpv2_{}.json",
+        hex(&sha256(args.join("\0").as_bytes()))
+    ))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{:02x}", b);
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(response_time: f64) -> Snapshot {
+        Snapshot {
+            response_time,
+            body_hash: Some("abc".to_string()),
+            certificate_serial: None,
+            final_url: "https://example.com/".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let mut history = History::default();
+        assert_eq!(history.average_response_time(), None);
+        for response_time in [1., 2., 3., 4.] {
+            history.record(snapshot(response_time), 3);
+        }
+        assert_eq!(history.response_times, vec![2., 3., 4.]);
+        assert_eq!(history.average_response_time(), Some(3.));
+        assert_eq!(history.previous, Some(snapshot(4.)));
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut history = History::default();
+        assert_eq!(
+            History::from_json(&history.to_json()),
+            Some(History::default())
+        );
+        history.record(snapshot(0.5), 10);
+        assert_eq!(History::from_json(&history.to_json()), Some(history));
+        assert_eq!(History::from_json(&json!({"response_times": ["x"]})), None);
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let path = std::env::temp_dir().join(format!("check_httpv2_test_{}", std::process::id()));
+        assert_eq!(History::load(&path).unwrap(), History::default());
+        let mut history = History::default();
+        history.record(snapshot(0.5), 10);
+        history.save(&path).unwrap();
+        assert_eq!(History::load(&path).unwrap(), history);
+        std::fs::write(&path, "garbage").unwrap();
+        assert!(History::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_state_file() {
+        let dir = Path::new("/tmp");
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            state_file(dir, &args(&["-u", "https://example.com"])),
+            state_file(dir, &args(&["-u", "https://example.com"]))
+        );
+        assert_ne!(
+            state_file(dir, &args(&["-u", "https://example.com"])),
+            state_file(dir, &args(&["-u", "https://example.org"]))
+        );
+        assert_ne!(
+            state_file(dir, &args(&["-u a", "b"])),
+            state_file(dir, &args(&["-u", "a b"]))
+        );
+    }
+}
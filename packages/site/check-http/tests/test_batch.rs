@@ -0,0 +1,141 @@
+// Copyright (C) 2023 Checkmk GmbH - License: GNU General Public License v2
+// This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
+// conditions defined in the file COPYING, which is part of this source code package.
+
+use anyhow::Result as AnyhowResult;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const CHECK_HTTP: &str = env!("CARGO_BIN_EXE_check_http");
+
+#[test]
+fn test_batch() -> AnyhowResult<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://127.0.0.1:{}", listener.local_addr()?.port());
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let server = {
+        let (running, max_running) = (running.clone(), max_running.clone());
+        thread::spawn(move || serve(listener, 5, running, max_running))
+    };
+
+    let definitions = [
+        json!({"id": "a", "args": ["--url", format!("{}/a", url)]}),
+        json!({"id": "b", "args": ["--url", format!("{}/b", url)]}),
+        json!({"id": 3, "args": ["--url", format!("{}/c", url)]}),
+        json!({"args": ["--url", format!("{}/unavailable", url)]}),
+        json!({"id": "e", "args": ["--url", format!("{}/e", url)]}),
+        json!({"id": "invalid", "args": ["--timeout", "1"]}),
+    ];
+    let input = definitions
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let output = run_batch(&["--parallelism", "2"], &input)?;
+    server.join().unwrap()?;
+
+    let records: HashMap<String, Value> = output
+        .lines()
+        .map(|line| {
+            let record: Value = serde_json::from_str(line).unwrap();
+            (record["id"].to_string(), record)
+        })
+        .collect();
+    assert_eq!(records.len(), definitions.len());
+    for id in [r#""a""#, r#""b""#, "3", r#""e""#] {
+        assert_eq!(records[id]["state"], json!(0));
+        assert!(records[id]["output"]
+            .as_str()
+            .unwrap()
+            .starts_with("Version: HTTP/1.1, Status: 200 OK"));
+    }
+    // Without an id, the line number is used.
+    assert_eq!(records["4"]["state"], json!(2));
+    assert!(records["4"]["output"]
+        .as_str()
+        .unwrap()
+        .contains("Status: 503 Service Unavailable"));
+    assert_eq!(records[r#""invalid""#]["state"], json!(3));
+
+    assert_eq!(max_running.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_invalid_input() -> AnyhowResult<()> {
+    let output = run_batch(&[], "\ngarbage\n")?;
+    assert_eq!(
+        serde_json::from_str::<Value>(output.trim())?,
+        json!({
+            "id": 2,
+            "state": 3,
+            "output": "Invalid check definition: expected value at line 1 column 1"
+        })
+    );
+    Ok(())
+}
+
+fn run_batch(args: &[&str], input: &str) -> AnyhowResult<String> {
+    let mut child = Command::new(CHECK_HTTP)
+        .args(["--batch", "-"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(input.as_bytes())?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+// Answers every request after a short delay, so concurrent checks overlap,
+// and records the maximal number of requests in flight.
+fn serve(
+    listener: TcpListener,
+    connections: usize,
+    running: Arc<AtomicUsize>,
+    max_running: Arc<AtomicUsize>,
+) -> AnyhowResult<()> {
+    let handlers = listener
+        .incoming()
+        .take(connections)
+        .map(|stream| {
+            let (running, max_running) = (running.clone(), max_running.clone());
+            let stream = stream?;
+            Ok(thread::spawn(move || {
+                max_running.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                let result = respond(stream);
+                running.fetch_sub(1, Ordering::SeqCst);
+                result
+            }))
+        })
+        .collect::<AnyhowResult<Vec<_>>>()?;
+    for handler in handlers {
+        handler.join().unwrap()?;
+    }
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream) -> AnyhowResult<()> {
+    let mut buffer: [u8; 1024] = [0; 1024];
+    let len = stream.read(&mut buffer)?;
+    let request = String::from_utf8_lossy(&buffer[..len]);
+    thread::sleep(Duration::from_millis(300));
+    let status = if request.starts_with("GET /unavailable ") {
+        "503 Service Unavailable"
+    } else {
+        "200 OK"
+    };
+    stream.write_all(format!("HTTP/1.1 {}\nConnection: close\n\n", status).as_bytes())?;
+    stream.shutdown(std::net::Shutdown::Both)?;
+    Ok(())
+}
@@ -3,17 +3,17 @@
 // conditions defined in the file COPYING, which is part of this source code package.
 
 use anyhow::Result as AnyhowResult;
-use check_http::checking_types::State;
-use check_http::checks::{CheckParameters, RequestInformation};
-use check_http::http::{ClientConfig, OnRedirect, RequestConfig};
+use check_http::checking_types::{State, UpperLevels};
+use check_http::checks::{CheckParameters, RequestInformation, StateMapping};
+use check_http::http::{ClientConfig, LinkScan, OnRedirect, RequestConfig, Retry, RetryOn};
 use check_http::output::Output;
 use check_http::runner::collect_checks;
-use reqwest::{Method, Url};
+use reqwest::{header::HeaderValue, Method, Url};
 
 use std::io::{Read, Write};
 use std::net::TcpListener;
 use std::sync::atomic;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 const START_PORT: u16 = 8888;
 const MAX_PORTS: u16 = 100;
@@ -43,6 +43,360 @@ async fn test_status_4xx() -> AnyhowResult<()> {
     .await
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_retry_on_server_error() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("0.0.0.0");
+    let (client_cfg, request_cfg, request_information, mut check_params) =
+        make_standard_configs(port);
+    check_params.retry = Some(Retry {
+        retries: 2,
+        delay: Duration::from_millis(10),
+        on: vec![RetryOn::ServerError],
+        time_limit: Duration::from_secs(60),
+    });
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    process_http(
+        &listener,
+        "HTTP/1.1 503 Service Unavailable\nConnection: close\n\n",
+    )?;
+    process_http(&listener, "HTTP/1.1 200 OK\nConnection: close\n\n")?;
+
+    let output = Output::from_check_results(check_http_thread.await?);
+
+    assert!(output.worst_state == State::Ok);
+    assert!(output.to_string().contains("Attempts: 2 of 3"));
+    assert!(output.to_string().contains("attempts=2;;;1;3"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_retry_on_connection_reset() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("0.0.0.0");
+    let (client_cfg, request_cfg, request_information, mut check_params) =
+        make_standard_configs(port);
+    check_params.retry = Some(Retry {
+        retries: 2,
+        delay: Duration::from_millis(10),
+        on: vec![RetryOn::Connect],
+        time_limit: Duration::from_secs(60),
+    });
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    let (mut stream, _addr) = listener.accept()?;
+    let _request = stream.read(&mut [0; 1024])?;
+    stream.set_nonblocking(true)?;
+    // Closing with a zero linger time sends a reset instead of a FIN.
+    tokio::net::TcpStream::from_std(stream)?.set_linger(Some(Duration::ZERO))?;
+    process_http(&listener, "HTTP/1.1 200 OK\nConnection: close\n\n")?;
+
+    let output = Output::from_check_results(check_http_thread.await?);
+
+    assert!(output.worst_state == State::Ok);
+    assert!(output.to_string().contains("Attempts: 2 of 3"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_retry_time_limit() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("0.0.0.0");
+    let (client_cfg, request_cfg, request_information, mut check_params) =
+        make_standard_configs(port);
+    // With a timeout of 1s, there is room for the first retry only:
+    // 0.5s + 1s fit into the limit, 0.5s + 1s + 1s for the second retry don't.
+    check_params.retry = Some(Retry {
+        retries: 5,
+        delay: Duration::from_millis(500),
+        on: vec![RetryOn::ServerError],
+        time_limit: Duration::from_millis(2200),
+    });
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    for _ in 0..2 {
+        process_http(
+            &listener,
+            "HTTP/1.1 503 Service Unavailable\nConnection: close\n\n",
+        )?;
+    }
+
+    let output = Output::from_check_results(check_http_thread.await?);
+
+    assert!(output.worst_state == State::Crit);
+    assert!(output.to_string().contains("Attempts: 2 of 6"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_header() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("0.0.0.0");
+    let (client_cfg, mut request_cfg, request_information, check_params) =
+        make_standard_configs(port);
+    request_cfg.host_header = Some(HeaderValue::from_static("vhost.example.com"));
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    let check_http_payload = process_http(&listener, "HTTP/1.1 200 OK\nConnection: close\n\n")?;
+    check_http_thread.await?;
+
+    assert!(check_http_payload.contains("host: vhost.example.com\r\n"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sni_name() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("127.0.0.1");
+    let (mut client_cfg, mut request_cfg, mut request_information, check_params) =
+        make_standard_configs(port);
+    let url = Url::parse(&format!("http://127.0.0.1:{}", port))?;
+    client_cfg.url = url.clone();
+    client_cfg.sni_name = Some("virtual.invalid".to_string());
+    request_cfg.url = url.clone();
+    request_information.request_url = url;
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    // The SNI name is resolved to the host of the URL, which stays in the Host header.
+    let check_http_payload = process_http(&listener, "HTTP/1.1 200 OK\nConnection: close\n\n")?;
+    let output = Output::from_check_results(check_http_thread.await?);
+
+    assert!(check_http_payload.contains(&format!("host: 127.0.0.1:{}\r\n", port)));
+    assert!(output.worst_state == State::Ok);
+    assert!(!output.to_string().contains("Followed redirect"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sni_name_unresolvable_host() -> AnyhowResult<()> {
+    let (port, _listener) = tcp_listener("127.0.0.1");
+    let (mut client_cfg, mut request_cfg, mut request_information, check_params) =
+        make_standard_configs(port);
+    let url = Url::parse("https://nonexistent.invalid/")?;
+    client_cfg.url = url.clone();
+    client_cfg.sni_name = Some("virtual.invalid".to_string());
+    request_cfg.url = url.clone();
+    request_information.request_url = url;
+
+    let output = Output::from_check_results(
+        collect_checks(client_cfg, request_cfg, request_information, check_params).await,
+    );
+
+    assert!(output.worst_state == State::Crit);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sni_name_via_proxy() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("127.0.0.1");
+    let (mut client_cfg, mut request_cfg, mut request_information, check_params) =
+        make_standard_configs(port);
+    let url = Url::parse("https://www.example.com/")?;
+    client_cfg.url = url.clone();
+    client_cfg.sni_name = Some("virtual.example.com".to_string());
+    client_cfg.proxy_url = Some(format!("http://127.0.0.1:{}", port));
+    request_cfg.url = url.clone();
+    request_information.request_url = url;
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    // The proxy is asked for the host of the URL, not for the SNI name.
+    let proxy_payload = process_http(&listener, "HTTP/1.1 403 Forbidden\r\n\r\n")?;
+    let output = Output::from_check_results(check_http_thread.await?);
+
+    assert!(proxy_payload.starts_with("CONNECT www.example.com:443 HTTP/1.1\r\n"));
+    assert!(proxy_payload.contains("\r\nHost: www.example.com:443\r\n"));
+    assert!(output.worst_state == State::Crit);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_proxy_with_and_without_relay() -> AnyhowResult<()> {
+    // Measuring the CONNECT routes it through the relay, which must not change what the
+    // proxy gets to see.
+    for measure_proxy_connect in [true, false] {
+        let (port, listener) = tcp_listener("127.0.0.1");
+        let (mut client_cfg, mut request_cfg, mut request_information, check_params) =
+            make_standard_configs(port);
+        let url = Url::parse("https://www.example.com/")?;
+        client_cfg.url = url.clone();
+        client_cfg.proxy_url = Some(format!("http://127.0.0.1:{}", port));
+        client_cfg.measure_proxy_connect = measure_proxy_connect;
+        request_cfg.url = url.clone();
+        request_information.request_url = url;
+
+        let check_http_thread = tokio::spawn(collect_checks(
+            client_cfg,
+            request_cfg,
+            request_information,
+            check_params,
+        ));
+
+        let proxy_payload = process_http(&listener, "HTTP/1.1 403 Forbidden\r\n\r\n")?;
+        let output = Output::from_check_results(check_http_thread.await?);
+
+        assert!(proxy_payload.starts_with("CONNECT www.example.com:443 HTTP/1.1\r\n"));
+        assert!(proxy_payload.contains("\r\nHost: www.example.com:443\r\n"));
+        assert!(output.worst_state == State::Crit);
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_clock_skew_with_slow_body() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("0.0.0.0");
+    let (mut client_cfg, request_cfg, mut request_information, mut check_params) =
+        make_standard_configs(port);
+    client_cfg.timeout = Duration::from_secs(10);
+    request_information.timeout = Duration::from_secs(10);
+    check_params.clock_skew_levels = Some(UpperLevels::warn_crit(2, 2));
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    // The Date header is accurate, but the body only follows after a while.
+    let (mut stream, _addr) = listener.accept()?;
+    let mut buffer: [u8; 1024] = [0; 1024];
+    let _ = stream.read(&mut buffer)?;
+    let headers = format!(
+        "HTTP/1.1 200 OK\nConnection: close\nContent-Length: 4\nDate: {}\n\n",
+        httpdate::fmt_http_date(SystemTime::now())
+    );
+    stream.write_all(headers.as_bytes())?;
+    stream.flush()?;
+    std::thread::sleep(Duration::from_secs(4));
+    stream.write_all(b"body")?;
+    stream.shutdown(std::net::Shutdown::Both)?;
+
+    let output = Output::from_check_results(check_http_thread.await?);
+    assert!(output.worst_state == State::Ok);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_link_scan_time_limit() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("0.0.0.0");
+    let (mut client_cfg, request_cfg, request_information, mut check_params) =
+        make_standard_configs(port);
+    client_cfg.timeout = Duration::from_secs(10);
+    check_params.link_scan = Some(LinkScan {
+        max_depth: 1,
+        max_links: 10,
+        max_parallel: 2,
+        time_limit: Duration::from_secs(1),
+    });
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    process_http(
+        &listener,
+        "HTTP/1.1 200 OK\nConnection: close\nContent-Type: text/html\n\n\
+         <a href=\"/slow\">slow</a><a href=\"/missing\">missing</a>",
+    )?;
+    // Both links are requested at once. /slow never answers, /missing does right away.
+    let mut slow = None;
+    for _ in 0..2 {
+        let (mut stream, _addr) = listener.accept()?;
+        let mut buffer: [u8; 1024] = [0; 1024];
+        let len = stream.read(&mut buffer)?;
+        if buffer[..len].starts_with(b"GET /slow ") {
+            slow = Some(stream);
+        } else {
+            stream.write_all(b"HTTP/1.1 404 Not Found\nConnection: close\n\n")?;
+            stream.shutdown(std::net::Shutdown::Both)?;
+        }
+    }
+
+    let output = Output::from_check_results(check_http_thread.await?).to_string();
+    drop(slow);
+    assert!(output.contains("Broken links: 1 of 1 checked"));
+    assert!(output.contains("Broken link: http://localhost:"));
+    assert!(output.contains("Link scan stopped at its time limit, remaining links not checked"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_link_scan_only_html() -> AnyhowResult<()> {
+    let (port, listener) = tcp_listener("0.0.0.0");
+    let (client_cfg, request_cfg, request_information, mut check_params) =
+        make_standard_configs(port);
+    check_params.link_scan = Some(LinkScan {
+        max_depth: 1,
+        max_links: 10,
+        max_parallel: 2,
+        time_limit: Duration::from_secs(1),
+    });
+
+    let check_http_thread = tokio::spawn(collect_checks(
+        client_cfg,
+        request_cfg,
+        request_information,
+        check_params,
+    ));
+
+    process_http(
+        &listener,
+        "HTTP/1.1 200 OK\nConnection: close\nContent-Type: text/plain\n\n\
+         <a href=\"/missing\">missing</a>",
+    )?;
+
+    let output = Output::from_check_results(check_http_thread.await?);
+    assert!(output.worst_state == State::Ok);
+    assert!(output.to_string().contains("Links checked: 0, none broken"));
+
+    Ok(())
+}
+
 async fn check_http_output(
     http_response: &str,
     expected_http_payload_start: &str,
@@ -59,7 +413,7 @@ async fn check_http_output(
         check_params,
     ));
 
-    let check_http_payload = process_http(listener, http_response)?;
+    let check_http_payload = process_http(&listener, http_response)?;
 
     let output = Output::from_check_results(check_http_thread.await?);
 
@@ -90,12 +444,18 @@ fn make_standard_configs(
             min_tls_version: None,
             max_tls_version: None,
             collect_tls_info: false,
+            collect_handshake_info: false,
+            measure_proxy_connect: false,
             ignore_proxy_env: false,
             proxy_url: None,
             proxy_auth: None,
+            proxy_pac: None,
+            no_proxy: None,
             disable_certificate_verification: false,
             url: url.clone(),
             server: None,
+            sni_name: None,
+            disable_sni: false,
         },
         RequestConfig {
             url: url.clone(),
@@ -108,6 +468,7 @@ fn make_standard_configs(
             auth_pw: None,
             without_body: false,
             token_auth: None,
+            host_header: None,
         },
         RequestInformation {
             request_url: url,
@@ -122,15 +483,28 @@ fn make_standard_configs(
             page_size: None,
             response_time_levels: None,
             document_age_levels: None,
+            clock_skew_levels: None,
             body_matchers: vec![],
             header_matchers: vec![],
             certificate_levels: None,
             disable_certificate_verification: false,
+            html_matchers: vec![],
+            link_scan: None,
+            retry: None,
+            prometheus_series: vec![],
+            json_schema: None,
+            graphql: None,
+            trend: None,
+            dual_stack: None,
+            har: None,
+            tls_weak_cipher_state: State::Warn,
+            tls_missing_stapling_state: None,
+            state_mapping: StateMapping::default(),
         },
     )
 }
 
-fn process_http(listener: TcpListener, send_response: &str) -> AnyhowResult<String> {
+fn process_http(listener: &TcpListener, send_response: &str) -> AnyhowResult<String> {
     let (mut stream, _addr) = listener.accept()?;
     let mut buffer: [u8; 1024] = [0; 1024];
     let len = stream.read(&mut buffer)?;
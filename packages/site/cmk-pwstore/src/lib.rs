@@ -2,6 +2,9 @@
 // This file is part of Checkmk (https://checkmk.com). It is subject to the terms and
 // conditions defined in the file COPYING, which is part of this source code package.
 
+//! Read passwords from the password store of the site, for the `--*-pwstore` options of the
+//! active checks.
+
 use aes_gcm::{
     aead::{Aead, KeyInit, Result as AeadResult},
     aes::{cipher::consts::U16, Aes256},
@@ -50,6 +53,9 @@ fn load_pw_store_bytes(pwstore_path: &str) -> AnyhowResult<(Vec<u8>, Vec<u8>)> {
 }
 
 fn unpack_pw_store(pw_store_bytes: &[u8], secret: &[u8]) -> AnyhowResult<String> {
+    if pw_store_bytes.len() < VERSION_LENGTH + SALT_LENGTH + NONCE_LENGTH + TAG_LENGTH {
+        return Err(anyhow!("Can't parse password store: File too short."));
+    }
     let (salt, nonce, tag, ciphertext) = unpack_cipher_contents(pw_store_bytes);
     let key = derive_key(secret, salt)?;
     let plaintext = decrypt(ciphertext, &key, nonce, tag)?;
@@ -101,7 +107,7 @@ fn lookup_pw(pw_store: &str, pw_id: &str) -> AnyhowResult<String> {
 
 #[cfg(test)]
 mod test_pw_store {
-    use crate::pwstore::{lookup_pw, unpack_pw_store};
+    use super::{lookup_pw, unpack_pw_store};
     use std::num::ParseIntError;
 
     fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
@@ -135,6 +141,8 @@ mod test_pw_store {
         let output = String::from("Time is an illusion. Lunchtime doubly so.");
 
         assert_eq!(unpack_pw_store(&input, secret).unwrap(), output);
+        assert!(unpack_pw_store(&input, b"wrong-secret").is_err());
+        assert!(unpack_pw_store(&input[..20], secret).is_err());
     }
 
     #[test]